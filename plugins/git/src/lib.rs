@@ -2,79 +2,57 @@
 #[macro_use]
 extern crate log;
 
+mod repository;
+mod status;
+
+pub use repository::GitRepository;
+pub use status::{GitStatus, GitSymbols, Head, Operation};
+
 use log::info;
-use std::path::Path;
-use std::process::{Command, Stdio};
+use std::path::{Path, PathBuf};
 
 pub struct GitCli {}
 
+// Shortcuts for the repository containing the working directory, reading
+// the same `GitStatus` as the command bar
 impl GitCli {
+    // Like `git rev-parse --abbrev-ref HEAD`, including its newline, empty
+    // outside of a repository
     pub fn get_current_branch() -> String {
-        // match Branch::get().name() {
-        //     Ok(branch) => branch,
-        //     Err(_) => None,
-        // };
         info!("Get Current branch");
 
-        if !GitCli::is_git_dir() {
-            return String::new();
-        }
-
-        match Command::new("git")
-            .args(&["rev-parse", "--abbrev-ref", "HEAD"])
-            .output()
-        {
-            Ok(output) => String::from_utf8(output.stdout).expect("Found invalid UTF-8"),
-            Err(_) => String::from(""),
+        match GitCli::status() {
+            Some(GitStatus {
+                head: Head::Branch(name),
+                ..
+            }) => format!("{}\n", name),
+            Some(_) => String::from("HEAD\n"),
+            None => String::new(),
         }
     }
 
-    pub fn no_upstream_commits<'a>() -> String {
+    // Number of commits not pushed to the upstream, empty outside of a
+    // repository
+    pub fn no_upstream_commits() -> String {
         info!("Get upstream commits");
-        match Command::new("git")
-            .args(&["cherry"])
-            .stdout(Stdio::piped())
-            .spawn()
-        {
-            Ok(git_call) => {
-                let wc = Command::new("wc")
-                    .args(&["-l"])
-                    .stdin(git_call.stdout.unwrap())
-                    .output()
-                    .unwrap();
-
-                String::from_utf8(wc.stdout)
-                    .expect("Found invalid UTF-8")
-                    .trim()
-                    .to_string()
-            }
-            Err(_) => String::from(""),
-        }
-    }
 
-    fn is_git_dir() -> bool {
-        Path::new("./.git").exists()
+        GitCli::status()
+            .map(|status| status.ahead.to_string())
+            .unwrap_or_default()
     }
 
-    pub fn compose_git_component() -> String {
-        if !GitCli::is_git_dir() {
-            return String::new();
-        }
-        let mut comps: Vec<String> = vec![];
-
-        comps.push(GitCli::get_current_branch().trim_end().to_string());
-
-        let us = GitCli::no_upstream_commits();
-        if us != String::from("0") {
-            comps.push(format!(" ⇡{}", us));
-        }
-
-        comps.join("")
+    fn status() -> Option<GitStatus> {
+        GitRepository::discover(".").map(|mut repository| repository.status())
     }
 
-    // fn number_untracked_files() -> String {}
-
-    // fn number_modified_files() -> String {}
+    // The closest directory containing `.git`, starting at `dir` and walking
+    // up the parent directories
+    pub fn find_git_root<P: AsRef<Path>>(dir: P) -> Option<PathBuf> {
+        dir.as_ref()
+            .ancestors()
+            .find(|dir| dir.join(".git").exists())
+            .map(PathBuf::from)
+    }
 }
 
 #[cfg(test)]
@@ -88,6 +66,18 @@ mod tests {
         assert_eq!(result, "master\n");
     }
 
+    #[test]
+    fn test_find_git_root() {
+        let temp = tempfile::tempdir().unwrap();
+        let root = temp.path().to_path_buf();
+        let sub = root.join("src").join("cli");
+        std::fs::create_dir_all(&sub).unwrap();
+        std::fs::create_dir(root.join(".git")).unwrap();
+
+        assert_eq!(GitCli::find_git_root(&sub), Some(root.clone()));
+        assert_eq!(GitCli::find_git_root(&root), Some(root.clone()));
+    }
+
     #[test]
     fn it_works1() {
         let res = GitCli::no_upstream_commits();
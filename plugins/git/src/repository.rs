@@ -0,0 +1,199 @@
+// Enumerates the objects of a repository, used as completion sources by
+// the shell (`git checkout <branch>`, `git add <modified file>`, ...)
+//
+// Every query returns an empty list instead of an error, a completion
+// should never fail loudly.
+
+use std::env;
+use std::path::{Path, PathBuf};
+
+use git2::{BranchType, Repository, Status, StatusOptions};
+
+use crate::status::GitStatus;
+
+pub struct GitRepository {
+    repo: Repository,
+}
+
+impl GitRepository {
+    // The repository containing `path`, searching the parent directories
+    pub fn discover<P: AsRef<Path>>(path: P) -> Option<Self> {
+        Repository::discover(path)
+            .ok()
+            .map(|repo| GitRepository { repo })
+    }
+
+    pub fn status(&mut self) -> GitStatus {
+        GitStatus::read(&mut self.repo)
+    }
+
+    pub fn local_branches(&self) -> Vec<String> {
+        self.branches(BranchType::Local)
+    }
+
+    // `origin/master`, without the symbolic `origin/HEAD`
+    pub fn remote_branches(&self) -> Vec<String> {
+        self.branches(BranchType::Remote)
+            .into_iter()
+            .filter(|name| !name.ends_with("/HEAD"))
+            .collect()
+    }
+
+    fn branches(&self, branch_type: BranchType) -> Vec<String> {
+        let branches = match self.repo.branches(Some(branch_type)) {
+            Ok(branches) => branches,
+            Err(_) => return Vec::new(),
+        };
+
+        let mut names: Vec<String> = branches
+            .filter_map(Result::ok)
+            .filter_map(|(branch, _)| branch.name().ok().flatten().map(String::from))
+            .collect();
+        names.sort();
+        names
+    }
+
+    pub fn remotes(&self) -> Vec<String> {
+        match self.repo.remotes() {
+            Ok(remotes) => remotes.iter().flatten().map(String::from).collect(),
+            Err(_) => Vec::new(),
+        }
+    }
+
+    pub fn tags(&self) -> Vec<String> {
+        match self.repo.tag_names(None) {
+            Ok(tags) => tags.iter().flatten().map(String::from).collect(),
+            Err(_) => Vec::new(),
+        }
+    }
+
+    // `stash@{0}`, `stash@{1}`, ... newest first
+    pub fn stashes(&mut self) -> Vec<String> {
+        let mut stashes = Vec::new();
+
+        let _ = self.repo.stash_foreach(|index, _, _| {
+            stashes.push(format!("stash@{{{}}}", index));
+            true
+        });
+
+        stashes
+    }
+
+    // Files with changes in the working tree which are not staged yet
+    pub fn modified_files(&self) -> Vec<String> {
+        self.files_with_status(
+            Status::WT_MODIFIED | Status::WT_DELETED | Status::WT_TYPECHANGE | Status::WT_RENAMED,
+            &current_dir(),
+        )
+    }
+
+    // Files with changes in the index
+    pub fn staged_files(&self) -> Vec<String> {
+        self.files_with_status(
+            Status::INDEX_NEW
+                | Status::INDEX_MODIFIED
+                | Status::INDEX_DELETED
+                | Status::INDEX_TYPECHANGE
+                | Status::INDEX_RENAMED,
+            &current_dir(),
+        )
+    }
+
+    pub fn untracked_files(&self) -> Vec<String> {
+        self.files_with_status(Status::WT_NEW, &current_dir())
+    }
+
+    // Paths are relative to `current_dir`, files outside of it are left out
+    fn files_with_status(&self, status: Status, current_dir: &Path) -> Vec<String> {
+        let workdir = match self.repo.workdir() {
+            Some(workdir) => workdir,
+            None => return Vec::new(),
+        };
+
+        let mut options = StatusOptions::new();
+        options
+            .include_untracked(true)
+            .recurse_untracked_dirs(true)
+            .include_ignored(false);
+
+        let statuses = match self.repo.statuses(Some(&mut options)) {
+            Ok(statuses) => statuses,
+            Err(_) => return Vec::new(),
+        };
+
+        let workdir = workdir
+            .canonicalize()
+            .unwrap_or_else(|_| workdir.to_path_buf());
+
+        statuses
+            .iter()
+            .filter(|entry| entry.status().intersects(status))
+            .filter_map(|entry| entry.path().map(|path| workdir.join(path)))
+            .filter_map(|path| relative_to(&path, current_dir))
+            .collect()
+    }
+}
+
+fn current_dir() -> PathBuf {
+    env::current_dir()
+        .and_then(|dir| dir.canonicalize())
+        .unwrap_or_default()
+}
+
+fn relative_to(path: &Path, dir: &Path) -> Option<String> {
+    path.strip_prefix(dir)
+        .ok()
+        .map(PathBuf::from)
+        .and_then(|path| path.to_str().map(String::from))
+}
+
+#[cfg(test)]
+mod tests {
+
+    use super::*;
+    use std::fs;
+
+    #[test]
+    fn test_enumerate_repository() {
+        let temp = tempfile::tempdir().unwrap();
+        let dir = temp.path().to_path_buf();
+        let repo = Repository::init(&dir).unwrap();
+
+        fs::write(dir.join("committed.txt"), "a").unwrap();
+        let mut index = repo.index().unwrap();
+        index.add_path(Path::new("committed.txt")).unwrap();
+        index.write().unwrap();
+        let tree = repo.find_tree(index.write_tree().unwrap()).unwrap();
+        let signature = git2::Signature::now("civa", "civa@example.com").unwrap();
+        let commit = repo
+            .commit(Some("HEAD"), &signature, &signature, "init", &tree, &[])
+            .unwrap();
+        let commit = repo.find_commit(commit).unwrap();
+        repo.branch("feature", &commit, false).unwrap();
+        repo.tag_lightweight("v1.0", commit.as_object(), false)
+            .unwrap();
+
+        fs::write(dir.join("committed.txt"), "changed").unwrap();
+        fs::write(dir.join("untracked.txt"), "c").unwrap();
+
+        let repository = GitRepository::discover(&dir).unwrap();
+        let dir = dir.canonicalize().unwrap();
+
+        assert!(repository
+            .local_branches()
+            .contains(&String::from("feature")));
+        assert_eq!(repository.tags(), vec![String::from("v1.0")]);
+        assert_eq!(
+            repository.files_with_status(Status::WT_MODIFIED, &dir),
+            vec![String::from("committed.txt")]
+        );
+        assert_eq!(
+            repository.files_with_status(Status::WT_NEW, &dir),
+            vec![String::from("untracked.txt")]
+        );
+        assert!(repository
+            .files_with_status(Status::WT_NEW, &dir.join("sub"))
+            .is_empty());
+        assert!(repository.remotes().is_empty());
+    }
+}
@@ -0,0 +1,368 @@
+// State of a repository as shown by the git component of the command bar
+//
+//      master REBASE ⇡1 ⇣2 +3 !1 ?4 =1 $2
+//
+// The branch (or the commit of a detached HEAD), an operation in progress,
+// commits ahead of and behind the upstream, staged, modified, untracked and
+// conflicted files and the number of stashes. Everything but the branch is
+// left out when there is nothing to count, the symbols are configurable.
+
+use git2::{Branch, Repository, RepositoryState, Status, StatusOptions};
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct GitSymbols {
+    pub ahead: String,
+    pub behind: String,
+    pub staged: String,
+    pub modified: String,
+    pub untracked: String,
+    pub conflicted: String,
+    pub stashed: String,
+    // Shown in front of the commit id
+    pub detached: String,
+    pub rebase: String,
+    pub merge: String,
+    pub bisect: String,
+    pub cherry_pick: String,
+    pub revert: String,
+}
+
+impl Default for GitSymbols {
+    fn default() -> Self {
+        Self {
+            ahead: String::from("⇡"),
+            behind: String::from("⇣"),
+            staged: String::from("+"),
+            modified: String::from("!"),
+            untracked: String::from("?"),
+            conflicted: String::from("="),
+            stashed: String::from("$"),
+            detached: String::from("➦"),
+            rebase: String::from("REBASE"),
+            merge: String::from("MERGE"),
+            bisect: String::from("BISECT"),
+            cherry_pick: String::from("CHERRY-PICK"),
+            revert: String::from("REVERT"),
+        }
+    }
+}
+
+#[derive(Debug, PartialEq)]
+pub enum Head {
+    Branch(String),
+    // Short id of the checked out commit
+    Detached(String),
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Operation {
+    Rebase,
+    Merge,
+    Bisect,
+    CherryPick,
+    Revert,
+}
+
+#[derive(Debug, PartialEq)]
+pub struct GitStatus {
+    pub head: Head,
+    pub operation: Option<Operation>,
+    pub ahead: usize,
+    pub behind: usize,
+    pub staged: usize,
+    pub modified: usize,
+    pub untracked: usize,
+    pub conflicted: usize,
+    pub stashes: usize,
+}
+
+impl GitStatus {
+    pub(crate) fn read(repo: &mut Repository) -> Self {
+        let (ahead, behind) = ahead_behind(repo).unwrap_or((0, 0));
+
+        let mut status = GitStatus {
+            head: head(repo),
+            operation: operation(repo.state()),
+            ahead,
+            behind,
+            staged: 0,
+            modified: 0,
+            untracked: 0,
+            conflicted: 0,
+            stashes: 0,
+        };
+
+        status.count_files(repo);
+
+        let _ = repo.stash_foreach(|_, _, _| {
+            status.stashes += 1;
+            true
+        });
+
+        status
+    }
+
+    fn count_files(&mut self, repo: &Repository) {
+        let mut options = StatusOptions::new();
+        options
+            .include_untracked(true)
+            .recurse_untracked_dirs(false)
+            .exclude_submodules(true);
+
+        let statuses = match repo.statuses(Some(&mut options)) {
+            Ok(statuses) => statuses,
+            Err(_) => return,
+        };
+
+        for entry in statuses.iter() {
+            let status = entry.status();
+
+            if status.contains(Status::CONFLICTED) {
+                self.conflicted += 1;
+                continue;
+            }
+            if status.intersects(
+                Status::INDEX_NEW
+                    | Status::INDEX_MODIFIED
+                    | Status::INDEX_DELETED
+                    | Status::INDEX_RENAMED
+                    | Status::INDEX_TYPECHANGE,
+            ) {
+                self.staged += 1;
+            }
+            if status.intersects(
+                Status::WT_MODIFIED
+                    | Status::WT_DELETED
+                    | Status::WT_RENAMED
+                    | Status::WT_TYPECHANGE,
+            ) {
+                self.modified += 1;
+            }
+            if status.contains(Status::WT_NEW) {
+                self.untracked += 1;
+            }
+        }
+    }
+
+    pub fn format(&self, symbols: &GitSymbols) -> String {
+        let mut parts = vec![match &self.head {
+            Head::Branch(name) => name.clone(),
+            Head::Detached(id) => format!("{}{}", symbols.detached, id),
+        }];
+
+        if let Some(operation) = self.operation {
+            parts.push(match operation {
+                Operation::Rebase => symbols.rebase.clone(),
+                Operation::Merge => symbols.merge.clone(),
+                Operation::Bisect => symbols.bisect.clone(),
+                Operation::CherryPick => symbols.cherry_pick.clone(),
+                Operation::Revert => symbols.revert.clone(),
+            });
+        }
+
+        let counts = [
+            (&symbols.ahead, self.ahead),
+            (&symbols.behind, self.behind),
+            (&symbols.staged, self.staged),
+            (&symbols.modified, self.modified),
+            (&symbols.untracked, self.untracked),
+            (&symbols.conflicted, self.conflicted),
+            (&symbols.stashed, self.stashes),
+        ];
+
+        for (symbol, count) in counts.iter() {
+            if *count > 0 {
+                parts.push(format!("{}{}", symbol, count));
+            }
+        }
+
+        parts.join(" ")
+    }
+}
+
+fn head(repo: &Repository) -> Head {
+    match repo.head() {
+        Ok(head) if head.is_branch() => {
+            Head::Branch(head.shorthand().map(String::from).unwrap_or_default())
+        }
+        Ok(head) => Head::Detached(
+            head.target()
+                .map(|id| id.to_string().chars().take(7).collect())
+                .unwrap_or_default(),
+        ),
+        // No commit yet, HEAD points to a branch that does not exist
+        Err(_) => Head::Branch(
+            repo.find_reference("HEAD")
+                .ok()
+                .and_then(|head| head.symbolic_target().map(String::from))
+                .map(|target| target.trim_start_matches("refs/heads/").to_string())
+                .unwrap_or_default(),
+        ),
+    }
+}
+
+fn operation(state: RepositoryState) -> Option<Operation> {
+    match state {
+        RepositoryState::Clean => None,
+        RepositoryState::Merge => Some(Operation::Merge),
+        RepositoryState::Revert | RepositoryState::RevertSequence => Some(Operation::Revert),
+        RepositoryState::CherryPick | RepositoryState::CherryPickSequence => {
+            Some(Operation::CherryPick)
+        }
+        RepositoryState::Bisect => Some(Operation::Bisect),
+        RepositoryState::Rebase
+        | RepositoryState::RebaseInteractive
+        | RepositoryState::RebaseMerge
+        | RepositoryState::ApplyMailbox
+        | RepositoryState::ApplyMailboxOrRebase => Some(Operation::Rebase),
+    }
+}
+
+// Commits ahead of and behind the upstream of the current branch
+fn ahead_behind(repo: &Repository) -> Option<(usize, usize)> {
+    let head = repo.head().ok()?;
+    if !head.is_branch() {
+        return None;
+    }
+
+    let local = head.target()?;
+    let upstream = Branch::wrap(head).upstream().ok()?.get().target()?;
+
+    repo.graph_ahead_behind(local, upstream).ok()
+}
+
+#[cfg(test)]
+mod tests {
+
+    use super::*;
+    use std::fs;
+    use std::path::Path;
+
+    fn commit(repo: &Repository, file: &str, message: &str) -> git2::Oid {
+        let mut index = repo.index().unwrap();
+        index.add_path(Path::new(file)).unwrap();
+        index.write().unwrap();
+
+        let tree = repo.find_tree(index.write_tree().unwrap()).unwrap();
+        let signature = git2::Signature::now("civa", "civa@example.com").unwrap();
+        let parents = match repo.head() {
+            Ok(head) => vec![head.peel_to_commit().unwrap()],
+            Err(_) => vec![],
+        };
+        let parents: Vec<&git2::Commit> = parents.iter().collect();
+
+        repo.commit(
+            Some("HEAD"),
+            &signature,
+            &signature,
+            message,
+            &tree,
+            &parents,
+        )
+        .unwrap()
+    }
+
+    #[test]
+    fn test_format() {
+        let status = GitStatus {
+            head: Head::Branch(String::from("master")),
+            operation: Some(Operation::Rebase),
+            ahead: 1,
+            behind: 2,
+            staged: 0,
+            modified: 3,
+            untracked: 0,
+            conflicted: 0,
+            stashes: 1,
+        };
+
+        assert_eq!(
+            status.format(&GitSymbols::default()),
+            "master REBASE ⇡1 ⇣2 !3 $1"
+        );
+
+        let symbols = GitSymbols {
+            modified: String::from("*"),
+            detached: String::from("HEAD@"),
+            ..GitSymbols::default()
+        };
+        let status = GitStatus {
+            head: Head::Detached(String::from("1a2b3c4")),
+            operation: None,
+            ahead: 0,
+            behind: 0,
+            staged: 0,
+            modified: 2,
+            untracked: 0,
+            conflicted: 0,
+            stashes: 0,
+        };
+        assert_eq!(status.format(&symbols), "HEAD@1a2b3c4 *2");
+    }
+
+    #[test]
+    fn test_read_status() {
+        let temp = tempfile::tempdir().unwrap();
+        let dir = temp.path().to_path_buf();
+        let mut repo = Repository::init(&dir).unwrap();
+
+        let status = GitStatus::read(&mut repo);
+        assert!(matches!(status.head, Head::Branch(ref name) if !name.is_empty()));
+
+        fs::write(dir.join("a.txt"), "a").unwrap();
+        fs::write(dir.join("b.txt"), "b").unwrap();
+        commit(&repo, "a.txt", "a");
+        let first = commit(&repo, "b.txt", "b");
+
+        // Upstream one commit behind
+        let upstream = repo
+            .reference("refs/remotes/origin/master", first, true, "")
+            .unwrap()
+            .target()
+            .unwrap();
+        fs::write(dir.join("a.txt"), "changed").unwrap();
+        commit(&repo, "a.txt", "change a");
+        let branch = repo.head().unwrap().shorthand().unwrap().to_string();
+        let mut config = repo.config().unwrap();
+        config
+            .set_str(&format!("branch.{}.remote", branch), "origin")
+            .unwrap();
+        config
+            .set_str(&format!("branch.{}.merge", branch), "refs/heads/master")
+            .unwrap();
+        repo.remote("origin", "https://example.com/civa.git")
+            .unwrap();
+
+        // Staged, modified and untracked files, and a stash
+        fs::write(dir.join("a.txt"), "stashed").unwrap();
+        let signature = git2::Signature::now("civa", "civa@example.com").unwrap();
+        repo.stash_save(&signature, "wip", None).unwrap();
+        fs::write(dir.join("b.txt"), "staged").unwrap();
+        let mut index = repo.index().unwrap();
+        index.add_path(Path::new("b.txt")).unwrap();
+        index.write().unwrap();
+        fs::write(dir.join("a.txt"), "modified").unwrap();
+        fs::write(dir.join("c.txt"), "c").unwrap();
+
+        let status = GitStatus::read(&mut repo);
+        assert_eq!(status.head, Head::Branch(branch));
+        assert_eq!((status.ahead, status.behind), (1, 0));
+        assert_eq!(
+            (status.staged, status.modified, status.untracked),
+            (1, 1, 1)
+        );
+        assert_eq!(status.stashes, 1);
+        assert_eq!(status.operation, None);
+
+        // Detached in the middle of a merge
+        repo.set_head_detached(upstream).unwrap();
+        fs::write(dir.join(".git").join("MERGE_HEAD"), format!("{}\n", first)).unwrap();
+
+        let status = GitStatus::read(&mut repo);
+        assert_eq!(
+            status.head,
+            Head::Detached(first.to_string().chars().take(7).collect())
+        );
+        assert_eq!(status.operation, Some(Operation::Merge));
+    }
+}
@@ -0,0 +1,67 @@
+use std::io::Write;
+
+use super::error::BuiltinError;
+use crate::builtins::exit_status::ExitStatus;
+use crate::builtins::registry::{ArgKind, ArgSpec, Builtin, BuiltinIo};
+
+use crate::config::alias::parse_alias_definition;
+use crate::config::ContextManager;
+
+pub struct Abbr;
+
+impl Builtin for Abbr {
+    fn name(&self) -> &str {
+        "abbr"
+    }
+
+    fn synopsis(&self) -> &str {
+        "abbr [name='expansion' | name expansion]"
+    }
+
+    fn help(&self) -> &str {
+        "Define or list abbreviations.\n\n\
+         An abbreviation is expanded in place when it is typed as a command followed by a space,\n\
+         so the history contains the full command, e.g. `abbr gco='git checkout'`."
+    }
+
+    fn arg_spec(&self) -> ArgSpec {
+        ArgSpec::new(0, None, ArgKind::Any)
+    }
+
+    fn run(
+        &self,
+        args: &[String],
+        io: &mut BuiltinIo,
+        ctx: &ContextManager,
+    ) -> Result<ExitStatus, BuiltinError> {
+        let abbreviations = ctx.alias_system.borrow().abbreviations();
+
+        if args.is_empty() {
+            for (name, expansion) in abbreviations.all() {
+                writeln!(io.stdout, "abbr {}='{}'", name, expansion)?;
+            }
+            return Ok(ExitStatus { code: 0 });
+        }
+
+        let definition = args.join(" ");
+
+        let parsed = if definition.contains('=') {
+            parse_alias_definition(definition.as_str())
+        } else if args.len() > 1 {
+            Some((args[0].clone(), args[1..].join(" ")))
+        } else {
+            None
+        };
+
+        match parsed {
+            Some((name, expansion)) => {
+                abbreviations.insert(name, expansion);
+                Ok(ExitStatus { code: 0 })
+            }
+            None => Err(BuiltinError {
+                kind: String::from("abbr"),
+                message: format!("Could not parse abbreviation\n\tusage: {}", self.synopsis()),
+            }),
+        }
+    }
+}
@@ -1,22 +1,81 @@
+use std::io::Write;
+
 use super::error::BuiltinError;
 use crate::builtins::exit_status::ExitStatus;
+use crate::builtins::registry::{ArgKind, ArgSpec, Builtin, BuiltinIo};
 
+use crate::config::alias::{parse_alias_definition, AliasKind};
 use crate::config::ContextManager;
 
-// TODO: Add $alias show
+pub struct Alias;
+
+impl Builtin for Alias {
+    fn name(&self) -> &str {
+        "alias"
+    }
+
+    fn synopsis(&self) -> &str {
+        "alias [-g | -s] [name[='value'] | name value]"
+    }
+
+    fn help(&self) -> &str {
+        "Define or show aliases.\n\n\
+         Without arguments all aliases are listed, with a single name that alias is shown.\n\
+         Aliases can be defined in bash syntax `alias ll='ls -la'` or as `alias ll \"ls -la\"`.\n\n\
+         -g  global alias, expanded anywhere on the line, e.g. `alias -g G='| grep'`\n\
+         -s  suffix alias, opens files by extension, e.g. `alias -s md='$EDITOR'`"
+    }
+
+    fn arg_spec(&self) -> ArgSpec {
+        ArgSpec::new(0, None, ArgKind::Alias)
+    }
+
+    fn run(
+        &self,
+        args: &[String],
+        io: &mut BuiltinIo,
+        ctx: &ContextManager,
+    ) -> Result<ExitStatus, BuiltinError> {
+        alias(args, &mut io.stdout, ctx)
+    }
+}
+
+// Supported forms:
+//
+//      $ alias                 -> list all aliases
+//      $ alias ll              -> show a single alias
+//      $ alias ll='ls -la'     -> bash syntax
+//      $ alias ll "ls -la"     -> key value syntax
+//      $ alias -g G='| grep'   -> global alias, expanded anywhere on the line
+//      $ alias -s md=vim       -> suffix alias, `notes.md` runs `vim notes.md`
+//
 // TODO: Make this a binary??
-pub fn alias(arguments: Vec<String>, ctx: &ContextManager) -> Result<ExitStatus, BuiltinError> {
-    if arguments.len() != 2 {
-        return Err(BuiltinError {
-            kind: String::from("alias"),
-            message: String::from(
-                "No Space seperated alias name and alias value provied\n\t $ alias <key> <value>",
-            ),
-        });
+pub fn alias(
+    arguments: &[String],
+    out: &mut dyn Write,
+    ctx: &ContextManager,
+) -> Result<ExitStatus, BuiltinError> {
+    if arguments.is_empty() {
+        for kind in &[AliasKind::Command, AliasKind::Global, AliasKind::Suffix] {
+            list_aliases(*kind, out, ctx)?;
+        }
+        return Ok(ExitStatus { code: 0 });
+    }
+
+    let (kind, arguments) = match AliasKind::from_flag(arguments[0].as_str()) {
+        Some(kind) => (kind, &arguments[1..]),
+        None => (AliasKind::Command, arguments),
+    };
+
+    if arguments.is_empty() {
+        list_aliases(kind, out, ctx)?;
+        return Ok(ExitStatus { code: 0 });
     }
 
-    let key: String = arguments[0].clone();
-    let value: String = arguments[1].clone();
+    let (key, value) = match alias_definition(arguments)? {
+        Some(alias) => alias,
+        None => return show_alias(kind, &arguments[0], out, ctx),
+    };
 
     if key == "" {
         return Err(BuiltinError {
@@ -25,8 +84,116 @@ pub fn alias(arguments: Vec<String>, ctx: &ContextManager) -> Result<ExitStatus,
         });
     }
 
-    match ctx.alias_system.borrow_mut().update_alias(key, value) {
-        true => Ok(ExitStatus { code: 0 }),
-        false => Ok(ExitStatus { code: 0 }),
+    ctx.alias_system
+        .borrow_mut()
+        .update_alias_of_kind(kind, key, value);
+
+    Ok(ExitStatus { code: 0 })
+}
+
+// Key and value of a definition, `None` for a single name to show. Only the
+// first argument decides between the bash and the key value syntax, the value
+// may contain `=` itself (`alias grep "grep --color=auto"`).
+fn alias_definition(arguments: &[String]) -> Result<Option<(String, String)>, BuiltinError> {
+    if arguments[0].contains('=') {
+        // The tokenizer splits `ll='ls -la'` on whitespace, so glue it back together
+        let definition = arguments.join(" ");
+
+        return match parse_alias_definition(definition.as_str()) {
+            Some(alias) => Ok(Some(alias)),
+            None => Err(BuiltinError {
+                kind: String::from("alias"),
+                message: format!(
+                    "Could not parse alias definition '{}'\n\t $ alias <key>='<value>'",
+                    definition
+                ),
+            }),
+        };
+    }
+
+    if arguments.len() == 1 {
+        return Ok(None);
+    }
+
+    Ok(Some((
+        arguments[0].clone(),
+        strip_quotes(arguments[1..].join(" ").as_str()),
+    )))
+}
+
+fn list_aliases(
+    kind: AliasKind,
+    out: &mut dyn Write,
+    ctx: &ContextManager,
+) -> Result<(), BuiltinError> {
+    for (key, value) in ctx.alias_system.borrow().aliases_of_kind(kind) {
+        writeln!(out, "alias {}{}='{}'", kind.flag(), key, value)?;
+    }
+    Ok(())
+}
+
+fn show_alias(
+    kind: AliasKind,
+    key: &str,
+    out: &mut dyn Write,
+    ctx: &ContextManager,
+) -> Result<ExitStatus, BuiltinError> {
+    match ctx.alias_system.borrow().get_alias_of_kind(kind, key) {
+        Some(value) => {
+            writeln!(out, "alias {}{}='{}'", kind.flag(), key, value)?;
+            Ok(ExitStatus { code: 0 })
+        }
+        None => Err(BuiltinError {
+            kind: String::from("alias"),
+            message: format!("No alias '{}' found", key),
+        }),
+    }
+}
+
+pub fn strip_quotes(value: &str) -> String {
+    let quoted = value.len() >= 2
+        && ((value.starts_with('"') && value.ends_with('"'))
+            || (value.starts_with('\'') && value.ends_with('\'')));
+
+    if quoted {
+        String::from(&value[1..value.len() - 1])
+    } else {
+        String::from(value)
+    }
+}
+
+#[cfg(test)]
+mod test {
+
+    use super::*;
+
+    #[test]
+    fn test_strip_quotes() {
+        assert_eq!(strip_quotes("\"ls -la\""), "ls -la");
+        assert_eq!(strip_quotes("'ls -la'"), "ls -la");
+        assert_eq!(strip_quotes("ls"), "ls");
+        assert_eq!(strip_quotes("\""), "\"");
+    }
+
+    fn definition(arguments: &[&str]) -> Option<(String, String)> {
+        let arguments: Vec<String> = arguments.iter().map(|arg| String::from(*arg)).collect();
+        alias_definition(&arguments).unwrap()
+    }
+
+    #[test]
+    fn test_alias_definition() {
+        let alias = |key: &str, value: &str| Some((String::from(key), String::from(value)));
+
+        assert_eq!(definition(&["ll='ls", "-la'"]), alias("ll", "ls -la"));
+        assert_eq!(definition(&["ll", "\"ls", "-la\""]), alias("ll", "ls -la"));
+        assert_eq!(
+            definition(&["grep", "\"grep", "--color=auto\""]),
+            alias("grep", "grep --color=auto")
+        );
+        assert_eq!(
+            definition(&["grep='grep", "--color=auto'"]),
+            alias("grep", "grep --color=auto")
+        );
+        assert_eq!(definition(&["ll"]), None);
     }
 }
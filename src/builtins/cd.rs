@@ -3,6 +3,38 @@ use std::path::Path;
 
 use super::error::BuiltinError;
 use super::exit_status::ExitStatus;
+use super::registry::{ArgKind, ArgSpec, Builtin, BuiltinIo};
+
+use crate::config::ContextManager;
+
+pub struct Cd;
+
+impl Builtin for Cd {
+    fn name(&self) -> &str {
+        "cd"
+    }
+
+    fn synopsis(&self) -> &str {
+        "cd [dir]"
+    }
+
+    fn help(&self) -> &str {
+        "Change the current working directory to dir, defaults to the current directory."
+    }
+
+    fn arg_spec(&self) -> ArgSpec {
+        ArgSpec::new(0, Some(1), ArgKind::Directory)
+    }
+
+    fn run(
+        &self,
+        args: &[String],
+        _io: &mut BuiltinIo,
+        _ctx: &ContextManager,
+    ) -> Result<ExitStatus, BuiltinError> {
+        cd(args.first())
+    }
+}
 
 pub fn cd(path: Option<&String>) -> Result<ExitStatus, BuiltinError> {
     match path {
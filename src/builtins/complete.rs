@@ -0,0 +1,265 @@
+use std::io::Write;
+use std::path::Path;
+
+use super::alias::strip_quotes;
+use super::error::BuiltinError;
+use crate::builtins::exit_status::ExitStatus;
+use crate::builtins::registry::{ArgKind, ArgSpec, Builtin, BuiltinIo};
+
+use crate::config::completion::{ArgType, CompletionSpec, FlagSpec};
+use crate::config::ContextManager;
+
+pub struct Complete;
+
+impl Builtin for Complete {
+    fn name(&self) -> &str {
+        "complete"
+    }
+
+    fn synopsis(&self) -> &str {
+        "complete [command | -c command [-s sub]... [-f flag] [-d desc] [-a type | -w words | -x cmd] | -r command | -l file]"
+    }
+
+    fn help(&self) -> &str {
+        "Define or show completion specs.\n\n\
+         Without arguments all commands with a spec are listed, with a single command its spec is printed.\n\
+         Specs are read from ~/.config/civa/completions/<command>.yaml at startup.\n\n\
+         -c  command the definition is for\n\
+         -s  subcommand, repeat for nested subcommands (`-s remote -s add`)\n\
+         -f  flag, the following options describe the flag instead of the (sub)command\n\
+         -d  description shown in the completion menu\n\
+         -a  argument type: files, dirs, hosts or none\n\
+         -w  complete a fixed list of words, e.g. `-w \"debug release\"`\n\
+         -x  complete the lines printed by a command, e.g. `-x \"git branch --format=%(refname:short)\"`\n\
+         -r  remove the spec of a command\n\
+         -l  load a spec file"
+    }
+
+    fn arg_spec(&self) -> ArgSpec {
+        ArgSpec::new(0, None, ArgKind::Any)
+    }
+
+    fn run(
+        &self,
+        args: &[String],
+        io: &mut BuiltinIo,
+        ctx: &ContextManager,
+    ) -> Result<ExitStatus, BuiltinError> {
+        complete(args, &mut io.stdout, ctx)
+    }
+}
+
+// A definition made on the command line, `complete -c cargo -s build -f --release`
+#[derive(Debug, Default, PartialEq)]
+struct Definition {
+    command: Option<String>,
+    subcommands: Vec<String>,
+    flag: Option<String>,
+    description: Option<String>,
+    args: Option<ArgType>,
+}
+
+impl Definition {
+    fn apply(self, spec: &mut CompletionSpec) {
+        let mut spec = spec;
+        for subcommand in &self.subcommands {
+            spec = spec
+                .subcommands
+                .entry(subcommand.clone())
+                .or_insert_with(|| CompletionSpec::new(subcommand));
+        }
+
+        match self.flag {
+            Some(name) => {
+                let flag = match spec.flags.iter().position(|flag| flag.name == name) {
+                    Some(index) => &mut spec.flags[index],
+                    None => {
+                        spec.flags.push(FlagSpec::new(&name));
+                        spec.flags.last_mut().unwrap()
+                    }
+                };
+                if let Some(description) = self.description {
+                    flag.description = description;
+                }
+                if let Some(args) = self.args {
+                    flag.args = args;
+                }
+            }
+            None => {
+                if let Some(description) = self.description {
+                    spec.description = description;
+                }
+                if let Some(args) = self.args {
+                    spec.args = args;
+                }
+            }
+        }
+    }
+}
+
+pub fn complete(
+    arguments: &[String],
+    out: &mut dyn Write,
+    ctx: &ContextManager,
+) -> Result<ExitStatus, BuiltinError> {
+    if arguments.is_empty() {
+        let specs = ctx.completion_specs.borrow();
+        for name in specs.names() {
+            writeln!(out, "{}\t{}", name, specs.get(name).unwrap().description)?;
+        }
+        return Ok(ExitStatus { code: 0 });
+    }
+
+    match arguments[0].as_str() {
+        "-r" if arguments.len() == 2 => {
+            return match ctx.completion_specs.borrow_mut().remove(&arguments[1]) {
+                Some(_) => Ok(ExitStatus { code: 0 }),
+                None => Err(no_spec_error(&arguments[1])),
+            };
+        }
+        "-l" if arguments.len() == 2 => {
+            let path = strip_quotes(&arguments[1]);
+            let spec = CompletionSpec::from_file(Path::new(&path)).map_err(|e| BuiltinError {
+                kind: String::from("complete"),
+                message: e.message,
+            })?;
+            ctx.completion_specs.borrow_mut().insert(spec);
+            return Ok(ExitStatus { code: 0 });
+        }
+        name if arguments.len() == 1 && !name.starts_with('-') => {
+            return match ctx.completion_specs.borrow().get(name) {
+                Some(spec) => {
+                    writeln!(out, "{}", spec.to_yaml_string())?;
+                    Ok(ExitStatus { code: 0 })
+                }
+                None => Err(no_spec_error(name)),
+            };
+        }
+        _ => {}
+    }
+
+    let definition = parse_definition(arguments)?;
+
+    let command = match &definition.command {
+        Some(command) => command.clone(),
+        None => {
+            return Err(BuiltinError {
+                kind: String::from("complete"),
+                message: String::from("No command provided, use -c <command>"),
+            })
+        }
+    };
+
+    definition.apply(ctx.completion_specs.borrow_mut().get_or_insert(&command));
+
+    Ok(ExitStatus { code: 0 })
+}
+
+fn parse_definition(arguments: &[String]) -> Result<Definition, BuiltinError> {
+    let mut definition = Definition::default();
+    let mut arguments = arguments.iter();
+
+    while let Some(option) = arguments.next() {
+        let value = match arguments.next() {
+            Some(value) => strip_quotes(value),
+            None => {
+                return Err(BuiltinError {
+                    kind: String::from("complete"),
+                    message: format!("Missing value for '{}'", option),
+                })
+            }
+        };
+
+        match option.as_str() {
+            "-c" => definition.command = Some(value),
+            "-s" => definition.subcommands.push(value),
+            "-f" => definition.flag = Some(value),
+            "-d" => definition.description = Some(value),
+            "-a" => match ArgType::from_name(&value) {
+                Some(args) => definition.args = Some(args),
+                None => {
+                    return Err(BuiltinError {
+                        kind: String::from("complete"),
+                        message: format!(
+                            "Unknown argument type '{}', expected files, dirs, hosts or none",
+                            value
+                        ),
+                    })
+                }
+            },
+            "-w" => {
+                definition.args = Some(ArgType::Words(
+                    value.split_whitespace().map(String::from).collect(),
+                ))
+            }
+            "-x" => definition.args = Some(ArgType::Command(value)),
+            _ => {
+                return Err(BuiltinError {
+                    kind: String::from("complete"),
+                    message: format!("Unknown option '{}'", option),
+                })
+            }
+        }
+    }
+
+    Ok(definition)
+}
+
+fn no_spec_error(command: &str) -> BuiltinError {
+    BuiltinError {
+        kind: String::from("complete"),
+        message: format!("No completion spec for '{}'", command),
+    }
+}
+
+#[cfg(test)]
+mod test {
+
+    use super::*;
+
+    fn args(line: &str) -> Vec<String> {
+        line.split(' ').map(String::from).collect()
+    }
+
+    #[test]
+    fn test_parse_definition() {
+        // The tokenizer keeps double quoted strings together
+        let mut arguments = args("-c git -s remote -s add -f -t -d track -w");
+        arguments.push(String::from("\"main dev\""));
+
+        let definition = parse_definition(&arguments).unwrap();
+
+        assert_eq!(
+            definition,
+            Definition {
+                command: Some(String::from("git")),
+                subcommands: vec![String::from("remote"), String::from("add")],
+                flag: Some(String::from("-t")),
+                description: Some(String::from("track")),
+                args: Some(ArgType::Words(vec![
+                    String::from("main"),
+                    String::from("dev")
+                ])),
+            }
+        );
+        assert!(parse_definition(&args("-c git -a tea")).is_err());
+        assert!(parse_definition(&args("-c")).is_err());
+    }
+
+    #[test]
+    fn test_apply_definition() {
+        let mut spec = CompletionSpec::new("cargo");
+
+        parse_definition(&args("-c cargo -s build -d compile -a none"))
+            .unwrap()
+            .apply(&mut spec);
+        parse_definition(&args("-c cargo -s build -f --release"))
+            .unwrap()
+            .apply(&mut spec);
+
+        let build = &spec.subcommands["build"];
+        assert_eq!(build.description, "compile");
+        assert_eq!(build.args, ArgType::None);
+        assert_eq!(build.flag("--release"), Some(&FlagSpec::new("--release")));
+    }
+}
@@ -1,31 +1,26 @@
 use crate::command::Command;
 
-use super::alias;
-use super::cd;
 use super::error::BuiltinError;
 use super::exit_status::ExitStatus;
-use super::penv;
-use super::BUILTIN_NAMES;
+use super::registry::{run_builtin, BuiltinIo};
 
 use crate::config::ContextManager;
 
 pub fn executor(command: Command, ctx: &ContextManager) -> Result<ExitStatus, BuiltinError> {
-    if BUILTIN_NAMES.contains(&command.command_name.as_str()) {
-        match command.command_name.as_str() {
-            "cd" => cd::cd(command.arguments.first()),
-            ":q" => std::process::exit(0),
-            "penv" => penv::penv(command.arguments.first().unwrap_or(&String::new())),
-            "alias" => alias::alias(command.arguments.clone(), ctx),
-            other => Err(BuiltinError {
-                kind: String::from("builtins"),
-                message: format!("Could not find builtin '{}'", other),
-            }),
-        }
-    } else {
-        Err(BuiltinError {
+    // Release the registry before running, builtins may want to register others
+    let builtin = ctx.builtins.borrow().get(command.command_name.as_str());
+
+    match builtin {
+        Some(builtin) => run_builtin(
+            builtin.as_ref(),
+            &command.arguments,
+            &mut BuiltinIo::std(),
+            ctx,
+        ),
+        None => Err(BuiltinError {
             kind: String::from("builtins"),
             message: format!("Could not find builtin '{}'", command.command_name),
-        })
+        }),
     }
 }
 
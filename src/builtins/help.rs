@@ -0,0 +1,76 @@
+use std::io::Write;
+
+use super::error::BuiltinError;
+use super::exit_status::ExitStatus;
+use super::registry::{ArgKind, ArgSpec, Builtin, BuiltinIo};
+
+use crate::config::ContextManager;
+
+use ansi_term::Colour::Green;
+
+pub struct Help;
+
+impl Builtin for Help {
+    fn name(&self) -> &str {
+        "help"
+    }
+
+    fn synopsis(&self) -> &str {
+        "help [builtin]"
+    }
+
+    fn help(&self) -> &str {
+        "List all builtins, or show the usage and description of a single builtin."
+    }
+
+    fn arg_spec(&self) -> ArgSpec {
+        ArgSpec::new(0, Some(1), ArgKind::Builtin)
+    }
+
+    fn run(
+        &self,
+        args: &[String],
+        io: &mut BuiltinIo,
+        ctx: &ContextManager,
+    ) -> Result<ExitStatus, BuiltinError> {
+        let registry = ctx.builtins.borrow();
+
+        match args.first() {
+            Some(name) => match registry.get(name) {
+                Some(builtin) => {
+                    writeln!(io.stdout, "{}", Green.bold().paint(builtin.synopsis()))?;
+                    if !builtin.aliases().is_empty() {
+                        writeln!(io.stdout, "Aliases: {}", builtin.aliases().join(", "))?;
+                    }
+                    writeln!(io.stdout, "\n{}", builtin.help())?;
+                    Ok(ExitStatus { code: 0 })
+                }
+                None => Err(BuiltinError {
+                    kind: String::from("help"),
+                    message: format!("No builtin '{}' found", name),
+                }),
+            },
+            None => {
+                let builtins = registry.builtins();
+                let width = builtins
+                    .iter()
+                    .map(|b| b.synopsis().len())
+                    .max()
+                    .unwrap_or(0);
+
+                for builtin in builtins {
+                    let summary = builtin.help().lines().next().unwrap_or_default();
+                    writeln!(
+                        io.stdout,
+                        "{}  {}",
+                        Green
+                            .bold()
+                            .paint(format!("{:width$}", builtin.synopsis(), width = width)),
+                        summary
+                    )?;
+                }
+                Ok(ExitStatus { code: 0 })
+            }
+        }
+    }
+}
@@ -0,0 +1,358 @@
+use std::env;
+use std::fs;
+use std::io::Write;
+
+use chrono::{Local, NaiveTime, TimeZone};
+use regex::Regex;
+
+use super::alias::strip_quotes;
+use super::error::BuiltinError;
+use crate::builtins::exit_status::ExitStatus;
+use crate::builtins::registry::{ArgKind, ArgSpec, Builtin, BuiltinIo};
+
+use crate::config::ContextManager;
+use crate::history::export::{export, ExportFormat};
+use crate::history::filter::{parse_time, HistoryFilter, StatusFilter};
+use crate::history::HistoryEntry;
+
+pub struct History;
+
+impl Builtin for History {
+    fn name(&self) -> &str {
+        "history"
+    }
+
+    fn synopsis(&self) -> &str {
+        "history [-n count] [-g regex] [--cwd dir] [--status code|ok|failed] [--since time] [--until time] [--export json|csv [file] | -d [id]... | -c | -i]"
+    }
+
+    fn help(&self) -> &str {
+        "List, search, delete or export the command history.\n\n\
+         Entries are listed with their id, start time and exit status.\n\n\
+         -n        only the last <count> matching entries, e.g. `history -n 20`\n\
+         -g        entries whose command matches a regex\n\
+         --cwd     entries run in a directory, `.` for the current one\n\
+         --status  entries with an exit code, `ok` or `failed`\n\
+         --since   entries started after a time, e.g. `2h`, `3d` or `2021-03-01 18:30`\n\
+         --until   entries started before a time\n\
+         --export  write the matching entries as json or csv to a file or stdout\n\
+         -d        delete the entries with the given ids, or all matching entries\n\
+         -c        clear the history\n\
+         -i        import the entries of other sessions, done before every prompt with `share_history()`"
+    }
+
+    fn arg_spec(&self) -> ArgSpec {
+        ArgSpec::new(0, None, ArgKind::Any)
+    }
+
+    fn run(
+        &self,
+        args: &[String],
+        io: &mut BuiltinIo,
+        ctx: &ContextManager,
+    ) -> Result<ExitStatus, BuiltinError> {
+        history(args, &mut io.stdout, ctx)
+    }
+}
+
+#[derive(Debug, PartialEq)]
+enum Action {
+    List,
+    Export(ExportFormat, Option<String>),
+    // Ids as listed, i.e. starting at 1
+    Delete(Vec<usize>),
+    Clear,
+    Import,
+}
+
+#[derive(Debug)]
+struct Query {
+    filter: HistoryFilter,
+    count: Option<usize>,
+    action: Action,
+}
+
+impl Query {
+    fn has_filter(&self) -> bool {
+        let filter = &self.filter;
+
+        filter.pattern.is_some()
+            || filter.cwd.is_some()
+            || filter.status.is_some()
+            || filter.since.is_some()
+            || filter.until.is_some()
+            || self.count.is_some()
+    }
+
+    // Positions of the selected entries in the store
+    fn select(&self, entries: &[HistoryEntry]) -> Vec<usize> {
+        let mut selected = self.filter.apply(entries);
+
+        if let Some(count) = self.count {
+            let skip = selected.len().saturating_sub(count);
+            selected.drain(..skip);
+        }
+
+        selected
+    }
+}
+
+fn history_error(message: String) -> BuiltinError {
+    BuiltinError {
+        kind: String::from("history"),
+        message,
+    }
+}
+
+fn parse_query(arguments: &[String]) -> Result<Query, BuiltinError> {
+    let mut query = Query {
+        filter: HistoryFilter::default(),
+        count: None,
+        action: Action::List,
+    };
+    let now = Local::now();
+
+    let mut args = arguments.iter().map(|arg| strip_quotes(arg)).peekable();
+    while let Some(option) = args.next() {
+        if option == "-c" {
+            query.action = Action::Clear;
+            continue;
+        }
+
+        if option == "-i" {
+            query.action = Action::Import;
+            continue;
+        }
+
+        if option == "-d" {
+            let mut ids = Vec::new();
+            while let Some(id) = args.peek().and_then(|arg| arg.parse::<usize>().ok()) {
+                ids.push(id);
+                args.next();
+            }
+            query.action = Action::Delete(ids);
+            continue;
+        }
+
+        if option == "--export" {
+            let format = args.next().unwrap_or_default();
+            let format = ExportFormat::from_str(&format)
+                .ok_or_else(|| history_error(format!("Unknown export format '{}'", format)))?;
+            let file = match args.peek() {
+                Some(file) if !file.starts_with('-') => args.next(),
+                _ => None,
+            };
+            query.action = Action::Export(format, file);
+            continue;
+        }
+
+        let value = args
+            .next()
+            .ok_or_else(|| history_error(format!("Missing value of {}", option)))?;
+
+        match option.as_str() {
+            "-n" => {
+                let count = value
+                    .parse()
+                    .map_err(|_| history_error(format!("Invalid count '{}'", value)))?;
+                query.count = Some(count);
+            }
+            "-g" => {
+                let pattern = Regex::new(&value)
+                    .map_err(|e| history_error(format!("Invalid regex: {}", e)))?;
+                query.filter.pattern = Some(pattern);
+            }
+            "--cwd" => query.filter.cwd = Some(absolute_dir(&value)),
+            "--status" => {
+                let status = StatusFilter::from_str(&value)
+                    .ok_or_else(|| history_error(format!("Invalid exit status '{}'", value)))?;
+                query.filter.status = Some(status);
+            }
+            "--since" | "--until" => {
+                // `2021-03-01 18:30` is split into two arguments
+                let value = match args.peek() {
+                    Some(clock) if is_clock_time(clock) && !value.contains(':') => {
+                        format!("{} {}", value, args.next().unwrap_or_default())
+                    }
+                    _ => value,
+                };
+                let time = parse_time(&value, now)
+                    .ok_or_else(|| history_error(format!("Invalid time '{}'", value)))?;
+                if option == "--since" {
+                    query.filter.since = Some(time);
+                } else {
+                    query.filter.until = Some(time);
+                }
+            }
+            _ => {
+                return Err(history_error(format!("Unknown option {}", option)));
+            }
+        }
+    }
+
+    Ok(query)
+}
+
+fn is_clock_time(value: &str) -> bool {
+    NaiveTime::parse_from_str(value, "%H:%M").is_ok()
+}
+
+// Directories are recorded as absolute paths
+fn absolute_dir(dir: &str) -> String {
+    env::current_dir()
+        .map(|cwd| cwd.join(dir))
+        .and_then(|path| path.canonicalize())
+        .map(|path| path.to_string_lossy().into_owned())
+        .unwrap_or_else(|_| String::from(dir))
+}
+
+fn format_timestamp(timestamp: u64) -> String {
+    Local
+        .timestamp_opt(timestamp as i64, 0)
+        .single()
+        .map(|time| time.format("%Y-%m-%d %H:%M").to_string())
+        .unwrap_or_default()
+}
+
+pub fn history(
+    arguments: &[String],
+    out: &mut dyn Write,
+    ctx: &ContextManager,
+) -> Result<ExitStatus, BuiltinError> {
+    let query = parse_query(arguments)?;
+
+    match &query.action {
+        Action::List => {
+            let store = ctx.history.read().unwrap();
+            let entries = store.entries();
+            for i in query.select(entries) {
+                writeln!(
+                    out,
+                    "{:>5}  {}  {:>3}  {}",
+                    i + 1,
+                    format_timestamp(entries[i].timestamp),
+                    entries[i].exit_status,
+                    entries[i].command
+                )?;
+            }
+        }
+        Action::Export(format, file) => {
+            let store = ctx.history.read().unwrap();
+            let entries = store.entries();
+            let selected: Vec<&HistoryEntry> = query
+                .select(entries)
+                .into_iter()
+                .map(|i| &entries[i])
+                .collect();
+            let exported = export(&selected, *format);
+
+            match file {
+                Some(file) => fs::write(file, exported)?,
+                None => write!(out, "{}", exported)?,
+            }
+        }
+        Action::Delete(ids) => {
+            let mut store = ctx.history.write().unwrap();
+            let len = store.entries().len();
+
+            let indices: Vec<usize> = if ids.is_empty() {
+                if !query.has_filter() {
+                    return Err(history_error(String::from(
+                        "Nothing to delete, pass ids or filters (`history -c` clears everything)",
+                    )));
+                }
+                query.select(store.entries())
+            } else {
+                if let Some(id) = ids.iter().find(|id| **id == 0 || **id > len) {
+                    return Err(history_error(format!("No history entry {}", id)));
+                }
+                ids.iter().map(|id| id - 1).collect()
+            };
+
+            store.remove(&indices)?;
+        }
+        Action::Clear => ctx.history.write().unwrap().clear()?,
+        Action::Import => {
+            ctx.history.write().unwrap().import()?;
+        }
+    }
+
+    Ok(ExitStatus { code: 0 })
+}
+
+#[cfg(test)]
+mod test {
+
+    use super::*;
+
+    fn args(line: &str) -> Vec<String> {
+        line.split(' ').map(String::from).collect()
+    }
+
+    fn entry(command: &str, exit_status: i32) -> HistoryEntry {
+        HistoryEntry::for_test(command, "/", exit_status)
+    }
+
+    #[test]
+    fn test_parse_query() {
+        let query = parse_query(&args("-n 2 -g ^git --status failed")).unwrap();
+        assert_eq!(query.count, Some(2));
+        assert_eq!(query.filter.status, Some(StatusFilter::Failure));
+        assert_eq!(query.action, Action::List);
+
+        let query = parse_query(&args("-i")).unwrap();
+        assert_eq!(query.action, Action::Import);
+
+        let query = parse_query(&args("-d 3 7")).unwrap();
+        assert_eq!(query.action, Action::Delete(vec![3, 7]));
+
+        let query = parse_query(&args("--export csv out.csv -g ssh")).unwrap();
+        assert_eq!(
+            query.action,
+            Action::Export(ExportFormat::Csv, Some(String::from("out.csv")))
+        );
+        assert!(query.filter.pattern.is_some());
+
+        assert!(parse_query(&args("--export xml")).is_err());
+        assert!(parse_query(&args("--since tomorrow")).is_err());
+        assert!(parse_query(&args("--since 2021-03-01 18:30 -x")).is_err());
+        assert!(parse_query(&args("-n")).is_err());
+        assert!(parse_query(&args("-x 1")).is_err());
+    }
+
+    #[test]
+    fn test_parse_date_and_time() {
+        let at = |time: &str| parse_time(time, Local::now());
+
+        let query = parse_query(&args("--since 2021-03-01 18:30 -n 3")).unwrap();
+        assert_eq!(query.filter.since, at("2021-03-01 18:30"));
+        assert_eq!(query.count, Some(3));
+
+        // Quoted it is a single argument
+        let query = parse_query(&[
+            String::from("--until"),
+            String::from("\"2021-03-01 18:30\""),
+        ])
+        .unwrap();
+        assert_eq!(query.filter.until, at("2021-03-01 18:30"));
+
+        let query = parse_query(&args("--since 2021-03-01")).unwrap();
+        assert_eq!(query.filter.since, at("2021-03-01"));
+    }
+
+    #[test]
+    fn test_select_last_matching() {
+        let entries = vec![
+            entry("git status", 0),
+            entry("ls", 0),
+            entry("git push", 1),
+            entry("git pull", 0),
+        ];
+
+        let query = parse_query(&args("-g ^git -n 2")).unwrap();
+        assert_eq!(query.select(&entries), vec![2, 3]);
+
+        let query = parse_query(&args("--status ok")).unwrap();
+        assert_eq!(query.select(&entries), vec![0, 1, 3]);
+    }
+}
@@ -0,0 +1,49 @@
+use super::error::BuiltinError;
+use super::exit_status::ExitStatus;
+use super::registry::{ArgKind, ArgSpec, Builtin, BuiltinIo};
+
+use crate::config::ContextManager;
+
+pub struct Incognito;
+
+impl Builtin for Incognito {
+    fn name(&self) -> &str {
+        "incognito"
+    }
+
+    fn synopsis(&self) -> &str {
+        "incognito [on|off]"
+    }
+
+    fn help(&self) -> &str {
+        "Stop or resume recording the history of this session.\n\n\
+         Commands run in incognito mode are still reachable with Up, but are not written to the\n\
+         history file. The command bar shows when incognito mode is on."
+    }
+
+    fn arg_spec(&self) -> ArgSpec {
+        ArgSpec::new(0, Some(1), ArgKind::Any)
+    }
+
+    fn run(
+        &self,
+        args: &[String],
+        _io: &mut BuiltinIo,
+        ctx: &ContextManager,
+    ) -> Result<ExitStatus, BuiltinError> {
+        let incognito = match args.first().map(String::as_str) {
+            None | Some("on") => true,
+            Some("off") => false,
+            Some(arg) => {
+                return Err(BuiltinError {
+                    kind: String::from("incognito"),
+                    message: format!("Expected on or off, got '{}'", arg),
+                })
+            }
+        };
+
+        ctx.history_privacy.borrow_mut().incognito = incognito;
+
+        Ok(ExitStatus { code: 0 })
+    }
+}
@@ -1,9 +1,16 @@
+pub mod abbr;
 pub mod alias;
-pub mod builtin_names;
 pub mod cd;
+pub mod complete;
 pub mod error;
 pub mod executer;
 pub mod exit_status;
+pub mod help;
+pub mod history;
+pub mod incognito;
 pub mod penv;
+pub mod quit;
+pub mod registry;
+pub mod update_completions;
 
-pub static BUILTIN_NAMES: &[&str] = &["cd", ":q", "quit", "penv"];
+pub use registry::BuiltinRegistry;
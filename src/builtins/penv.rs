@@ -1,4 +1,5 @@
 use std::env;
+use std::io::Write;
 
 use ansi_term::Colour::{Blue, Green};
 
@@ -8,8 +9,39 @@ use prettytable::Table;
 // a environment variable
 use crate::builtins::error::BuiltinError;
 use crate::builtins::exit_status::ExitStatus;
+use crate::builtins::registry::{ArgKind, ArgSpec, Builtin, BuiltinIo};
+use crate::config::ContextManager;
 
-pub fn penv(var_name: &str) -> Result<ExitStatus, BuiltinError> {
+pub struct Penv;
+
+impl Builtin for Penv {
+    fn name(&self) -> &str {
+        "penv"
+    }
+
+    fn synopsis(&self) -> &str {
+        "penv <variable>"
+    }
+
+    fn help(&self) -> &str {
+        "Pretty print the value of an environment variable, one entry per row for ':' separated lists like PATH."
+    }
+
+    fn arg_spec(&self) -> ArgSpec {
+        ArgSpec::new(1, Some(1), ArgKind::EnvVar)
+    }
+
+    fn run(
+        &self,
+        args: &[String],
+        io: &mut BuiltinIo,
+        _ctx: &ContextManager,
+    ) -> Result<ExitStatus, BuiltinError> {
+        penv(args.first().unwrap_or(&String::new()), &mut io.stdout)
+    }
+}
+
+pub fn penv(var_name: &str, out: &mut dyn Write) -> Result<ExitStatus, BuiltinError> {
     if var_name == "" {
         return Err(BuiltinError {
             kind: String::from("penv"),
@@ -25,19 +57,20 @@ pub fn penv(var_name: &str) -> Result<ExitStatus, BuiltinError> {
 
             let mut table = Table::new();
 
-            println!(
+            writeln!(
+                out,
                 "\n{}",
                 Blue.paint(format!(
                     "ENVIRONMENT VARIABLE: {}",
                     Blue.bold().paint(var_name.to_uppercase())
                 ))
-            );
+            )?;
 
             for (i, path) in paths.enumerate() {
                 table.add_row(row![Green.bold().paint((i + 1).to_string()), path]);
             }
 
-            table.printstd();
+            table.print(out)?;
 
             Ok(ExitStatus { code: 1 })
         }
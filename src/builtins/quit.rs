@@ -0,0 +1,38 @@
+use super::error::BuiltinError;
+use super::exit_status::ExitStatus;
+use super::registry::{ArgSpec, Builtin, BuiltinIo};
+
+use crate::config::ContextManager;
+
+pub struct Quit;
+
+impl Builtin for Quit {
+    fn name(&self) -> &str {
+        "quit"
+    }
+
+    fn aliases(&self) -> &[&str] {
+        &[":q"]
+    }
+
+    fn synopsis(&self) -> &str {
+        "quit"
+    }
+
+    fn help(&self) -> &str {
+        "Exit the shell."
+    }
+
+    fn arg_spec(&self) -> ArgSpec {
+        ArgSpec::none()
+    }
+
+    fn run(
+        &self,
+        _args: &[String],
+        _io: &mut BuiltinIo,
+        _ctx: &ContextManager,
+    ) -> Result<ExitStatus, BuiltinError> {
+        std::process::exit(0)
+    }
+}
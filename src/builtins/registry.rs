@@ -0,0 +1,232 @@
+// Builtin Registry
+//
+// Every builtin implements the `Builtin` trait and describes itself (name,
+// synopsis, help text and expected arguments). The registry is populated at
+// startup with the shipped builtins, plugins can register further ones.
+//
+// Because the builtins describe themselves, `help`, argument validation and
+// completion metadata all read from the same place.
+//
+
+use std::collections::BTreeMap;
+use std::io::{self, Read, Write};
+use std::rc::Rc;
+
+use super::error::BuiltinError;
+use super::exit_status::ExitStatus;
+use super::{abbr, alias, cd, complete, help, history, incognito, penv, quit, update_completions};
+
+use crate::config::ContextManager;
+
+// What kind of value a builtin expects, used for completion
+#[derive(Debug, PartialEq, Copy, Clone)]
+pub enum ArgKind {
+    Any,
+    Directory,
+    File,
+    EnvVar,
+    Alias,
+    Builtin,
+}
+
+#[derive(Debug, PartialEq, Copy, Clone)]
+pub struct ArgSpec {
+    pub min: usize,
+    pub max: Option<usize>,
+    pub kind: ArgKind,
+}
+
+impl ArgSpec {
+    pub fn none() -> Self {
+        Self {
+            min: 0,
+            max: Some(0),
+            kind: ArgKind::Any,
+        }
+    }
+
+    pub fn new(min: usize, max: Option<usize>, kind: ArgKind) -> Self {
+        Self { min, max, kind }
+    }
+
+    pub fn accepts(&self, count: usize) -> bool {
+        count >= self.min && self.max.map_or(true, |max| count <= max)
+    }
+}
+
+// Standard streams handed to a builtin. By default these are the streams of
+// the shell itself, but they can be swapped out e.g. for pipes or tests.
+pub struct BuiltinIo {
+    pub stdin: Box<dyn Read>,
+    pub stdout: Box<dyn Write>,
+    pub stderr: Box<dyn Write>,
+}
+
+impl BuiltinIo {
+    pub fn std() -> Self {
+        Self {
+            stdin: Box::new(io::stdin()),
+            stdout: Box::new(io::stdout()),
+            stderr: Box::new(io::stderr()),
+        }
+    }
+}
+
+pub trait Builtin {
+    fn name(&self) -> &str;
+
+    // Additional names the builtin can be called with
+    fn aliases(&self) -> &[&str] {
+        &[]
+    }
+
+    // One line usage, e.g. `cd [dir]`
+    fn synopsis(&self) -> &str;
+
+    fn help(&self) -> &str;
+
+    fn arg_spec(&self) -> ArgSpec;
+
+    fn run(
+        &self,
+        args: &[String],
+        io: &mut BuiltinIo,
+        ctx: &ContextManager,
+    ) -> Result<ExitStatus, BuiltinError>;
+}
+
+pub struct BuiltinRegistry {
+    builtins: BTreeMap<String, Rc<dyn Builtin>>,
+}
+
+impl BuiltinRegistry {
+    pub fn new() -> Self {
+        Self {
+            builtins: BTreeMap::new(),
+        }
+    }
+
+    pub fn with_defaults() -> Self {
+        let mut registry = Self::new();
+
+        registry.register(Box::new(cd::Cd));
+        registry.register(Box::new(quit::Quit));
+        registry.register(Box::new(penv::Penv));
+        registry.register(Box::new(alias::Alias));
+        registry.register(Box::new(abbr::Abbr));
+        registry.register(Box::new(complete::Complete));
+        registry.register(Box::new(update_completions::UpdateCompletions));
+        registry.register(Box::new(history::History));
+        registry.register(Box::new(incognito::Incognito));
+        registry.register(Box::new(help::Help));
+
+        registry
+    }
+
+    // Registers a builtin under its name and all of its aliases. An existing
+    // builtin with the same name is replaced.
+    pub fn register(&mut self, builtin: Box<dyn Builtin>) {
+        let builtin: Rc<dyn Builtin> = Rc::from(builtin);
+
+        for name in builtin.aliases() {
+            self.builtins
+                .insert(String::from(*name), Rc::clone(&builtin));
+        }
+
+        self.builtins
+            .insert(String::from(builtin.name()), Rc::clone(&builtin));
+    }
+
+    pub fn get(&self, name: &str) -> Option<Rc<dyn Builtin>> {
+        self.builtins.get(name).map(Rc::clone)
+    }
+
+    pub fn contains(&self, name: &str) -> bool {
+        self.builtins.contains_key(name)
+    }
+
+    // All callable names, including aliases, sorted
+    pub fn names(&self) -> Vec<&str> {
+        self.builtins.keys().map(|name| name.as_str()).collect()
+    }
+
+    // Every builtin once (under its primary name), sorted by name
+    pub fn builtins(&self) -> Vec<Rc<dyn Builtin>> {
+        self.builtins
+            .iter()
+            .filter(|(name, builtin)| builtin.name() == name.as_str())
+            .map(|(_, builtin)| Rc::clone(builtin))
+            .collect()
+    }
+}
+
+// Validates the arguments against the spec of the builtin and runs it
+pub fn run_builtin(
+    builtin: &dyn Builtin,
+    args: &[String],
+    io: &mut BuiltinIo,
+    ctx: &ContextManager,
+) -> Result<ExitStatus, BuiltinError> {
+    if !builtin.arg_spec().accepts(args.len()) {
+        return Err(BuiltinError {
+            kind: String::from(builtin.name()),
+            message: format!("Invalid arguments\n\tusage: {}", builtin.synopsis()),
+        });
+    }
+
+    builtin.run(args, io, ctx)
+}
+
+#[cfg(test)]
+mod test {
+
+    use super::*;
+
+    #[test]
+    fn test_arg_spec_accepts() {
+        let spec = ArgSpec::new(1, Some(2), ArgKind::Any);
+
+        assert!(!spec.accepts(0));
+        assert!(spec.accepts(1));
+        assert!(spec.accepts(2));
+        assert!(!spec.accepts(3));
+        assert!(ArgSpec::new(0, None, ArgKind::Any).accepts(42));
+    }
+
+    #[test]
+    fn test_registry_registers_aliases() {
+        let registry = BuiltinRegistry::with_defaults();
+
+        assert!(registry.contains("quit"));
+        assert!(registry.contains(":q"));
+        assert!(registry.contains("alias"));
+        assert!(!registry.contains("ls"));
+    }
+
+    #[test]
+    fn test_registry_builtins_are_listed_once() {
+        let registry = BuiltinRegistry::with_defaults();
+
+        let names: Vec<String> = registry
+            .builtins()
+            .iter()
+            .map(|b| String::from(b.name()))
+            .collect();
+
+        assert_eq!(
+            names,
+            vec![
+                "abbr",
+                "alias",
+                "cd",
+                "complete",
+                "help",
+                "history",
+                "incognito",
+                "penv",
+                "quit",
+                "update_completions"
+            ]
+        );
+    }
+}
@@ -0,0 +1,133 @@
+use std::fs;
+use std::io::Write;
+
+use super::error::BuiltinError;
+use crate::builtins::exit_status::ExitStatus;
+use crate::builtins::registry::{ArgKind, ArgSpec, Builtin, BuiltinIo};
+
+use crate::config::completion::CompletionSpec;
+use crate::config::manpage::{
+    find_man_page, list_man_pages, parse_help_output, parse_man_page, read_help_output,
+    read_man_page,
+};
+use crate::config::ContextManager;
+
+pub struct UpdateCompletions;
+
+impl Builtin for UpdateCompletions {
+    fn name(&self) -> &str {
+        "update_completions"
+    }
+
+    fn synopsis(&self) -> &str {
+        "update_completions [command]"
+    }
+
+    fn help(&self) -> &str {
+        "Generate completion specs from the installed man pages.\n\n\
+         Without arguments a spec is generated for every command on the PATH with a man page,\n\
+         with a command only its spec is regenerated. A command without man page is asked\n\
+         for its `--help` output instead. Specs are cached in ~/.cache/civa/completions and\n\
+         never replace specs from ~/.config/civa/completions."
+    }
+
+    fn arg_spec(&self) -> ArgSpec {
+        ArgSpec::new(0, Some(1), ArgKind::Any)
+    }
+
+    fn run(
+        &self,
+        args: &[String],
+        io: &mut BuiltinIo,
+        ctx: &ContextManager,
+    ) -> Result<ExitStatus, BuiltinError> {
+        match args.first() {
+            Some(command) => update_command(command, &mut io.stdout, ctx),
+            None => update_all(&mut io.stdout, ctx),
+        }
+    }
+}
+
+fn update_command(
+    command: &str,
+    out: &mut dyn Write,
+    ctx: &ContextManager,
+) -> Result<ExitStatus, BuiltinError> {
+    let spec = match find_man_page(command) {
+        Some(page) => {
+            let roff = read_man_page(&page).map_err(|e| BuiltinError {
+                kind: String::from("update_completions"),
+                message: e.message,
+            })?;
+            parse_man_page(command, &roff)
+        }
+        // Running arbitrary programs is only done when asked for explicitly
+        None if ctx.env_manager.has_command(command) => match read_help_output(command) {
+            Some(help) => parse_help_output(command, &help),
+            None => return Err(no_source_error(command)),
+        },
+        None => return Err(no_source_error(command)),
+    };
+
+    if spec.flags.is_empty() && spec.subcommands.is_empty() {
+        return Err(BuiltinError {
+            kind: String::from("update_completions"),
+            message: format!("No options found for '{}'", command),
+        });
+    }
+
+    writeln!(
+        out,
+        "{}: {} flags, {} subcommands",
+        command,
+        spec.flags.len(),
+        spec.subcommands.len()
+    )?;
+    store(spec, ctx)?;
+
+    Ok(ExitStatus { code: 0 })
+}
+
+fn update_all(out: &mut dyn Write, ctx: &ContextManager) -> Result<ExitStatus, BuiltinError> {
+    let mut count = 0;
+
+    for (command, page) in list_man_pages() {
+        if !ctx.env_manager.has_command(&command) {
+            continue;
+        }
+
+        // A single unreadable page should not stop the update
+        let spec = match read_man_page(&page) {
+            Ok(roff) => parse_man_page(&command, &roff),
+            Err(_) => continue,
+        };
+
+        if !spec.flags.is_empty() && store(spec, ctx)? {
+            count += 1;
+        }
+    }
+
+    writeln!(out, "Generated completions for {} commands", count)?;
+
+    Ok(ExitStatus { code: 0 })
+}
+
+// Writes the spec to the cache and hands it to the completer, returns false
+// if the user has an own spec for the command
+fn store(spec: CompletionSpec, ctx: &ContextManager) -> Result<bool, BuiltinError> {
+    let cache_dir = ctx.completion_cache_dir()?;
+
+    fs::write(
+        cache_dir.join(format!("{}.yaml", spec.name)),
+        spec.to_yaml_string(),
+    )?;
+
+    Ok(ctx.completion_specs.borrow_mut().insert_generated(spec))
+}
+
+fn no_source_error(command: &str) -> BuiltinError {
+    BuiltinError {
+        kind: String::from("update_completions"),
+        message: format!("No man page or --help output found for '{}'", command),
+    }
+}
@@ -0,0 +1,236 @@
+// Escape sequences of the colors of bar.yaml
+//
+// The colors a terminal supports are guessed from the environment once:
+// `COLORTERM=truecolor` (or `24bit`) for true colors, a `TERM` containing
+// `256color` for the 256 color palette and the 16 ANSI colors otherwise.
+// Colors the terminal does not support are replaced by the closest one it
+// has, e.g. `#ff8800` becomes 208 on a 256 color terminal and yellow on a
+// 16 color one. Distances are measured on the default xterm palette.
+//
+// The 16 ANSI colors use their own codes (`31` for red, `91` for bright red)
+// when nothing more is supported, terminals without the 256 color palette
+// may not understand `38;5;N`.
+
+use std::env;
+
+use termion::color::{AnsiValue, Bg, Fg, Rgb};
+
+use crate::config::ColorName;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum ColorSupport {
+    Ansi16,
+    Ansi256,
+    TrueColor,
+}
+
+impl ColorSupport {
+    fn detect(colorterm: Option<&str>, term: Option<&str>) -> Self {
+        let colorterm = colorterm.unwrap_or_default().to_lowercase();
+        let term = term.unwrap_or_default().to_lowercase();
+
+        if colorterm == "truecolor" || colorterm == "24bit" || term.ends_with("-direct") {
+            ColorSupport::TrueColor
+        } else if term.contains("256color") {
+            ColorSupport::Ansi256
+        } else {
+            ColorSupport::Ansi16
+        }
+    }
+}
+
+lazy_static! {
+    static ref COLOR_SUPPORT: ColorSupport = ColorSupport::detect(
+        env::var("COLORTERM").ok().as_deref(),
+        env::var("TERM").ok().as_deref()
+    );
+}
+
+// Colors of the terminal the shell runs in
+pub fn support() -> ColorSupport {
+    *COLOR_SUPPORT
+}
+
+// xterm defaults of the 16 ANSI colors
+static ANSI_16: [(u8, u8, u8); 16] = [
+    (0, 0, 0),
+    (205, 0, 0),
+    (0, 205, 0),
+    (205, 205, 0),
+    (0, 0, 238),
+    (205, 0, 205),
+    (0, 205, 205),
+    (229, 229, 229),
+    (127, 127, 127),
+    (255, 0, 0),
+    (0, 255, 0),
+    (255, 255, 0),
+    (92, 92, 255),
+    (255, 0, 255),
+    (0, 255, 255),
+    (255, 255, 255),
+];
+
+// Steps of the 6x6x6 color cube starting at index 16
+static CUBE_STEPS: [u8; 6] = [0, 95, 135, 175, 215, 255];
+
+enum Resolved {
+    Index(u8),
+    Rgb(u8, u8, u8),
+}
+
+pub fn foreground(color_name: &ColorName) -> String {
+    color_sequence(color_name, *COLOR_SUPPORT, false)
+}
+
+pub fn background(color_name: &ColorName) -> String {
+    color_sequence(color_name, *COLOR_SUPPORT, true)
+}
+
+pub fn color_sequence(color_name: &ColorName, support: ColorSupport, background: bool) -> String {
+    match (resolve(color_name, support), background) {
+        (Resolved::Index(index), _) if support == ColorSupport::Ansi16 => {
+            format!("\x1b[{}m", ansi_16_code(index, background))
+        }
+        (Resolved::Index(index), false) => format!("{}", Fg(AnsiValue(index))),
+        (Resolved::Index(index), true) => format!("{}", Bg(AnsiValue(index))),
+        (Resolved::Rgb(r, g, b), false) => format!("{}", Fg(Rgb(r, g, b))),
+        (Resolved::Rgb(r, g, b), true) => format!("{}", Bg(Rgb(r, g, b))),
+    }
+}
+
+// SGR code of one of the 16 ANSI colors, 30-37 and 90-97 for the bright ones,
+// backgrounds start at 40 and 100
+fn ansi_16_code(index: u8, background: bool) -> u8 {
+    let base = if background { 40 } else { 30 };
+
+    if index < 8 {
+        base + index
+    } else {
+        base + 60 + (index - 8)
+    }
+}
+
+fn resolve(color_name: &ColorName, support: ColorSupport) -> Resolved {
+    let index = match color_name {
+        ColorName::BLACK => 0,
+        ColorName::RED => 1,
+        ColorName::GREEN => 2,
+        ColorName::YELLOW => 3,
+        ColorName::BLUE => 4,
+        ColorName::MAGENTA => 5,
+        ColorName::CYAN => 6,
+        ColorName::WHITE => 7,
+        ColorName::FIXED(index) => *index,
+        ColorName::RGB(r, g, b) => {
+            return match support {
+                ColorSupport::TrueColor => Resolved::Rgb(*r, *g, *b),
+                ColorSupport::Ansi256 => Resolved::Index(nearest(16..=255, (*r, *g, *b))),
+                ColorSupport::Ansi16 => Resolved::Index(nearest(0..=15, (*r, *g, *b))),
+            }
+        }
+    };
+
+    if index >= 16 && support == ColorSupport::Ansi16 {
+        return Resolved::Index(nearest(0..=15, palette_rgb(index)));
+    }
+
+    Resolved::Index(index)
+}
+
+fn palette_rgb(index: u8) -> (u8, u8, u8) {
+    match index {
+        0..=15 => ANSI_16[index as usize],
+        16..=231 => {
+            let cube = index - 16;
+            (
+                CUBE_STEPS[(cube / 36) as usize],
+                CUBE_STEPS[(cube / 6 % 6) as usize],
+                CUBE_STEPS[(cube % 6) as usize],
+            )
+        }
+        _ => {
+            let gray = 8 + (index - 232) * 10;
+            (gray, gray, gray)
+        }
+    }
+}
+
+// Palette index out of `indices` closest to `rgb`
+fn nearest<I: Iterator<Item = u8>>(indices: I, rgb: (u8, u8, u8)) -> u8 {
+    let distance = |other: (u8, u8, u8)| {
+        let channel = |a: u8, b: u8| (i32::from(a) - i32::from(b)).pow(2);
+        channel(rgb.0, other.0) + channel(rgb.1, other.1) + channel(rgb.2, other.2)
+    };
+
+    indices
+        .min_by_key(|index| distance(palette_rgb(*index)))
+        .unwrap_or(0)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_detect() {
+        assert_eq!(
+            ColorSupport::detect(Some("truecolor"), Some("xterm-256color")),
+            ColorSupport::TrueColor
+        );
+        assert_eq!(
+            ColorSupport::detect(None, Some("xterm-256color")),
+            ColorSupport::Ansi256
+        );
+        assert_eq!(
+            ColorSupport::detect(None, Some("linux")),
+            ColorSupport::Ansi16
+        );
+        assert_eq!(ColorSupport::detect(None, None), ColorSupport::Ansi16);
+    }
+
+    #[test]
+    fn test_palette_rgb() {
+        assert_eq!(palette_rgb(4), (0, 0, 238));
+        assert_eq!(palette_rgb(208), (255, 135, 0));
+        assert_eq!(palette_rgb(244), (128, 128, 128));
+    }
+
+    #[test]
+    fn test_color_sequence() {
+        let orange = ColorName::RGB(255, 136, 0);
+
+        assert_eq!(
+            color_sequence(&orange, ColorSupport::TrueColor, false),
+            "\u{1b}[38;2;255;136;0m"
+        );
+        assert_eq!(
+            color_sequence(&orange, ColorSupport::Ansi256, false),
+            "\u{1b}[38;5;208m"
+        );
+        assert_eq!(
+            color_sequence(&orange, ColorSupport::Ansi16, true),
+            "\u{1b}[43m"
+        );
+        assert_eq!(
+            color_sequence(&ColorName::FIXED(196), ColorSupport::Ansi16, false),
+            "\u{1b}[91m"
+        );
+        assert_eq!(
+            color_sequence(&ColorName::BLACK, ColorSupport::Ansi16, true),
+            "\u{1b}[40m"
+        );
+        assert_eq!(
+            color_sequence(&ColorName::FIXED(12), ColorSupport::Ansi16, true),
+            "\u{1b}[104m"
+        );
+        assert_eq!(
+            color_sequence(&ColorName::RED, ColorSupport::Ansi16, false),
+            "\u{1b}[31m"
+        );
+        assert_eq!(
+            color_sequence(&ColorName::RED, ColorSupport::Ansi256, false),
+            "\u{1b}[38;5;1m"
+        );
+    }
+}
@@ -0,0 +1,463 @@
+// Command line completion
+//
+// What is offered depends on the position of the cursor:
+//
+//      - command position (first word, after ; && || |): PATH binaries,
+//        builtins, aliases and abbreviations, annotated with their kind
+//      - arguments of a builtin: whatever its ArgSpec asks for
+//      - arguments of a command with a completion spec: its subcommands,
+//        flags and argument types (see config/completion.rs)
+//      - everything else: paths
+//
+
+use std::cell::RefCell;
+use std::collections::BTreeMap;
+use std::env;
+use std::fs;
+use std::path::MAIN_SEPARATOR;
+use std::process::{Command, Stdio};
+use std::rc::Rc;
+
+use git::GitRepository;
+use rustyline::completion::{Completer, FilenameCompleter, Pair};
+use rustyline::Context;
+
+use crate::builtins::registry::ArgKind;
+use crate::builtins::BuiltinRegistry;
+use crate::command::handler::is_command_position;
+use crate::config::alias::{AliasKind, AliasSystem};
+use crate::config::completion::{ArgType, CompletionSpecs, GitSource};
+use crate::env::environment::EnvManager;
+
+// Ordered by precedence, an alias shadows a builtin of the same name etc.
+#[derive(Debug, PartialEq, Eq, PartialOrd, Ord, Copy, Clone)]
+enum CandidateKind {
+    Command,
+    Builtin,
+    Alias,
+    Abbreviation,
+    Variable,
+}
+
+impl CandidateKind {
+    fn label(self) -> &'static str {
+        match self {
+            CandidateKind::Command => "command",
+            CandidateKind::Builtin => "builtin",
+            CandidateKind::Alias => "alias",
+            CandidateKind::Abbreviation => "abbr",
+            CandidateKind::Variable => "variable",
+        }
+    }
+}
+
+pub struct ShellCompleter {
+    filename_completer: FilenameCompleter,
+    env_manager: Rc<EnvManager>,
+    builtins: Rc<RefCell<BuiltinRegistry>>,
+    alias_system: Rc<RefCell<AliasSystem>>,
+    specs: Rc<RefCell<CompletionSpecs>>,
+}
+
+impl ShellCompleter {
+    pub fn new(
+        env_manager: Rc<EnvManager>,
+        builtins: Rc<RefCell<BuiltinRegistry>>,
+        alias_system: Rc<RefCell<AliasSystem>>,
+        specs: Rc<RefCell<CompletionSpecs>>,
+    ) -> Self {
+        Self {
+            filename_completer: FilenameCompleter::new(),
+            env_manager,
+            builtins,
+            alias_system,
+            specs,
+        }
+    }
+
+    fn command_candidates(&self, prefix: &str) -> Vec<Pair> {
+        let mut candidates: BTreeMap<String, CandidateKind> = BTreeMap::new();
+
+        for name in self.env_manager.command_names() {
+            add_candidate(&mut candidates, prefix, name, CandidateKind::Command);
+        }
+
+        for name in self.builtins.borrow().names() {
+            add_candidate(&mut candidates, prefix, name, CandidateKind::Builtin);
+        }
+
+        let alias_system = self.alias_system.borrow();
+        for (name, _) in alias_system.aliases_of_kind(AliasKind::Command) {
+            add_candidate(&mut candidates, prefix, name, CandidateKind::Alias);
+        }
+
+        for (name, _) in alias_system.abbreviations().all() {
+            add_candidate(&mut candidates, prefix, &name, CandidateKind::Abbreviation);
+        }
+
+        to_pairs(candidates)
+    }
+
+    fn argument_candidates(&self, kind: ArgKind, prefix: &str) -> Option<Vec<Pair>> {
+        let mut candidates: BTreeMap<String, CandidateKind> = BTreeMap::new();
+
+        match kind {
+            ArgKind::EnvVar => {
+                for (name, _) in env::vars() {
+                    add_candidate(&mut candidates, prefix, &name, CandidateKind::Variable);
+                }
+            }
+            ArgKind::Alias => {
+                for (name, _) in self
+                    .alias_system
+                    .borrow()
+                    .aliases_of_kind(AliasKind::Command)
+                {
+                    add_candidate(&mut candidates, prefix, name, CandidateKind::Alias);
+                }
+            }
+            ArgKind::Builtin => {
+                for name in self.builtins.borrow().names() {
+                    add_candidate(&mut candidates, prefix, name, CandidateKind::Builtin);
+                }
+            }
+            ArgKind::Directory | ArgKind::File | ArgKind::Any => return None,
+        }
+
+        Some(to_pairs(candidates))
+    }
+
+    // Completion driven by the spec of `command`, None if there is no spec
+    fn spec_candidates(
+        &self,
+        command: &str,
+        words: &[&str],
+        line: &str,
+        pos: usize,
+        ctx: &Context<'_>,
+    ) -> rustyline::Result<Option<(usize, Vec<Pair>)>> {
+        let specs = self.specs.borrow();
+        let spec = match specs.get(command) {
+            Some(spec) => spec,
+            None => return Ok(None),
+        };
+
+        let start = word_start(line, pos);
+        let current = &line[start..pos];
+        let resolved = spec.resolve(words, current);
+
+        let mut candidates: Vec<Pair> = resolved
+            .candidates
+            .iter()
+            .map(|(name, description)| Pair {
+                display: if description.is_empty() {
+                    String::from(*name)
+                } else {
+                    format!("{} ({})", name, description)
+                },
+                replacement: String::from(*name),
+            })
+            .collect();
+
+        let mut words = argument_words(resolved.args);
+        words.sort();
+        words.dedup();
+
+        for word in words {
+            if word.starts_with(current) && !candidates.iter().any(|c| c.replacement == word) {
+                candidates.push(Pair {
+                    display: word.clone(),
+                    replacement: word,
+                });
+            }
+        }
+
+        // Paths only if nothing else matched, they would drown the other candidates otherwise
+        if candidates.is_empty() {
+            match path_kind(resolved.args) {
+                Some(ArgType::Dirs) => {
+                    let (start, paths) = self.filename_completer.complete(line, pos, ctx)?;
+                    return Ok(Some((start, directories(paths))));
+                }
+                Some(_) => return self.filename_completer.complete(line, pos, ctx).map(Some),
+                None => {}
+            }
+        }
+
+        Ok(Some((start, candidates)))
+    }
+
+    pub fn complete(
+        &self,
+        line: &str,
+        pos: usize,
+        ctx: &Context<'_>,
+    ) -> rustyline::Result<(usize, Vec<Pair>)> {
+        let start = word_start(line, pos);
+        let word = &line[start..pos];
+        let preceding = &line[..start];
+
+        // Relative and absolute paths are commands as well, complete them as paths
+        if is_command_position(preceding) && !word.contains('/') && !word.starts_with('.') {
+            return Ok((start, self.command_candidates(word)));
+        }
+
+        let words = current_command_words(preceding);
+        if let Some((command, words)) = words.split_first() {
+            if let Some(completion) = self.spec_candidates(command, words, line, pos, ctx)? {
+                return Ok(completion);
+            }
+        }
+
+        let arg_kind = current_command(preceding)
+            .and_then(|command| self.builtins.borrow().get(command))
+            .map(|builtin| builtin.arg_spec().kind);
+
+        if let Some(kind) = arg_kind {
+            if let Some(candidates) = self.argument_candidates(kind, word) {
+                return Ok((start, candidates));
+            }
+
+            if kind == ArgKind::Directory {
+                let (start, paths) = self.filename_completer.complete(line, pos, ctx)?;
+                return Ok((start, directories(paths)));
+            }
+        }
+
+        self.filename_completer.complete(line, pos, ctx)
+    }
+}
+
+fn add_candidate(
+    candidates: &mut BTreeMap<String, CandidateKind>,
+    prefix: &str,
+    name: &str,
+    kind: CandidateKind,
+) {
+    if !name.starts_with(prefix) {
+        return;
+    }
+
+    let entry = candidates.entry(String::from(name)).or_insert(kind);
+    if kind > *entry {
+        *entry = kind;
+    }
+}
+
+fn to_pairs(candidates: BTreeMap<String, CandidateKind>) -> Vec<Pair> {
+    candidates
+        .into_iter()
+        .map(|(name, kind)| Pair {
+            display: format!("{} ({})", name, kind.label()),
+            replacement: name,
+        })
+        .collect()
+}
+
+// Candidates of every argument type except paths
+fn argument_words(args: &ArgType) -> Vec<String> {
+    match args {
+        ArgType::None | ArgType::Files | ArgType::Dirs => Vec::new(),
+        ArgType::Words(words) => words.clone(),
+        ArgType::Hosts => hosts(),
+        ArgType::Command(command) => command_output(command),
+        ArgType::Git(source) => git_objects(*source),
+        ArgType::Union(types) => types.iter().flat_map(argument_words).collect(),
+    }
+}
+
+// Which paths to complete, files win over directories in a union
+fn path_kind(args: &ArgType) -> Option<&ArgType> {
+    match args {
+        ArgType::Files | ArgType::Dirs => Some(args),
+        ArgType::Union(types) => types
+            .iter()
+            .filter_map(path_kind)
+            .min_by_key(|kind| **kind != ArgType::Files),
+        _ => None,
+    }
+}
+
+fn git_objects(source: GitSource) -> Vec<String> {
+    let mut repository = match GitRepository::discover(".") {
+        Some(repository) => repository,
+        None => return Vec::new(),
+    };
+
+    match source {
+        GitSource::Branches => repository.local_branches(),
+        GitSource::RemoteBranches => repository.remote_branches(),
+        GitSource::Remotes => repository.remotes(),
+        GitSource::Tags => repository.tags(),
+        GitSource::Stashes => repository.stashes(),
+        GitSource::Modified => repository.modified_files(),
+        GitSource::Staged => repository.staged_files(),
+        GitSource::Untracked => repository.untracked_files(),
+    }
+}
+
+fn directories(paths: Vec<Pair>) -> Vec<Pair> {
+    paths
+        .into_iter()
+        .filter(|pair| pair.replacement.ends_with(MAIN_SEPARATOR))
+        .collect()
+}
+
+// Host names from /etc/hosts and the `Host` entries of ~/.ssh/config
+fn hosts() -> Vec<String> {
+    let mut hosts = Vec::new();
+
+    if let Ok(contents) = fs::read_to_string("/etc/hosts") {
+        for line in contents.lines() {
+            let line = line.split('#').next().unwrap_or("");
+            hosts.extend(line.split_whitespace().skip(1).map(String::from));
+        }
+    }
+
+    if let Ok(home) = env::var("HOME") {
+        if let Ok(contents) = fs::read_to_string(format!("{}/.ssh/config", home)) {
+            for line in contents.lines() {
+                let mut tokens = line.split_whitespace();
+                if tokens
+                    .next()
+                    .map_or(false, |key| key.eq_ignore_ascii_case("host"))
+                {
+                    hosts.extend(
+                        tokens
+                            .filter(|host| !host.contains('*') && !host.contains('?'))
+                            .map(String::from),
+                    );
+                }
+            }
+        }
+    }
+
+    hosts.sort();
+    hosts.dedup();
+    hosts
+}
+
+// Every non empty line printed by `command`, run through `sh`
+fn command_output(command: &str) -> Vec<String> {
+    let output = Command::new("sh")
+        .arg("-c")
+        .arg(command)
+        .stdin(Stdio::null())
+        .stderr(Stdio::null())
+        .output();
+
+    match output {
+        Ok(output) => String::from_utf8_lossy(&output.stdout)
+            .lines()
+            .map(str::trim)
+            .filter(|line| !line.is_empty())
+            .map(String::from)
+            .collect(),
+        Err(_) => Vec::new(),
+    }
+}
+
+// Start of the word the cursor is in
+fn word_start(line: &str, pos: usize) -> usize {
+    line[..pos]
+        .rfind(char::is_whitespace)
+        .map_or(0, |i| i + line[i..].chars().next().unwrap().len_utf8())
+}
+
+// Name of the command the words before the cursor belong to
+fn current_command(preceding: &str) -> Option<&str> {
+    current_command_words(preceding).first().copied()
+}
+
+// Words of the command the cursor is in, starting with its name
+fn current_command_words(preceding: &str) -> Vec<&str> {
+    let mut words = Vec::new();
+
+    for token in preceding.split_whitespace() {
+        // A single token is in command position exactly if it is a delimiter
+        if is_command_position(token) {
+            words.clear();
+        } else {
+            words.push(token);
+        }
+    }
+
+    words
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn completer() -> ShellCompleter {
+        let mut alias_system = AliasSystem::new();
+        alias_system.update_alias_of_kind(
+            AliasKind::Command,
+            String::from("helpme"),
+            String::from("help"),
+        );
+
+        ShellCompleter::new(
+            Rc::new(EnvManager::new()),
+            Rc::new(RefCell::new(BuiltinRegistry::with_defaults())),
+            Rc::new(RefCell::new(alias_system)),
+            Rc::new(RefCell::new(CompletionSpecs::default())),
+        )
+    }
+
+    #[test]
+    fn test_word_start() {
+        assert_eq!(word_start("", 0), 0);
+        assert_eq!(word_start("gi", 2), 0);
+        assert_eq!(word_start("git chec", 8), 4);
+        assert_eq!(word_start("ls ", 3), 3);
+    }
+
+    #[test]
+    fn test_current_command() {
+        assert_eq!(current_command(""), None);
+        assert_eq!(current_command("cd "), Some("cd"));
+        assert_eq!(current_command("ls -la && penv "), Some("penv"));
+        assert_eq!(current_command("ls | help foo "), Some("help"));
+        assert_eq!(
+            current_command_words("ls && cargo build --release "),
+            vec!["cargo", "build", "--release"]
+        );
+    }
+
+    #[test]
+    fn test_command_candidates_are_annotated() {
+        let candidates = completer().command_candidates("help");
+
+        let displays: Vec<&str> = candidates.iter().map(|p| p.display.as_str()).collect();
+
+        assert!(displays.contains(&"help (builtin)"));
+        assert!(displays.contains(&"helpme (alias)"));
+    }
+
+    #[test]
+    fn test_path_kind() {
+        assert_eq!(path_kind(&ArgType::Hosts), None);
+        assert_eq!(
+            path_kind(&ArgType::Union(vec![
+                ArgType::Dirs,
+                ArgType::Git(GitSource::Tags),
+                ArgType::Files
+            ])),
+            Some(&ArgType::Files)
+        );
+    }
+
+    #[test]
+    fn test_builtin_argument_candidates() {
+        let candidates = completer()
+            .argument_candidates(ArgKind::Builtin, "al")
+            .unwrap();
+
+        assert_eq!(candidates.len(), 1);
+        assert_eq!(candidates[0].replacement, "alias");
+        assert!(completer()
+            .argument_candidates(ArgKind::Directory, "")
+            .is_none());
+    }
+}
@@ -0,0 +1,390 @@
+// Content of the command bar components which are not part of `Cli` itself
+//
+// An empty string hides the component including its sorround, e.g. the
+// duration of quick commands or the virtualenv when none is active.
+
+use std::env;
+use std::fs;
+use std::path::Path;
+use std::process;
+use std::time::Duration;
+
+use chrono::Local;
+use unicode_width::UnicodeWidthChar;
+
+use super::segments::Job;
+use crate::config::command_bar::{CommandBarComponents, Component};
+use crate::config::template::Padding;
+use crate::config::ContextManager;
+use crate::vcs;
+
+pub fn component_value(component: &CommandBarComponents, ctx: &ContextManager) -> String {
+    match component {
+        CommandBarComponents::STATUS { .. } => ctx
+            .last_exit_status
+            .get()
+            .map(|code| code.to_string())
+            .unwrap_or_default(),
+        CommandBarComponents::DURATION { threshold } => match ctx.last_duration.get() {
+            Some(duration) if duration >= *threshold => format_duration(duration),
+            _ => String::new(),
+        },
+        CommandBarComponents::TIME { format } => Local::now().format(format).to_string(),
+        CommandBarComponents::HOSTNAME => short_hostname(&ctx.session.hostname),
+        CommandBarComponents::SHLVL => env::var("SHLVL").unwrap_or_default(),
+        CommandBarComponents::VENV => virtual_env(),
+        CommandBarComponents::TEXT { text } => text.clone(),
+        _ => String::new(),
+    }
+}
+
+// Components which can block on the file system are rendered in the
+// background, see `segments`
+pub fn background_job(component: &CommandBarComponents) -> Option<Job> {
+    match component {
+        CommandBarComponents::SVN { symbols } => {
+            let symbols = symbols.clone();
+            Some(Box::new(move |dir: &Path| vcs::segment(dir, &symbols)))
+        }
+        CommandBarComponents::JOBS => Some(Box::new(|_: &Path| match running_jobs() {
+            0 => String::new(),
+            jobs => jobs.to_string(),
+        })),
+        CommandBarComponents::TOOLCHAIN => Some(Box::new(|dir: &Path| rust_toolchain(dir))),
+        _ => None,
+    }
+}
+
+// How the value of a component is shown, see `template`
+#[derive(Debug, Clone)]
+pub struct ValueFormat {
+    format: String,
+    condition: Option<String>,
+    max_width: Option<usize>,
+    padding: Option<Padding>,
+}
+
+impl ValueFormat {
+    pub fn new(format: &str) -> Self {
+        Self {
+            format: String::from(format),
+            condition: None,
+            max_width: None,
+            padding: None,
+        }
+    }
+
+    pub fn of(component: &Component) -> Self {
+        Self {
+            condition: component.condition.clone(),
+            max_width: component.max_width,
+            padding: component.padding,
+            ..ValueFormat::new(&component.format)
+        }
+    }
+
+    // Nothing for an empty value
+    pub fn apply(&self, value: &str) -> String {
+        let value = match self.max_width {
+            Some(width) => truncate(value, width),
+            None => String::from(value),
+        };
+
+        let content = match &self.condition {
+            Some(_) if value == "0" => String::new(),
+            Some(condition) => apply_format(condition, &value),
+            None => apply_format(&self.format, &value),
+        };
+
+        match self.padding {
+            Some(padding) if !content.is_empty() => pad(&content, padding),
+            _ => content,
+        }
+    }
+}
+
+// `%s` of the format replaced by the value, nothing for an empty value
+pub fn apply_format(format: &str, value: &str) -> String {
+    if value.is_empty() {
+        return String::new();
+    }
+
+    format.replace("%s", value)
+}
+
+// At most `width` columns, the end is replaced by `…` if it is longer
+fn truncate(value: &str, width: usize) -> String {
+    let char_width = |c: char| UnicodeWidthChar::width(c).unwrap_or(0);
+
+    if value.chars().map(char_width).sum::<usize>() <= width {
+        return String::from(value);
+    }
+
+    let mut truncated = String::new();
+    let mut used = 0;
+
+    for c in value.chars() {
+        if used + char_width(c) + 1 > width {
+            break;
+        }
+        used += char_width(c);
+        truncated.push(c);
+    }
+
+    if width > 0 {
+        truncated.push('…');
+    }
+    truncated
+}
+
+fn pad(content: &str, padding: Padding) -> String {
+    let width = content
+        .chars()
+        .map(|c| UnicodeWidthChar::width(c).unwrap_or(0))
+        .sum::<usize>();
+
+    match padding {
+        Padding::Right(min) => format!("{}{}", content, " ".repeat(min.saturating_sub(width))),
+        Padding::Left(min) => format!("{}{}", " ".repeat(min.saturating_sub(width)), content),
+    }
+}
+
+// `850ms`, `4.2s`, `3m 12s`, `1h 5m`
+pub fn format_duration(duration: Duration) -> String {
+    let millis = duration.as_millis();
+    let seconds = duration.as_secs();
+
+    if millis < 1000 {
+        format!("{}ms", millis)
+    } else if seconds < 60 {
+        format!("{:.1}s", duration.as_secs_f64())
+    } else if seconds < 3600 {
+        format!("{}m {}s", seconds / 60, seconds % 60)
+    } else {
+        format!("{}h {}m", seconds / 3600, seconds % 3600 / 60)
+    }
+}
+
+fn short_hostname(hostname: &str) -> String {
+    hostname
+        .split('.')
+        .next()
+        .map(String::from)
+        .unwrap_or_default()
+}
+
+// Processes started by the shell which are still running, exited ones not
+// waited for yet (zombies) are left out
+fn running_jobs() -> usize {
+    let stats: Vec<String> = match child_pids() {
+        Some(pids) => pids
+            .iter()
+            .filter_map(|pid| fs::read_to_string(Path::new("/proc").join(pid).join("stat")).ok())
+            .collect(),
+        None => all_stats()
+            .into_iter()
+            .filter(|stat| parent_pid(stat) == Some(process::id().to_string().as_str()))
+            .collect(),
+    };
+
+    stats
+        .iter()
+        .filter(|stat| process_state(stat).map_or(false, |state| state != "Z"))
+        .count()
+}
+
+// Children of all threads of the shell as listed by the kernel, `None` if
+// it was built without `/proc/<pid>/task/<tid>/children`
+fn child_pids() -> Option<Vec<String>> {
+    let mut pids = Vec::new();
+
+    for task in fs::read_dir("/proc/self/task").ok()?.filter_map(Result::ok) {
+        let children = fs::read_to_string(task.path().join("children")).ok()?;
+        pids.extend(children.split_whitespace().map(String::from));
+    }
+
+    Some(pids)
+}
+
+// `/proc/<pid>/stat` of every process
+fn all_stats() -> Vec<String> {
+    let entries = match fs::read_dir("/proc") {
+        Ok(entries) => entries,
+        Err(_) => return Vec::new(),
+    };
+
+    entries
+        .filter_map(Result::ok)
+        .filter(|entry| {
+            entry
+                .file_name()
+                .to_string_lossy()
+                .chars()
+                .all(|c| c.is_ascii_digit())
+        })
+        .filter_map(|entry| fs::read_to_string(entry.path().join("stat")).ok())
+        .collect()
+}
+
+// `/proc/<pid>/stat` is `pid (comm) state ppid ...`, where comm may contain
+// spaces and parentheses itself
+fn process_state(stat: &str) -> Option<&str> {
+    let (_, fields) = stat.rsplit_once(')')?;
+    fields.split_whitespace().next()
+}
+
+fn parent_pid(stat: &str) -> Option<&str> {
+    let (_, fields) = stat.rsplit_once(')')?;
+    fields.split_whitespace().nth(1)
+}
+
+fn virtual_env() -> String {
+    if let Ok(venv) = env::var("VIRTUAL_ENV") {
+        return Path::new(&venv)
+            .file_name()
+            .map(|name| name.to_string_lossy().into_owned())
+            .unwrap_or(venv);
+    }
+
+    env::var("CONDA_DEFAULT_ENV").unwrap_or_default()
+}
+
+// The toolchain rustup would use in `cwd`: the override of the environment or
+// the closest `rust-toolchain` file. Directory overrides of rustup itself are
+// not considered, asking rustup is too slow for every prompt.
+fn rust_toolchain(cwd: &Path) -> String {
+    if let Ok(toolchain) = env::var("RUSTUP_TOOLCHAIN") {
+        return toolchain;
+    }
+
+    for dir in cwd.ancestors() {
+        for name in ["rust-toolchain.toml", "rust-toolchain"].iter() {
+            if let Ok(content) = fs::read_to_string(dir.join(name)) {
+                return parse_toolchain_file(&content);
+            }
+        }
+    }
+
+    String::new()
+}
+
+// Either the toml format with `channel = "..."` or just the name
+fn parse_toolchain_file(content: &str) -> String {
+    for line in content.lines() {
+        let line = line.trim();
+
+        if let Some(value) = line.strip_prefix("channel") {
+            if let Some(value) = value.trim_start().strip_prefix('=') {
+                return String::from(value.trim().trim_matches(|c| c == '"' || c == '\''));
+            }
+        }
+    }
+
+    match content.lines().map(str::trim).find(|line| !line.is_empty()) {
+        Some(line) if !line.contains('=') && !line.starts_with('[') => String::from(line),
+        _ => String::new(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_apply_format() {
+        assert_eq!(apply_format("took %s", "3.2s"), "took 3.2s");
+        assert_eq!(apply_format("took %s", ""), "");
+        assert_eq!(apply_format("λ", "1"), "λ");
+    }
+
+    #[test]
+    fn test_value_format() {
+        let condition = ValueFormat {
+            condition: Some(String::from("✘ %s")),
+            ..ValueFormat::new("%s")
+        };
+        assert_eq!(condition.apply("1"), "✘ 1");
+        assert_eq!(condition.apply("0"), "");
+        assert_eq!(condition.apply(""), "");
+
+        let truncated = ValueFormat {
+            max_width: Some(6),
+            padding: Some(Padding::Right(8)),
+            ..ValueFormat::new("[%s]")
+        };
+        assert_eq!(truncated.apply("feature/prompt"), "[featu…]");
+        assert_eq!(truncated.apply("dev"), "[dev]   ");
+        assert_eq!(truncated.apply(""), "");
+
+        let padded = ValueFormat {
+            padding: Some(Padding::Left(3)),
+            ..ValueFormat::new("%s")
+        };
+        assert_eq!(padded.apply("2"), "  2");
+        assert_eq!(padded.apply("1234"), "1234");
+    }
+
+    #[test]
+    fn test_truncate() {
+        assert_eq!(truncate("master", 6), "master");
+        assert_eq!(truncate("日本語", 4), "日…");
+        assert_eq!(truncate("master", 0), "");
+    }
+
+    #[test]
+    fn test_format_duration() {
+        assert_eq!(format_duration(Duration::from_millis(850)), "850ms");
+        assert_eq!(format_duration(Duration::from_millis(4210)), "4.2s");
+        assert_eq!(format_duration(Duration::from_secs(192)), "3m 12s");
+        assert_eq!(format_duration(Duration::from_secs(3900)), "1h 5m");
+    }
+
+    #[test]
+    fn test_parent_pid() {
+        assert_eq!(parent_pid("42 (cargo) S 7 42 7 0"), Some("7"));
+        assert_eq!(parent_pid("43 (tmux: server) (x) R 1 43"), Some("1"));
+        assert_eq!(parent_pid("garbage"), None);
+    }
+
+    #[test]
+    fn test_process_state() {
+        assert_eq!(process_state("42 (cargo) S 7 42 7 0"), Some("S"));
+        assert_eq!(process_state("43 (tmux: server) (x) Z 1 43"), Some("Z"));
+        assert_eq!(process_state("garbage"), None);
+    }
+
+    #[test]
+    fn test_running_jobs() {
+        let mut sleeping = process::Command::new("sleep").arg("5").spawn().unwrap();
+        let mut exited = process::Command::new("true").spawn().unwrap();
+        // Until it is waited for `true` stays a zombie
+        std::thread::sleep(Duration::from_millis(200));
+
+        let pid = sleeping.id().to_string();
+        assert!(child_pids().map_or(true, |pids| pids.contains(&pid)));
+        assert!(running_jobs() >= 1);
+
+        let zombie = fs::read_to_string(format!("/proc/{}/stat", exited.id())).unwrap();
+        assert_eq!(process_state(&zombie), Some("Z"));
+
+        sleeping.kill().unwrap();
+        sleeping.wait().unwrap();
+        exited.wait().unwrap();
+    }
+
+    #[test]
+    fn test_parse_toolchain_file() {
+        assert_eq!(
+            parse_toolchain_file("nightly-2021-03-01\n"),
+            "nightly-2021-03-01"
+        );
+        assert_eq!(
+            parse_toolchain_file("[toolchain]\nchannel = \"1.51.0\"\ncomponents = [\"rustfmt\"]\n"),
+            "1.51.0"
+        );
+        assert_eq!(
+            parse_toolchain_file("[toolchain]\nprofile = \"minimal\"\n"),
+            ""
+        );
+    }
+}
@@ -0,0 +1,236 @@
+// Label of the working directory in the command bar
+//
+//      /home/alice/src/projects/civa
+//
+//      full          ~/src/projects/civa
+//      fish          ~/s/p/civa
+//      last (2)      …/projects/civa
+//      repository    civa, or civa/src/cli in a subdirectory
+//
+// `$HOME` is only replaced if the path is inside of it, `/data/alice-backup`
+// stays as it is. With a maximum width the directories in front are left
+// out until the label fits, a single directory loses its beginning.
+
+use std::env;
+use std::path::{Component, Path, PathBuf};
+
+use unicode_width::UnicodeWidthStr;
+
+use crate::config::command_bar::CwdTruncation;
+
+static ELLIPSIS: &str = "…";
+
+pub fn home_dir() -> Option<PathBuf> {
+    env::var_os("HOME")
+        .filter(|home| !home.is_empty())
+        .map(PathBuf::from)
+}
+
+// `path` with the home directory replaced by `~`
+pub fn home_relative(path: &Path, home: Option<&Path>) -> String {
+    Label::new(path, home, None).join()
+}
+
+pub fn cwd_label(
+    path: &Path,
+    home: Option<&Path>,
+    repository: Option<&Path>,
+    truncation: CwdTruncation,
+    max_width: Option<usize>,
+) -> String {
+    let repository = match truncation {
+        CwdTruncation::Repository => repository,
+        _ => None,
+    };
+    let mut label = Label::new(path, home, repository);
+
+    match truncation {
+        CwdTruncation::Fish { segments } => label.abbreviate(segments),
+        CwdTruncation::Last { segments } => label.keep_last(segments),
+        CwdTruncation::Full | CwdTruncation::Repository => {}
+    }
+
+    match max_width {
+        Some(width) => label.fit(width),
+        None => label.join(),
+    }
+}
+
+struct Label {
+    // `~`, the name of the repository, `…` once directories were left out or
+    // empty for the file system root
+    prefix: String,
+    directories: Vec<String>,
+}
+
+impl Label {
+    fn new(path: &Path, home: Option<&Path>, repository: Option<&Path>) -> Self {
+        let (prefix, rest) = match (repository, home) {
+            (Some(root), _) if path.starts_with(root) => (
+                root.file_name()
+                    .map(|name| name.to_string_lossy().into_owned())
+                    .unwrap_or_default(),
+                path.strip_prefix(root).unwrap_or(path),
+            ),
+            (_, Some(home)) if path.starts_with(home) => {
+                (String::from("~"), path.strip_prefix(home).unwrap_or(path))
+            }
+            _ => (String::new(), path),
+        };
+
+        Self {
+            prefix,
+            directories: rest
+                .components()
+                .filter_map(|component| match component {
+                    Component::Normal(name) => Some(name.to_string_lossy().into_owned()),
+                    _ => None,
+                })
+                .collect(),
+        }
+    }
+
+    // The first character of every directory but the last `keep`, hidden
+    // directories keep their dot
+    fn abbreviate(&mut self, keep: usize) {
+        let abbreviated = self.directories.len().saturating_sub(keep);
+
+        for directory in self.directories.iter_mut().take(abbreviated) {
+            let length = if directory.starts_with('.') { 2 } else { 1 };
+            *directory = directory.chars().take(length).collect();
+        }
+    }
+
+    fn keep_last(&mut self, keep: usize) {
+        if self.directories.len() > keep {
+            self.directories.drain(..self.directories.len() - keep);
+            self.prefix = String::from(ELLIPSIS);
+        }
+    }
+
+    fn fit(mut self, width: usize) -> String {
+        while UnicodeWidthStr::width(self.join().as_str()) > width && self.directories.len() > 1 {
+            self.directories.remove(0);
+            self.prefix = String::from(ELLIPSIS);
+        }
+
+        let label = self.join();
+        if UnicodeWidthStr::width(label.as_str()) <= width {
+            return label;
+        }
+
+        // The end of the last directory
+        let mut tail: Vec<char> = Vec::new();
+        let mut used = UnicodeWidthStr::width(ELLIPSIS);
+        for c in label.chars().rev() {
+            used += UnicodeWidthStr::width(c.to_string().as_str());
+            if used > width {
+                break;
+            }
+            tail.push(c);
+        }
+
+        format!("{}{}", ELLIPSIS, tail.iter().rev().collect::<String>())
+    }
+
+    fn join(&self) -> String {
+        if self.prefix.is_empty() {
+            return format!("/{}", self.directories.join("/"));
+        }
+
+        let mut parts = vec![self.prefix.clone()];
+        parts.extend(self.directories.iter().cloned());
+        parts.join("/")
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn label(path: &str, truncation: CwdTruncation, max_width: Option<usize>) -> String {
+        cwd_label(
+            Path::new(path),
+            Some(Path::new("/home/alice")),
+            Some(Path::new("/home/alice/src/civa")),
+            truncation,
+            max_width,
+        )
+    }
+
+    #[test]
+    fn test_home_relative() {
+        let home = Some(Path::new("/home/alice"));
+
+        assert_eq!(home_relative(Path::new("/home/alice/src"), home), "~/src");
+        assert_eq!(home_relative(Path::new("/home/alice"), home), "~");
+        assert_eq!(
+            home_relative(Path::new("/home/alice-backup/src"), home),
+            "/home/alice-backup/src"
+        );
+        assert_eq!(
+            home_relative(Path::new("/data/alice/src"), home),
+            "/data/alice/src"
+        );
+        assert_eq!(home_relative(Path::new("/"), home), "/");
+        assert_eq!(home_relative(Path::new("/etc"), None), "/etc");
+    }
+
+    #[test]
+    fn test_truncation() {
+        let path = "/home/alice/src/projects/.config/civa";
+
+        assert_eq!(
+            label(path, CwdTruncation::Full, None),
+            "~/src/projects/.config/civa"
+        );
+        assert_eq!(
+            label(path, CwdTruncation::Fish { segments: 1 }, None),
+            "~/s/p/.c/civa"
+        );
+        assert_eq!(
+            label(path, CwdTruncation::Fish { segments: 2 }, None),
+            "~/s/p/.config/civa"
+        );
+        assert_eq!(
+            label(path, CwdTruncation::Last { segments: 2 }, None),
+            "…/.config/civa"
+        );
+        assert_eq!(
+            label("/home/alice/src", CwdTruncation::Last { segments: 2 }, None),
+            "~/src"
+        );
+        assert_eq!(
+            label(
+                "/home/alice/src/civa/src/cli",
+                CwdTruncation::Repository,
+                None
+            ),
+            "civa/src/cli"
+        );
+        assert_eq!(
+            label("/home/alice/src/civa", CwdTruncation::Repository, None),
+            "civa"
+        );
+        assert_eq!(
+            label("/home/alice/src/other", CwdTruncation::Repository, None),
+            "~/src/other"
+        );
+    }
+
+    #[test]
+    fn test_max_width() {
+        let path = "/home/alice/src/projects/civa";
+
+        assert_eq!(
+            label(path, CwdTruncation::Full, Some(40)),
+            "~/src/projects/civa"
+        );
+        assert_eq!(
+            label(path, CwdTruncation::Full, Some(15)),
+            "…/projects/civa"
+        );
+        assert_eq!(label(path, CwdTruncation::Full, Some(8)), "…/civa");
+        assert_eq!(label(path, CwdTruncation::Full, Some(4)), "…iva");
+    }
+}
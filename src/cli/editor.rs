@@ -1,28 +1,66 @@
 use std::borrow::Cow::{self, Borrowed, Owned};
-use std::cell::RefCell;
+use std::cell::{Cell, RefCell};
+use std::path::Path;
+use std::rc::Rc;
+use std::sync::Arc;
 
+use super::completion::ShellCompleter;
+use super::highlight::SyntaxHighlighter;
+use super::history_search::HistorySearchHandler;
+use super::right_prompt::{display_width, place_right_prompt};
+use super::segments::Bar;
+use crate::command::handler::is_command_position;
+use crate::config::alias::Abbreviations;
 use crate::config::ContextManager;
+use crate::history::scope::scoped_order;
+use git::GitCli;
 use log::info;
 use rcalc::{Calculator, RuntimeItem, Value};
-use rustyline::completion::{Completer, FilenameCompleter, Pair};
+use rustyline::completion::{Completer, Pair};
 use rustyline::config::OutputStreamType;
 use rustyline::error::ReadlineError;
 use rustyline::highlight::{Highlighter, MatchingBracketHighlighter};
 use rustyline::hint::{Hinter, HistoryHinter};
 use rustyline::validate::{self, MatchingBracketValidator, Validator};
 use rustyline::Helper;
-use rustyline::{Cmd, CompletionType, Config, Context, EditMode, Editor, KeyPress};
+use rustyline::{
+    Cmd, CompletionType, ConditionalEventHandler, Config, Context, EditMode, Editor, Event,
+    EventContext, EventHandler, KeyEvent, Modifiers, Movement, RepeatCount,
+};
 use termion::color::{Fg, Green};
 
 pub struct MyHelper {
-    pub completer: FilenameCompleter,
+    pub completer: ShellCompleter,
     pub highlighter: MatchingBracketHighlighter,
+    pub syntax_highlighter: SyntaxHighlighter,
     pub validator: MatchingBracketValidator,
     pub hinter: HistoryHinter,
     pub colored_prompt: String,
+    // Rendered again on every repaint, late segments fill in
+    pub prompt_bar: Bar,
+    pub right_bar: Bar,
+    // Columns of the left prompt when reading started
+    pub prompt_width: usize,
+    // Columns of the hint shown last, the right prompt has to leave room for it
+    hint_width: Cell<usize>,
     pub calculator: RefCell<Calculator>,
 }
 
+impl MyHelper {
+    // Drawn after the input line, if it fits
+    fn right_prompt(&self, line: &str) -> Option<String> {
+        if line.contains('\n') {
+            return None;
+        }
+
+        let (columns, _) = termion::terminal_size().ok()?;
+        let cursor = self.prompt_width + display_width(line);
+        let used = cursor + self.hint_width.get();
+
+        place_right_prompt(&self.right_bar.render(), cursor, used, columns as usize)
+    }
+}
+
 impl Helper for MyHelper {}
 
 impl Completer for MyHelper {
@@ -39,7 +77,18 @@ impl Completer for MyHelper {
 }
 
 impl Hinter for MyHelper {
+    type Hint = String;
+
     fn hint(&self, line: &str, pos: usize, ctx: &Context<'_>) -> Option<String> {
+        let hint = self.hint_line(line, pos, ctx);
+        self.hint_width
+            .set(hint.as_deref().map_or(0, display_width));
+        hint
+    }
+}
+
+impl MyHelper {
+    fn hint_line(&self, line: &str, pos: usize, ctx: &Context<'_>) -> Option<String> {
         if self.calculator.borrow().is_arithmetic_expression(line) {
             let curr_str = line;
 
@@ -80,10 +129,16 @@ impl Highlighter for MyHelper {
         prompt: &'p str,
         default: bool,
     ) -> Cow<'b, str> {
-        if default {
-            Borrowed(&self.colored_prompt)
+        if !default {
+            return Borrowed(prompt);
+        }
+
+        // rustyline positions the cursor with the width it measured at first
+        let prompt = self.prompt_bar.render();
+        if display_width(&prompt) == self.prompt_width {
+            Owned(colored_prompt(&prompt))
         } else {
-            Borrowed(prompt)
+            Borrowed(&self.colored_prompt)
         }
     }
 
@@ -91,12 +146,24 @@ impl Highlighter for MyHelper {
         Owned("\x1b[1m".to_owned() + hint + "\x1b[m")
     }
 
+    // Arithmetic expressions keep the bracket matching, everything else is
+    // highlighted as shell syntax
     fn highlight<'l>(&self, line: &'l str, pos: usize) -> Cow<'l, str> {
-        self.highlighter.highlight(line, pos)
+        let highlighted = if self.calculator.borrow().is_arithmetic_expression(line) {
+            self.highlighter.highlight(line, pos)
+        } else {
+            Owned(self.syntax_highlighter.highlight(line))
+        };
+
+        match self.right_prompt(line) {
+            Some(right_prompt) => Owned(highlighted.into_owned() + &right_prompt),
+            None => highlighted,
+        }
     }
 
-    fn highlight_char(&self, line: &str, pos: usize) -> bool {
-        self.highlighter.highlight_char(line, pos)
+    // Every edit can change the highlighting, e.g. a command becomes known
+    fn highlight_char(&self, _line: &str, _pos: usize) -> bool {
+        true
     }
 }
 
@@ -112,7 +179,50 @@ impl Validator for MyHelper {
         self.validator.validate_while_typing()
     }
 }
-pub fn built_editor(ctx: &ContextManager) -> Editor<MyHelper> {
+// Expands an abbreviation in command position when space is typed after it
+struct AbbreviationHandler {
+    abbreviations: Abbreviations,
+}
+
+impl ConditionalEventHandler for AbbreviationHandler {
+    fn handle(
+        &self,
+        _evt: &Event,
+        _n: RepeatCount,
+        _positive: bool,
+        ctx: &EventContext,
+    ) -> Option<Cmd> {
+        let before_cursor = &ctx.line()[..ctx.pos()];
+        let word_start = last_word_start(before_cursor);
+        let word = &before_cursor[word_start..];
+
+        if word.is_empty() || !is_command_position(&before_cursor[..word_start]) {
+            return None;
+        }
+
+        self.abbreviations.get(word).map(|expansion| {
+            Cmd::Replace(
+                Movement::BackwardChar(word.chars().count()),
+                Some(format!("{} ", expansion)),
+            )
+        })
+    }
+}
+
+// Start of the last word, whitespace like U+3000 takes more than one byte
+fn last_word_start(before_cursor: &str) -> usize {
+    before_cursor
+        .char_indices()
+        .rev()
+        .find(|(_, c)| c.is_whitespace())
+        .map_or(0, |(i, c)| i + c.len_utf8())
+}
+
+pub fn colored_prompt(prompt: &str) -> String {
+    format!("\x1b[1;32m{}\x1b[0m", prompt)
+}
+
+pub fn built_editor(ctx: &ContextManager, cwd: &Path) -> Editor<MyHelper> {
     let config = Config::builder()
         .history_ignore_space(true)
         .completion_type(CompletionType::List)
@@ -120,26 +230,74 @@ pub fn built_editor(ctx: &ContextManager) -> Editor<MyHelper> {
         .output_stream(OutputStreamType::Stdout)
         .build();
     let h = MyHelper {
-        completer: FilenameCompleter::new(),
+        completer: ShellCompleter::new(
+            Rc::clone(&ctx.env_manager),
+            Rc::clone(&ctx.builtins),
+            Rc::clone(&ctx.alias_system),
+            Rc::clone(&ctx.completion_specs),
+        ),
         highlighter: MatchingBracketHighlighter::new(),
+        syntax_highlighter: SyntaxHighlighter::new(
+            &ctx.command_bar_config.highlight,
+            Rc::clone(&ctx.env_manager),
+            Rc::clone(&ctx.builtins),
+            Rc::clone(&ctx.alias_system),
+        ),
         hinter: HistoryHinter {},
         colored_prompt: "".to_owned(),
+        prompt_bar: Bar::default(),
+        right_bar: Bar::default(),
+        prompt_width: 0,
+        hint_width: Cell::new(0),
         validator: MatchingBracketValidator::new(),
         calculator: RefCell::new(Calculator::new()),
     };
     let mut rl = Editor::with_config(config);
     rl.set_helper(Some(h));
-    rl.bind_sequence(KeyPress::Meta('N'), Cmd::HistorySearchForward);
-    rl.bind_sequence(KeyPress::Meta('P'), Cmd::HistorySearchBackward);
-
-    info!("{}", ctx.retrieve_history_cache().as_str());
-    if rl
-        .load_history(ctx.retrieve_history_cache().as_str())
-        .is_err()
-    {
-        info!("No previous history.");
-    } else {
-        info!("Load history file succesfully");
-    }
+    rl.bind_sequence(
+        KeyEvent::ctrl('R'),
+        EventHandler::Conditional(Box::new(HistorySearchHandler::new(Arc::clone(
+            &ctx.history,
+        )))),
+    );
+    rl.bind_sequence(
+        KeyEvent::new(' ', Modifiers::NONE),
+        EventHandler::Conditional(Box::new(AbbreviationHandler {
+            abbreviations: ctx.alias_system.borrow().abbreviations(),
+        })),
+    );
+
+    populate_history(&mut rl, ctx, cwd);
+
     rl
 }
+
+// Fills the editor history (Up/Down, hints) from the history store, the
+// commands run in `cwd` and its project are reached first
+pub fn populate_history(rl: &mut Editor<MyHelper>, ctx: &ContextManager, cwd: &Path) {
+    let project = GitCli::find_git_root(cwd);
+    let history = ctx.history.read().unwrap();
+    let entries = history.entries();
+
+    rl.clear_history();
+    for i in scoped_order(entries, cwd, project.as_deref()) {
+        rl.add_history_entry(entries[i].command.as_str());
+    }
+    info!("Loaded {} history entries", rl.history().len());
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_last_word_start() {
+        assert_eq!(last_word_start("gco"), 0);
+        assert_eq!(last_word_start("sudo gco"), 5);
+        assert_eq!(last_word_start("echo\u{a0}gco"), 6);
+        assert_eq!(last_word_start("echo\u{3000}gco"), 7);
+
+        let line = "ls;\u{3000}gco";
+        assert_eq!(&line[last_word_start(line)..], "gco");
+    }
+}
@@ -0,0 +1,409 @@
+// Syntax highlighting of the input line
+//
+// The line is split into tokens without dropping a single character, every
+// token is then painted according to the `highlight` section of bar.yaml:
+//
+//      - commands and unknown commands
+//      - quoted strings and variables (`$HOME`, `${HOME}`, `$?`)
+//      - operators (`&&`, `||`, `|`, `;`, `&`) and redirections (`>`, `2>&1`)
+//      - comments
+//      - arguments naming an existing path
+//
+// Commands are green if they can be run (PATH, builtins, aliases and
+// abbreviations) and red otherwise, arguments naming an existing path are
+// underlined.
+
+use std::cell::RefCell;
+use std::env;
+use std::path::Path;
+use std::rc::Rc;
+
+use termion::style;
+
+use super::{colors, Cli};
+use crate::builtins::BuiltinRegistry;
+use crate::config::alias::AliasSystem;
+use crate::config::highlight::{HighlightConfig, HighlightStyle};
+use crate::env::environment::EnvManager;
+
+#[derive(Debug, PartialEq, Copy, Clone)]
+enum TokenKind {
+    Command,
+    Argument,
+    String,
+    Variable,
+    Operator,
+    Redirection,
+    Comment,
+    Space,
+}
+
+// Escape sequences of every token kind, rendered once from the config
+struct Palette {
+    command: String,
+    unknown_command: String,
+    string: String,
+    variable: String,
+    operator: String,
+    redirection: String,
+    comment: String,
+    path: String,
+}
+
+fn escape_sequence(highlight_style: &HighlightStyle) -> String {
+    let mut vec = Vec::new();
+
+    if let Some(color) = &highlight_style.color {
+        Cli::push_color(&mut vec, &color.color_name, colors::support());
+    }
+    Cli::push_styles(&mut vec, &highlight_style.style);
+
+    vec.join("")
+}
+
+impl Palette {
+    fn from_config(config: &HighlightConfig) -> Self {
+        Self {
+            command: escape_sequence(&config.command),
+            unknown_command: escape_sequence(&config.unknown_command),
+            string: escape_sequence(&config.string),
+            variable: escape_sequence(&config.variable),
+            operator: escape_sequence(&config.operator),
+            redirection: escape_sequence(&config.redirection),
+            comment: escape_sequence(&config.comment),
+            path: escape_sequence(&config.path),
+        }
+    }
+}
+
+pub struct SyntaxHighlighter {
+    palette: Palette,
+    env_manager: Rc<EnvManager>,
+    builtins: Rc<RefCell<BuiltinRegistry>>,
+    alias_system: Rc<RefCell<AliasSystem>>,
+}
+
+impl SyntaxHighlighter {
+    pub fn new(
+        config: &HighlightConfig,
+        env_manager: Rc<EnvManager>,
+        builtins: Rc<RefCell<BuiltinRegistry>>,
+        alias_system: Rc<RefCell<AliasSystem>>,
+    ) -> Self {
+        Self {
+            palette: Palette::from_config(config),
+            env_manager,
+            builtins,
+            alias_system,
+        }
+    }
+
+    pub fn highlight(&self, line: &str) -> String {
+        let mut highlighted = String::with_capacity(line.len() * 2);
+
+        for (kind, start, end) in tokenize(line) {
+            let text = &line[start..end];
+
+            let escape = match kind {
+                TokenKind::Command if self.is_known_command(text) => &self.palette.command,
+                TokenKind::Command => &self.palette.unknown_command,
+                TokenKind::Argument if is_existing_path(text) => &self.palette.path,
+                TokenKind::String => &self.palette.string,
+                TokenKind::Variable => &self.palette.variable,
+                TokenKind::Operator => &self.palette.operator,
+                TokenKind::Redirection => &self.palette.redirection,
+                TokenKind::Comment => &self.palette.comment,
+                TokenKind::Argument | TokenKind::Space => {
+                    highlighted.push_str(text);
+                    continue;
+                }
+            };
+
+            highlighted.push_str(escape);
+            highlighted.push_str(text);
+            highlighted.push_str(&format!("{}", style::Reset));
+        }
+
+        highlighted
+    }
+
+    fn is_known_command(&self, name: &str) -> bool {
+        if name.contains('/') {
+            return is_existing_path(name);
+        }
+
+        let alias_system = self.alias_system.borrow();
+
+        self.env_manager.has_command(name)
+            || self.builtins.borrow().contains(name)
+            || alias_system.get_alias(String::from(name)).is_some()
+            || alias_system.abbreviations().get(name).is_some()
+    }
+}
+
+fn is_existing_path(word: &str) -> bool {
+    match word.strip_prefix('~') {
+        Some(rest) => env::var("HOME")
+            .map(|home| Path::new(&format!("{}{}", home, rest)).exists())
+            .unwrap_or(false),
+        None => Path::new(word).exists(),
+    }
+}
+
+fn is_operator_char(c: char) -> bool {
+    c == '|' || c == '&' || c == ';'
+}
+
+fn is_redirection_char(c: char) -> bool {
+    c == '>' || c == '<'
+}
+
+// Length of the operator or redirection at the start of `rest`, if any
+fn operator_len(rest: &str) -> Option<(TokenKind, usize)> {
+    for operator in &["&&", "||", "|", ";"] {
+        if rest.starts_with(operator) {
+            return Some((TokenKind::Operator, operator.len()));
+        }
+    }
+
+    // `>`, `>>`, `<`, `2>`, `2>&1`, `&>`
+    let digits = rest.chars().take_while(char::is_ascii_digit).count();
+    let after_digits = &rest[digits..];
+    let redirect = if after_digits.starts_with(">>") {
+        2
+    } else if after_digits.starts_with('>') || after_digits.starts_with('<') {
+        1
+    } else if digits == 0 && rest.starts_with("&>") {
+        return Some((TokenKind::Redirection, 2));
+    } else {
+        return if rest.starts_with('&') {
+            Some((TokenKind::Operator, 1))
+        } else {
+            None
+        };
+    };
+
+    let mut len = digits + redirect;
+    // Duplicated file descriptor, `2>&1`
+    if rest[len..].starts_with('&') {
+        len += 1 + rest[len + 1..]
+            .chars()
+            .take_while(char::is_ascii_digit)
+            .count();
+    }
+
+    Some((TokenKind::Redirection, len))
+}
+
+// Splits the line into (kind, start, end) byte ranges covering every character
+fn tokenize(line: &str) -> Vec<(TokenKind, usize, usize)> {
+    let mut tokens = Vec::new();
+    let mut expect_command = true;
+    let mut i = 0;
+
+    while i < line.len() {
+        let rest = &line[i..];
+        let c = rest.chars().next().unwrap();
+
+        if c.is_whitespace() {
+            let len = rest
+                .find(|c: char| !c.is_whitespace())
+                .unwrap_or(rest.len());
+            tokens.push((TokenKind::Space, i, i + len));
+            i += len;
+            continue;
+        }
+
+        if c == '#' {
+            tokens.push((TokenKind::Comment, i, line.len()));
+            break;
+        }
+
+        if let Some((kind, len)) = operator_len(rest) {
+            // Redirections are followed by a file, not by a command
+            expect_command = kind == TokenKind::Operator;
+            tokens.push((kind, i, i + len));
+            i += len;
+            continue;
+        }
+
+        let word_end = i + word_len(rest);
+        tokenize_word(line, i, word_end, expect_command, &mut tokens);
+        expect_command = false;
+        i = word_end;
+    }
+
+    tokens
+}
+
+// Length of the word at the start of `rest`, quotes may contain whitespace
+fn word_len(rest: &str) -> usize {
+    let mut quote: Option<char> = None;
+
+    for (i, c) in rest.char_indices() {
+        match quote {
+            Some(q) if c == q => quote = None,
+            Some(_) => {}
+            None if c == '"' || c == '\'' => quote = Some(c),
+            None if c.is_whitespace() || is_operator_char(c) || is_redirection_char(c) => return i,
+            None => {}
+        }
+    }
+
+    rest.len()
+}
+
+// A plain word is a single token, words containing quotes or variables
+// are split into their parts
+fn tokenize_word(
+    line: &str,
+    start: usize,
+    end: usize,
+    is_command: bool,
+    tokens: &mut Vec<(TokenKind, usize, usize)>,
+) {
+    let word = &line[start..end];
+
+    if !word.contains(|c| c == '"' || c == '\'' || c == '$') {
+        let kind = if is_command {
+            TokenKind::Command
+        } else {
+            TokenKind::Argument
+        };
+        tokens.push((kind, start, end));
+        return;
+    }
+
+    let mut plain_start = 0;
+    let mut i = 0;
+
+    while i < word.len() {
+        let rest = &word[i..];
+        let c = rest.chars().next().unwrap();
+
+        let len = match c {
+            '"' | '\'' => rest[1..].find(c).map_or(rest.len(), |close| close + 2),
+            '$' => variable_len(rest),
+            _ => 0,
+        };
+
+        if len == 0 {
+            i += c.len_utf8();
+            continue;
+        }
+
+        if plain_start < i {
+            tokens.push((TokenKind::Argument, start + plain_start, start + i));
+        }
+
+        let kind = if c == '$' {
+            TokenKind::Variable
+        } else {
+            TokenKind::String
+        };
+        tokens.push((kind, start + i, start + i + len));
+
+        i += len;
+        plain_start = i;
+    }
+
+    if plain_start < word.len() {
+        tokens.push((TokenKind::Argument, start + plain_start, end));
+    }
+}
+
+// `$NAME`, `${NAME}` and `$?`, 0 if `$` does not start a variable
+fn variable_len(rest: &str) -> usize {
+    let name = &rest[1..];
+
+    if name.starts_with('{') {
+        return name.find('}').map_or(rest.len(), |close| close + 2);
+    }
+
+    if name.starts_with('?') {
+        return 2;
+    }
+
+    let len = name
+        .find(|c: char| !(c.is_ascii_alphanumeric() || c == '_'))
+        .unwrap_or(name.len());
+
+    if len == 0 {
+        0
+    } else {
+        len + 1
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn kinds(line: &str) -> Vec<(TokenKind, &str)> {
+        tokenize(line)
+            .into_iter()
+            .filter(|(kind, _, _)| *kind != TokenKind::Space)
+            .map(|(kind, start, end)| (kind, &line[start..end]))
+            .collect()
+    }
+
+    #[test]
+    fn test_tokenize_covers_whole_line() {
+        let line = "echo \"a b\" $HOME>out 2>&1 | grep x # done";
+        let joined: String = tokenize(line)
+            .into_iter()
+            .map(|(_, start, end)| &line[start..end])
+            .collect();
+
+        assert_eq!(joined, line);
+    }
+
+    #[test]
+    fn test_tokenize_kinds() {
+        assert_eq!(
+            kinds("git commit -m \"a b\" && ls ${HOME}/x # c"),
+            vec![
+                (TokenKind::Command, "git"),
+                (TokenKind::Argument, "commit"),
+                (TokenKind::Argument, "-m"),
+                (TokenKind::String, "\"a b\""),
+                (TokenKind::Operator, "&&"),
+                (TokenKind::Command, "ls"),
+                (TokenKind::Variable, "${HOME}"),
+                (TokenKind::Argument, "/x"),
+                (TokenKind::Comment, "# c"),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_tokenize_redirections() {
+        assert_eq!(
+            kinds("make 2>&1 >> log|less"),
+            vec![
+                (TokenKind::Command, "make"),
+                (TokenKind::Redirection, "2>&1"),
+                (TokenKind::Redirection, ">>"),
+                (TokenKind::Argument, "log"),
+                (TokenKind::Operator, "|"),
+                (TokenKind::Command, "less"),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_highlight_unknown_command() {
+        let highlighter = SyntaxHighlighter::new(
+            &HighlightConfig::default(),
+            Rc::new(EnvManager::new()),
+            Rc::new(RefCell::new(BuiltinRegistry::with_defaults())),
+            Rc::new(RefCell::new(AliasSystem::new())),
+        );
+
+        let known = highlighter.highlight("cd");
+        let unknown = highlighter.highlight("surely-not-a-command-42");
+
+        assert!(known.starts_with(&highlighter.palette.command));
+        assert!(unknown.starts_with(&highlighter.palette.unknown_command));
+    }
+}
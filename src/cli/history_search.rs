@@ -0,0 +1,488 @@
+// Fuzzy history search, bound to Ctrl-R
+//
+// A full screen list of the history, most recent first, narrowed down while
+// typing. Every command is listed once with the exit status and directory of
+// its last run, the matched characters are highlighted and the best matches
+// come first.
+//
+//      Up/Down, Ctrl-P/Ctrl-N  move the selection
+//      Enter                   put the selected command on the input line
+//      Esc, Ctrl-C, Ctrl-G     leave without changing the input line
+//      Ctrl-D                  toggle: only commands run in this directory
+//      Ctrl-S                  toggle: only commands that succeeded
+//      Ctrl-U                  clear the query
+//
+// The input line is the initial query.
+
+use std::collections::HashSet;
+use std::env;
+use std::fs::File;
+use std::io::{self, Read, Write};
+
+use log::warn;
+use rustyline::{Cmd, ConditionalEventHandler, Event, EventContext, Movement, RepeatCount};
+use termion::color::{self, Fg};
+use termion::event::{parse_event, Event as TermEvent, Key};
+use termion::screen::AlternateScreen;
+use termion::{clear, cursor, style};
+
+use super::Cli;
+use crate::history::{HistoryEntry, SharedHistory};
+
+// Width of the directory column
+static CWD_WIDTH: usize = 24;
+
+pub struct HistorySearchHandler {
+    history: SharedHistory,
+}
+
+impl HistorySearchHandler {
+    pub fn new(history: SharedHistory) -> Self {
+        Self { history }
+    }
+}
+
+impl ConditionalEventHandler for HistorySearchHandler {
+    fn handle(
+        &self,
+        _evt: &Event,
+        _n: RepeatCount,
+        _positive: bool,
+        ctx: &EventContext,
+    ) -> Option<Cmd> {
+        let line = ctx.line();
+
+        let selected = match search(&self.history, line) {
+            Ok(selected) => selected,
+            Err(err) => {
+                warn!("History search failed: {}", err);
+                None
+            }
+        };
+
+        // Replacing the line also redraws it after leaving the alternate screen
+        Some(Cmd::Replace(
+            Movement::WholeBuffer,
+            Some(selected.unwrap_or_else(|| String::from(line))),
+        ))
+    }
+}
+
+#[derive(Debug, Default)]
+struct SearchState {
+    query: String,
+    only_cwd: bool,
+    only_successful: bool,
+    // Position in the matches
+    selected: usize,
+}
+
+#[derive(Debug, PartialEq)]
+struct Match {
+    // Position in the entries
+    index: usize,
+    score: i64,
+    // Indices of the matched characters of the command
+    positions: Vec<usize>,
+}
+
+// Scores `text` for the characters of `query` appearing in order. Case is
+// ignored unless the query contains an uppercase character.
+fn fuzzy_match(query: &str, text: &str) -> Option<(i64, Vec<usize>)> {
+    let case_sensitive = query.chars().any(char::is_uppercase);
+    let normalize = |c: char| {
+        if case_sensitive {
+            c
+        } else {
+            c.to_ascii_lowercase()
+        }
+    };
+
+    let query: Vec<char> = query.chars().map(normalize).collect();
+    let text: Vec<char> = text.chars().collect();
+
+    if query.is_empty() {
+        return Some((0, Vec::new()));
+    }
+
+    // Leftmost match to find where the match ends at the earliest
+    let mut q = 0;
+    let mut end = None;
+    for (i, c) in text.iter().enumerate() {
+        if normalize(*c) == query[q] {
+            q += 1;
+            if q == query.len() {
+                end = Some(i);
+                break;
+            }
+        }
+    }
+    let end = end?;
+
+    // Then backwards from there, for the shortest span
+    let mut positions = Vec::with_capacity(query.len());
+    let mut q = query.len();
+    for i in (0..=end).rev() {
+        if normalize(text[i]) == query[q - 1] {
+            positions.push(i);
+            q -= 1;
+            if q == 0 {
+                break;
+            }
+        }
+    }
+    positions.reverse();
+
+    let mut score = 0;
+    for (n, position) in positions.iter().enumerate() {
+        score += 16;
+
+        let at_word_start = *position == 0 || " /-_.=".contains(text[*position - 1]);
+        if at_word_start {
+            score += 8;
+        }
+        if n > 0 && positions[n - 1] + 1 == *position {
+            score += 8;
+        }
+    }
+
+    let span = (positions[positions.len() - 1] - positions[0] + 1) as i64;
+    score -= span - query.len() as i64;
+
+    Some((score, positions))
+}
+
+// The matching entries, each command once with its most recent run, best
+// matches first and more recent ones first among equally good matches
+fn find_matches(entries: &[HistoryEntry], state: &SearchState, cwd: &str) -> Vec<Match> {
+    let mut seen = HashSet::new();
+    let mut matches = Vec::new();
+
+    for (index, entry) in entries.iter().enumerate().rev() {
+        if state.only_cwd && entry.cwd != cwd {
+            continue;
+        }
+        if state.only_successful && entry.exit_status != 0 {
+            continue;
+        }
+        if !seen.insert(entry.command.as_str()) {
+            continue;
+        }
+
+        if let Some((score, positions)) = fuzzy_match(&state.query, &entry.command) {
+            matches.push(Match {
+                index,
+                score,
+                positions,
+            });
+        }
+    }
+
+    // Stable, so the order by recency is kept for equal scores
+    matches.sort_by_key(|m| std::cmp::Reverse(m.score));
+    matches
+}
+
+#[derive(Debug, PartialEq)]
+enum KeyAction {
+    Accept,
+    Cancel,
+    // The selection moved
+    Move,
+    // The query or the toggles changed
+    Refilter,
+    Ignore,
+}
+
+fn handle_key(state: &mut SearchState, key: Key, match_count: usize) -> KeyAction {
+    match key {
+        Key::Char('\n') => KeyAction::Accept,
+        Key::Esc | Key::Ctrl('c') | Key::Ctrl('g') => KeyAction::Cancel,
+        Key::Up | Key::Ctrl('p') => {
+            state.selected = state.selected.saturating_sub(1);
+            KeyAction::Move
+        }
+        Key::Down | Key::Ctrl('n') => {
+            if state.selected + 1 < match_count {
+                state.selected += 1;
+            }
+            KeyAction::Move
+        }
+        Key::Ctrl('d') => {
+            state.only_cwd = !state.only_cwd;
+            KeyAction::Refilter
+        }
+        Key::Ctrl('s') => {
+            state.only_successful = !state.only_successful;
+            KeyAction::Refilter
+        }
+        Key::Ctrl('u') => {
+            state.query.clear();
+            KeyAction::Refilter
+        }
+        Key::Backspace | Key::Ctrl('h') => match state.query.pop() {
+            Some(_) => KeyAction::Refilter,
+            None => KeyAction::Ignore,
+        },
+        Key::Char(c) if !c.is_control() => {
+            state.query.push(c);
+            KeyAction::Refilter
+        }
+        _ => KeyAction::Ignore,
+    }
+}
+
+// Blocks until input is available and returns all keys read at once
+fn read_keys(tty: &mut File) -> io::Result<Vec<Key>> {
+    let mut buffer = [0; 256];
+    let len = tty.read(&mut buffer)?;
+
+    // termion waits for the rest of an escape sequence, Esc on its own is a key
+    if buffer[..len] == [0x1b] {
+        return Ok(vec![Key::Esc]);
+    }
+
+    let mut bytes = buffer[..len].iter().map(|b| Ok(*b));
+    let mut keys = Vec::new();
+    while let Some(Ok(byte)) = bytes.next() {
+        if let Ok(TermEvent::Key(key)) = parse_event(byte, &mut bytes) {
+            keys.push(key);
+        }
+    }
+
+    Ok(keys)
+}
+
+// Runs the search on the alternate screen, returns the selected command
+fn search(history: &SharedHistory, line: &str) -> io::Result<Option<String>> {
+    let history = history.read().unwrap();
+    let entries = history.entries();
+    let cwd = env::current_dir()
+        .map(|dir| dir.to_string_lossy().into_owned())
+        .unwrap_or_default();
+
+    // The terminal is in raw mode already, rustyline is reading a line
+    let mut tty = termion::get_tty()?;
+    let mut screen = AlternateScreen::from(io::stdout());
+    write!(screen, "{}", cursor::Hide)?;
+
+    let mut state = SearchState {
+        query: String::from(line),
+        ..SearchState::default()
+    };
+    let mut matches = find_matches(entries, &state, &cwd);
+
+    let selected = 'search: loop {
+        render(&mut screen, entries, &matches, &state, &cwd)?;
+
+        for key in read_keys(&mut tty)? {
+            match handle_key(&mut state, key, matches.len()) {
+                KeyAction::Accept => break 'search matches.get(state.selected),
+                KeyAction::Cancel => break 'search None,
+                KeyAction::Refilter => {
+                    matches = find_matches(entries, &state, &cwd);
+                    state.selected = 0;
+                }
+                KeyAction::Move | KeyAction::Ignore => {}
+            }
+        }
+    };
+
+    write!(screen, "{}", cursor::Show)?;
+    screen.flush()?;
+    Ok(selected.map(|m| entries[m.index].command.clone()))
+}
+
+fn toggle_label(label: &str, enabled: bool) -> String {
+    format!("[{}] {}", if enabled { "x" } else { " " }, label)
+}
+
+// The end of the directory if it does not fit the column
+fn cwd_label(cwd: &str) -> String {
+    let cwd = Cli::shrink_user_dir(String::from(cwd));
+    let len = cwd.chars().count();
+
+    if len <= CWD_WIDTH {
+        return format!("{:<width$}", cwd, width = CWD_WIDTH);
+    }
+
+    let tail: String = cwd.chars().skip(len - CWD_WIDTH + 1).collect();
+    format!("…{}", tail)
+}
+
+fn render(
+    screen: &mut dyn Write,
+    entries: &[HistoryEntry],
+    matches: &[Match],
+    state: &SearchState,
+    cwd: &str,
+) -> io::Result<()> {
+    let (width, height) = termion::terminal_size()?;
+    let width = width as usize;
+    let rows = (height as usize).saturating_sub(2);
+
+    write!(screen, "{}{}", clear::All, cursor::Goto(1, 1))?;
+
+    // Query and toggles
+    let status = format!(
+        "{}  {}  {}/{}",
+        toggle_label("this directory", state.only_cwd),
+        toggle_label("successful", state.only_successful),
+        matches.len(),
+        entries.len()
+    );
+    let query_width = width.saturating_sub(status.chars().count() + 3);
+    let query: String = state.query.chars().take(query_width).collect();
+    write!(
+        screen,
+        "{}> {}{}{:>pad$}{}",
+        style::Bold,
+        style::Reset,
+        query,
+        status,
+        style::Reset,
+        pad = width.saturating_sub(query.chars().count() + 2)
+    )?;
+
+    // Keep the selection on screen
+    let first = (state.selected + 1).saturating_sub(rows);
+    for (row, m) in matches.iter().enumerate().skip(first).take(rows) {
+        let entry = &entries[m.index];
+        let is_selected = row == state.selected;
+
+        write!(screen, "{}", cursor::Goto(1, (row - first + 2) as u16))?;
+        if is_selected {
+            write!(screen, "{}▶ ", style::Bold)?;
+        } else {
+            write!(screen, "  ")?;
+        }
+
+        if entry.exit_status == 0 {
+            write!(screen, "{}", Fg(color::Green))?;
+        } else {
+            write!(screen, "{}", Fg(color::Red))?;
+        }
+        write!(screen, "{:>3} ", entry.exit_status)?;
+
+        let cwd_color = if entry.cwd == cwd {
+            format!("{}", Fg(color::Blue))
+        } else {
+            format!("{}", Fg(color::LightBlack))
+        };
+        write!(
+            screen,
+            "{}{} {}",
+            cwd_color,
+            cwd_label(&entry.cwd),
+            Fg(color::Reset)
+        )?;
+
+        // Marker, status and directory columns
+        let command_width = width.saturating_sub(2 + 4 + CWD_WIDTH + 1);
+        for (i, c) in entry.command.chars().take(command_width).enumerate() {
+            if m.positions.contains(&i) {
+                write!(screen, "{}{}{}", Fg(color::Yellow), c, Fg(color::Reset))?;
+            } else {
+                write!(screen, "{}", c)?;
+            }
+        }
+        write!(screen, "{}", style::Reset)?;
+    }
+
+    write!(
+        screen,
+        "{}{}enter select  esc cancel  ^d this directory  ^s successful  ^u clear{}",
+        cursor::Goto(1, height),
+        Fg(color::LightBlack),
+        style::Reset
+    )?;
+
+    screen.flush()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn entry(command: &str, cwd: &str, exit_status: i32) -> HistoryEntry {
+        HistoryEntry::for_test(command, cwd, exit_status)
+    }
+
+    #[test]
+    fn test_fuzzy_match() {
+        assert_eq!(fuzzy_match("", "ls").map(|(_, p)| p), Some(vec![]));
+        assert_eq!(
+            fuzzy_match("gch", "git checkout").map(|(_, p)| p),
+            Some(vec![0, 4, 5])
+        );
+        assert_eq!(fuzzy_match("xyz", "git checkout"), None);
+        assert_eq!(fuzzy_match("GIT", "git status"), None);
+        assert!(fuzzy_match("git", "GIT status").is_some());
+
+        // The shortest span is highlighted, `status` instead of `s...tatus`
+        assert_eq!(
+            fuzzy_match("status", "ls src && git status").map(|(_, p)| p),
+            Some(vec![14, 15, 16, 17, 18, 19])
+        );
+
+        // Consecutive characters at a word start score higher
+        let (tight, _) = fuzzy_match("build", "cargo build").unwrap();
+        let (scattered, _) = fuzzy_match("build", "bat ui lsd").unwrap();
+        assert!(tight > scattered);
+    }
+
+    #[test]
+    fn test_find_matches() {
+        let entries = vec![
+            entry("cargo build", "/src", 0),
+            entry("cargo test", "/src", 101),
+            entry("cat notes", "/tmp", 0),
+            entry("cargo build", "/tmp", 1),
+        ];
+
+        let mut state = SearchState::default();
+        let indices = |state: &SearchState| -> Vec<usize> {
+            find_matches(&entries, state, "/src")
+                .iter()
+                .map(|m| m.index)
+                .collect()
+        };
+
+        // Most recent first, `cargo build` only once
+        assert_eq!(indices(&state), vec![3, 2, 1]);
+
+        state.query = String::from("cb");
+        assert_eq!(indices(&state), vec![3]);
+
+        state.only_cwd = true;
+        assert_eq!(indices(&state), vec![0]);
+
+        state.query.clear();
+        state.only_successful = true;
+        assert_eq!(indices(&state), vec![0]);
+    }
+
+    #[test]
+    fn test_handle_key() {
+        let mut state = SearchState::default();
+
+        assert_eq!(
+            handle_key(&mut state, Key::Char('g'), 0),
+            KeyAction::Refilter
+        );
+        assert_eq!(state.query, "g");
+        assert_eq!(handle_key(&mut state, Key::Down, 2), KeyAction::Move);
+        assert_eq!(handle_key(&mut state, Key::Down, 2), KeyAction::Move);
+        assert_eq!(state.selected, 1);
+        assert_eq!(
+            handle_key(&mut state, Key::Ctrl('d'), 2),
+            KeyAction::Refilter
+        );
+        assert!(state.only_cwd);
+        assert_eq!(
+            handle_key(&mut state, Key::Char('\n'), 2),
+            KeyAction::Accept
+        );
+        assert_eq!(handle_key(&mut state, Key::Esc, 2), KeyAction::Cancel);
+    }
+}
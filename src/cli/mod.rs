@@ -1,4 +1,12 @@
+mod colors;
+mod completion;
+mod components;
+mod cwd;
 mod editor;
+mod highlight;
+mod history_search;
+mod right_prompt;
+mod segments;
 
 // Construct the command line cli
 // In the future this should all be able to be customized through
@@ -21,96 +29,197 @@ mod editor;
 //
 
 use std::env::{current_dir, var};
+use std::path::{Path, PathBuf};
 
-use crate::config::command_bar::CommandBarComponents;
+use crate::config::command_bar::{CommandBarComponents, Component, CwdTruncation};
 use crate::config::ContextManager;
+use crate::vcs;
 
-use crate::config::{ColorName, StyleName};
+use crate::config::{Color, ColorName, Style, StyleName};
+use colors::ColorSupport;
+use components::{background_job, component_value, ValueFormat};
 use editor::built_editor;
+use editor::colored_prompt;
+use editor::populate_history;
 use editor::MyHelper;
+use right_prompt::display_width;
+use segments::{AsyncSegments, Background, Bar, Direction, Piece};
 
-use git::GitCli;
+use log::warn;
 
 use rustyline::Editor;
 use termion::color as termion_colors;
 use termion::color::Fg;
 use termion::style;
 
+static INCOGNITO_MARKER: &str = "[incognito]";
+
 pub struct Cli {
     pub context: ContextManager,
     pub editor: Editor<MyHelper>,
+    // Directory the editor history is ordered for
+    history_dir: PathBuf,
+    segments: AsyncSegments,
 }
 
 impl Cli {
     pub fn new() -> Self {
         let context = ContextManager::init();
+        let history_dir = current_dir().unwrap_or_default();
 
         // info!("{:?}", conf);
 
         Self {
-            editor: built_editor(&context),
+            editor: built_editor(&context, &history_dir),
             context,
+            history_dir,
+            segments: AsyncSegments::new(),
         }
     }
 
-    fn get_cwd_label() -> String {
-        Cli::shrink_user_dir(String::from(current_dir().unwrap().to_str().unwrap()))
+    fn get_cwd_label(truncation: CwdTruncation, max_width: Option<usize>) -> String {
+        let cwd = current_dir().unwrap_or_default();
+
+        // Only looked up if it is shown
+        let repository = match truncation {
+            CwdTruncation::Repository => vcs::detect(&cwd).map(|(_, root)| root),
+            _ => None,
+        };
+
+        cwd::cwd_label(
+            &cwd,
+            cwd::home_dir().as_deref(),
+            repository.as_deref(),
+            truncation,
+            max_width,
+        )
     }
 
     fn shrink_user_dir(cwd: String) -> String {
-        let user = Cli::get_current_user();
-
-        if cwd.contains(user.as_str()) {
-            let v: Vec<&str> = cwd.split(user.as_str()).collect();
-            return format!("~{}", v[1]);
-        }
-        cwd
+        cwd::home_relative(Path::new(&cwd), cwd::home_dir().as_deref())
     }
 
     pub fn update(&mut self) -> String {
-        let p = self.build_cmd_bar();
-        self.editor.helper_mut().expect("No helper").colored_prompt =
-            format!("\x1b[1;32m{}\x1b[0m", p);
+        let bar = self.build_cmd_bar();
+        let right_bar = self.build_right_bar();
+        let p = bar.render();
+
+        let helper = self.editor.helper_mut().expect("No helper");
+        helper.colored_prompt = colored_prompt(&p);
+        helper.prompt_width = display_width(&p);
+        helper.prompt_bar = bar;
+        helper.right_bar = right_bar;
 
         p
     }
 
+    // Reads a line, late command bar segments repaint the prompt meanwhile
+    pub fn readline(&mut self, prompt: &str) -> rustyline::Result<String> {
+        self.segments.set_reading(true);
+        let line = self.editor.readline(prompt);
+        self.segments.set_reading(false);
+
+        line
+    }
+
+    // Entries were removed from the history store, e.g. by the `history` builtin
+    pub fn reload_history(&mut self) {
+        self.history_dir = current_dir().unwrap_or_default();
+        populate_history(&mut self.editor, &self.context, &self.history_dir);
+    }
+
+    // Reorders the editor history after the working directory changed
+    pub fn update_history_scope(&mut self) {
+        if current_dir().map_or(false, |dir| dir != self.history_dir) {
+            self.reload_history();
+        }
+    }
+
+    // Adds the entries recorded by other sessions to the editor history
+    pub fn import_history(&mut self) {
+        let imported = self.context.history.write().unwrap().import();
+
+        match imported {
+            Ok(true) => self.reload_history(),
+            Ok(false) => {}
+            Err(err) => warn!("Could not import history: {}", err),
+        }
+    }
+
     fn get_current_user() -> String {
         var("USER").unwrap_or_default()
     }
 
-    fn push_color(vec: &mut Vec<String>, color_name: &ColorName) {
-        // Paint Color
-        match color_name {
-            ColorName::YELLOW => {
-                vec.push(format!("{}", Fg(termion_colors::Yellow)));
-            }
-            ColorName::WHITE => {
-                vec.push(format!("{}", Fg(termion_colors::White)));
-            }
+    fn push_color(vec: &mut Vec<String>, color_name: &ColorName, support: ColorSupport) {
+        vec.push(colors::color_sequence(color_name, support, false));
+    }
 
-            ColorName::BLUE => {
-                vec.push(format!("{}", Fg(termion_colors::Blue)));
-            }
+    fn push_background(vec: &mut Vec<String>, color_name: &ColorName, support: ColorSupport) {
+        vec.push(colors::color_sequence(color_name, support, true));
+    }
 
-            ColorName::RED => {
-                vec.push(format!("{}", Fg(termion_colors::Red)));
-            }
-            ColorName::GREEN => {
-                vec.push(format!("{}", Fg(termion_colors::Green)));
-            }
-            _ => {
-                vec.push(format!("{}", Fg(termion_colors::Blue)));
-            }
+    // The background of a powerline segment
+    fn segment_background(background: &Option<Color>) -> Background {
+        match background {
+            Some(color) => Background {
+                background: colors::background(&color.color_name),
+                foreground: colors::foreground(&color.color_name),
+            },
+            None => Background::default(),
+        }
+    }
+
+    // Formatted content of a component, empty if there is nothing to show
+    fn component_content(&self, config: &Component) -> String {
+        let value = match &config.component_type {
+            CommandBarComponents::CWD {
+                truncation,
+                max_width,
+            } => Cli::get_cwd_label(*truncation, max_width.or(config.max_width)),
+            CommandBarComponents::USER => Cli::get_current_user(),
+            CommandBarComponents::PROMPT => self.context.command_bar_config.prompt.symbol.clone(),
+            other => component_value(other, &self.context),
         };
+
+        ValueFormat::of(config).apply(&value)
     }
 
-    fn push_component_content(vec: &mut Vec<String>, component_type: &CommandBarComponents) {
-        match component_type {
-            CommandBarComponents::CWD => vec.push(Cli::get_cwd_label()),
-            CommandBarComponents::SVN => vec.push(GitCli::compose_git_component()),
-            CommandBarComponents::USER => vec.push(Cli::get_current_user()),
-            _ => {}
+    // A component between `before` and `after`, nothing if it is empty.
+    // Slow components are rendered in the background.
+    fn component_piece(&self, config: &Component, before: String, after: String) -> Piece {
+        if let Some(job) = background_job(&config.component_type) {
+            let name = format!("{:?}", config.component_type);
+            return Piece::Async {
+                before,
+                after,
+                format: ValueFormat::of(config),
+                value: self
+                    .segments
+                    .render(&name, job, config.timeout, &config.placeholder),
+            };
+        }
+
+        let content = self.component_content(config);
+        if content.is_empty() {
+            return Piece::Text(content);
+        }
+
+        Piece::Text(format!("{}{}{}", before, content, after))
+    }
+
+    // The color of the config, the status component turns to its failure color
+    fn component_color<'a>(&self, config: &'a Component) -> &'a ColorName {
+        match &config.component_type {
+            CommandBarComponents::STATUS { failure_color }
+                if self
+                    .context
+                    .last_exit_status
+                    .get()
+                    .map_or(false, |code| code != 0) =>
+            {
+                &failure_color.color_name
+            }
+            _ => &config.color.color_name,
         }
     }
 
@@ -123,54 +232,176 @@ impl Cli {
             StyleName::ITALIC => {
                 vec.push(format!("{}", style::Italic));
             }
+
+            StyleName::UNDERLINE => {
+                vec.push(format!("{}", style::Underline));
+            }
+
+            StyleName::DIM => {
+                vec.push(format!("{}", style::Faint));
+            }
+
+            StyleName::REVERSE => {
+                vec.push(format!("{}", style::Invert));
+            }
+
+            StyleName::STRIKETHROUGH => {
+                vec.push(format!("{}", style::CrossedOut));
+            }
             _ => {}
         };
     }
 
-    fn build_cmd_bar(&self) -> String {
-        let mut vec: Vec<String> =
-            Vec::with_capacity(self.context.command_bar_config.components.len());
-        for config in &self.context.command_bar_config.components {
-            Cli::push_color(&mut vec, &config.color.color_name);
-
-            vec.push(config.sorround.left.clone());
+    fn push_styles(vec: &mut Vec<String>, style: &Style) {
+        for style_name in &style.style_names {
+            Cli::push_style(vec, style_name);
+        }
+    }
 
-            // Build Component Content
-            Cli::push_component_content(&mut vec, &config.component_type);
+    // Escape sequences starting a component. Powerline segments are padded
+    // on their background.
+    fn component_start(
+        color_name: &ColorName,
+        background: &Option<Color>,
+        style: &Style,
+        powerline: bool,
+    ) -> Vec<String> {
+        let mut vec = Vec::new();
+
+        if let Some(background) = background {
+            Cli::push_background(&mut vec, &background.color_name, colors::support());
+        }
+        Cli::push_color(&mut vec, color_name, colors::support());
+        Cli::push_styles(&mut vec, style);
 
-            vec.push(config.sorround.right.clone());
+        if powerline {
             vec.push(String::from(" "));
+        }
 
-            // Set Style
-            Cli::push_style(&mut vec, &config.style.style_name);
+        vec
+    }
 
-            vec.push(format!("{}", style::Reset));
+    // A styled component followed by `trailing`, the text of templates as it is
+    fn component_segment(
+        &self,
+        config: &Component,
+        powerline: bool,
+        trailing: &str,
+    ) -> (Piece, Background) {
+        if let CommandBarComponents::TEXT { text } = &config.component_type {
+            return (Piece::Text(text.clone()), Background::default());
         }
 
-        // Add prompt
-        Cli::push_color(
-            &mut vec,
-            &self.context.command_bar_config.prompt.color.color_name,
+        let mut before = Cli::component_start(
+            self.component_color(config),
+            &config.background,
+            &config.style,
+            powerline,
         );
-        vec.push(self.context.command_bar_config.prompt.sorround.left.clone());
-        vec.push(self.context.command_bar_config.prompt.symbol.clone());
-        vec.push(
-            self.context
-                .command_bar_config
-                .prompt
-                .sorround
-                .right
-                .clone(),
+        before.push(config.sorround.left.clone());
+
+        // Powerline segments are padded on their background
+        let padding = if powerline { " " } else { "" };
+        let after = format!(
+            "{}{}{}{}",
+            config.sorround.right,
+            padding,
+            style::Reset,
+            trailing
         );
-        vec.push(String::from(" "));
-        Cli::push_style(
-            &mut vec,
-            &self.context.command_bar_config.prompt.style.style_name,
+
+        (
+            self.component_piece(config, before.join(""), after),
+            Cli::segment_background(&config.background),
+        )
+    }
+
+    fn build_cmd_bar(&self) -> Bar {
+        let command_bar_config = &self.context.command_bar_config;
+        let powerline = command_bar_config.powerline.as_ref();
+
+        let mut bar = match powerline {
+            Some(powerline) => Bar::powerline(&powerline.separator, Direction::Right),
+            None => Bar::new(""),
+        };
+
+        // Nothing is recorded, see the `incognito` builtin
+        if self.context.history_privacy.borrow().incognito {
+            bar.push(Piece::Text(format!(
+                "{}{}{} ",
+                Fg(termion_colors::LightBlack),
+                INCOGNITO_MARKER,
+                style::Reset
+            )));
+        }
+
+        // A template places the prompt symbol and the spaces itself
+        if command_bar_config.template {
+            for config in &command_bar_config.components {
+                let (piece, background) = self.component_segment(config, powerline.is_some(), "");
+                bar.push_on(piece, background);
+            }
+
+            return bar;
+        }
+
+        // Components are followed by a space, powerline segments are
+        // separated by the separator
+        let trailing = if powerline.is_some() { "" } else { " " };
+
+        for config in &command_bar_config.components {
+            // Build Component Content
+            let (piece, background) = self.component_segment(config, powerline.is_some(), trailing);
+            bar.push_on(piece, background);
+        }
+
+        // Add prompt
+        let prompt = &command_bar_config.prompt;
+        let mut vec = Cli::component_start(
+            &prompt.color.color_name,
+            &prompt.background,
+            &prompt.style,
+            powerline.is_some(),
         );
-        vec.push(format!("{}", style::Reset));
+        vec.push(prompt.sorround.left.clone());
+        vec.push(prompt.symbol.clone());
+        vec.push(prompt.sorround.right.clone());
+        if powerline.is_some() {
+            vec.push(String::from(" "));
+        }
+        vec.push(format!("{}{}", style::Reset, trailing));
 
         // info!("{:?}", vec);
-        vec.join("")
+        bar.push_on(
+            Piece::Text(vec.join("")),
+            Cli::segment_background(&prompt.background),
+        );
+
+        // The input starts after the last separator
+        if powerline.is_some() {
+            bar.push(Piece::Text(String::from(" ")));
+        }
+
+        bar
+    }
+
+    // Components without content are left out, e.g. svn outside of a repository
+    fn build_right_bar(&self) -> Bar {
+        let command_bar_config = &self.context.command_bar_config;
+        let powerline = command_bar_config.powerline.as_ref();
+
+        let mut bar = match powerline {
+            Some(powerline) => Bar::powerline(&powerline.right_separator, Direction::Left),
+            None if command_bar_config.right_template => Bar::new(""),
+            None => Bar::new(" "),
+        };
+
+        for config in &command_bar_config.right_components {
+            let (piece, background) = self.component_segment(config, powerline.is_some(), "");
+            bar.push_on(piece, background);
+        }
+
+        bar
     }
 }
 
@@ -183,7 +414,7 @@ mod tests_push_color {
     fn push_color_blue() {
         let mut vec: Vec<String> = Vec::new();
 
-        Cli::push_color(&mut vec, &ColorName::BLUE);
+        Cli::push_color(&mut vec, &ColorName::BLUE, ColorSupport::Ansi256);
 
         assert_eq!(vec.len(), 1);
         assert_eq!(vec.first().unwrap(), "\u{1b}[38;5;4m");
@@ -193,7 +424,7 @@ mod tests_push_color {
     fn push_color_yellow() {
         let mut vec: Vec<String> = Vec::new();
 
-        Cli::push_color(&mut vec, &ColorName::YELLOW);
+        Cli::push_color(&mut vec, &ColorName::YELLOW, ColorSupport::Ansi256);
 
         assert_eq!(vec.len(), 1);
         assert_eq!(vec.first().unwrap(), "\u{1b}[38;5;3m");
@@ -203,7 +434,7 @@ mod tests_push_color {
     fn push_color_red() {
         let mut vec: Vec<String> = Vec::new();
 
-        Cli::push_color(&mut vec, &ColorName::RED);
+        Cli::push_color(&mut vec, &ColorName::RED, ColorSupport::Ansi256);
 
         assert_eq!(vec.len(), 1);
         assert_eq!(vec.first().unwrap(), "\u{1b}[38;5;1m");
@@ -213,11 +444,21 @@ mod tests_push_color {
     fn push_color_green() {
         let mut vec: Vec<String> = Vec::new();
 
-        Cli::push_color(&mut vec, &ColorName::GREEN);
+        Cli::push_color(&mut vec, &ColorName::GREEN, ColorSupport::Ansi256);
 
         assert_eq!(vec.len(), 1);
         assert_eq!(vec.first().unwrap(), "\u{1b}[38;5;2m");
     }
+
+    #[test]
+    fn push_color_black() {
+        let mut vec: Vec<String> = Vec::new();
+
+        Cli::push_color(&mut vec, &ColorName::BLACK, ColorSupport::Ansi256);
+
+        assert_eq!(vec.len(), 1);
+        assert_eq!(vec.first().unwrap(), "\u{1b}[38;5;0m");
+    }
 }
 
 #[cfg(test)]
@@ -0,0 +1,114 @@
+// Right prompt
+//
+// rustyline only knows the left prompt, so the right one is drawn after the
+// input line: the cursor jumps to the right edge, draws it and jumps back to
+// the end of the input, where rustyline continues with the hint. rustyline
+// positions the cursor from its own layout afterwards, which the right
+// prompt does not change as long as it stays on the first row.
+//
+// It is left out as soon as the prompt, input and hint would reach it.
+
+use unicode_width::UnicodeWidthStr;
+
+// Free columns between the input and the right prompt
+static GAP: usize = 1;
+
+// Columns taken on the terminal, ignoring ANSI escape sequences
+pub fn display_width(text: &str) -> usize {
+    let mut visible = String::with_capacity(text.len());
+    let mut chars = text.chars();
+
+    while let Some(c) = chars.next() {
+        if c != '\x1b' {
+            visible.push(c);
+            continue;
+        }
+
+        match chars.next() {
+            // CSI sequences end with a letter, e.g. `\x1b[1;32m`
+            Some('[') => {
+                for c in chars.by_ref() {
+                    if c.is_ascii_alphabetic() || c == '~' {
+                        break;
+                    }
+                }
+            }
+            // Character set selection, e.g. `\x1b(B` of `tput sgr0`
+            Some('(') | Some(')') => {
+                chars.next();
+            }
+            _ => {}
+        }
+    }
+
+    UnicodeWidthStr::width(visible.as_str())
+}
+
+// Escape sequences drawing `right` at the right edge of a terminal with
+// `columns` columns. The cursor is `cursor` columns into the first row, at
+// the end of the input, the hint after it takes the row up to `used`.
+// `None` if it does not fit.
+pub fn place_right_prompt(
+    right: &str,
+    cursor: usize,
+    used: usize,
+    columns: usize,
+) -> Option<String> {
+    let width = display_width(right);
+
+    // The last column is kept free, writing it makes some terminals wrap
+    if width == 0 || used.max(cursor) + GAP + width >= columns {
+        return None;
+    }
+
+    let start = columns - 1 - width;
+    let back = if cursor > 0 {
+        format!("\r\x1b[{}C", cursor)
+    } else {
+        String::from("\r")
+    };
+
+    Some(format!("\r\x1b[{}C{}{}", start, right, back))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_display_width() {
+        assert_eq!(display_width("~/src"), 5);
+        assert_eq!(display_width("\x1b[1;32m~/src \x1b[38;5;4mλ\x1b[m "), 8);
+        assert_eq!(display_width("\x1b(B日本"), 4);
+        assert_eq!(display_width(""), 0);
+    }
+
+    #[test]
+    fn test_place_right_prompt() {
+        assert_eq!(
+            place_right_prompt("\x1b[33m12:00\x1b[m", 10, 10, 40),
+            Some(String::from("\r\x1b[34C\x1b[33m12:00\x1b[m\r\x1b[10C"))
+        );
+        assert_eq!(
+            place_right_prompt("12:00", 0, 0, 40),
+            Some(String::from("\r\x1b[34C12:00\r"))
+        );
+
+        // Input reaching the right prompt
+        assert_eq!(place_right_prompt("12:00", 34, 34, 40), None);
+        assert!(place_right_prompt("12:00", 33, 33, 40).is_some());
+        assert_eq!(place_right_prompt("", 0, 0, 40), None);
+    }
+
+    #[test]
+    fn test_place_right_prompt_with_hint() {
+        // The cursor goes back to the end of the input, not of the hint
+        assert_eq!(
+            place_right_prompt("12:00", 10, 16, 40),
+            Some(String::from("\r\x1b[34C12:00\r\x1b[10C"))
+        );
+
+        // The hint reaching the right prompt
+        assert_eq!(place_right_prompt("12:00", 10, 34, 40), None);
+    }
+}
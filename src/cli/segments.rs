@@ -0,0 +1,414 @@
+// Asynchronous command bar segments
+//
+// Components which can be slow, e.g. the git status of a huge repository or
+// files on a network file system, are rendered on a thread. `Cli::update`
+// waits for them up to their `timeout`, afterwards the bar shows the value
+// last rendered in the same directory, or a placeholder, and the prompt is
+// repainted as soon as the result arrives.
+//
+// The repaint is requested with SIGWINCH, on which rustyline redraws the
+// prompt and the input line. rustyline measures the left prompt once when
+// reading starts, so a late left segment is only repainted in place if the
+// prompt keeps its width (see `MyHelper::highlight_prompt`), otherwise it is
+// shown with the next prompt. The right prompt is always repainted.
+//
+// With powerline separators the pieces are drawn on their background and
+// the separators between them blend one background into the next. The
+// transitions are only known once empty pieces are left out, so they are
+// assembled on every render as well.
+
+use std::collections::{HashMap, HashSet};
+use std::env;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::mpsc;
+use std::sync::{Arc, Mutex};
+use std::thread;
+use std::time::Duration;
+
+use termion::style;
+
+use super::components::ValueFormat;
+
+// Renders a segment for a directory
+pub type Job = Box<dyn FnOnce(&Path) -> String + Send>;
+
+// Component and directory
+type Key = (String, PathBuf);
+type Values = Arc<Mutex<HashMap<Key, String>>>;
+
+// The value of a segment rendered on a thread, the placeholder until the
+// first result for the directory arrived
+pub struct AsyncValue {
+    key: Key,
+    values: Values,
+    placeholder: String,
+}
+
+impl AsyncValue {
+    pub fn get(&self) -> String {
+        self.values
+            .lock()
+            .unwrap()
+            .get(&self.key)
+            .cloned()
+            .unwrap_or_else(|| self.placeholder.clone())
+    }
+}
+
+pub enum Piece {
+    Text(String),
+    // Left out with its surroundings while the value is empty
+    Async {
+        before: String,
+        after: String,
+        format: ValueFormat,
+        value: AsyncValue,
+    },
+}
+
+impl Piece {
+    fn render(&self) -> String {
+        match self {
+            Piece::Text(text) => text.clone(),
+            Piece::Async {
+                before,
+                after,
+                format,
+                value,
+            } => {
+                let content = format.apply(&value.get());
+                if content.is_empty() {
+                    return content;
+                }
+                format!("{}{}{}", before, content, after)
+            }
+        }
+    }
+}
+
+// Background of a piece as escape sequences, `foreground` is the same color
+// which the separators take on the background of the next piece
+#[derive(Debug, Default, Clone)]
+pub struct Background {
+    pub background: String,
+    pub foreground: String,
+}
+
+impl Background {
+    fn is_empty(&self) -> bool {
+        self.background.is_empty()
+    }
+}
+
+// The way powerline separators point, right (U+E0B0) on the left prompt and
+// left (U+E0B2) on the right one
+#[derive(Debug, Clone, Copy)]
+pub enum Direction {
+    Right,
+    Left,
+}
+
+struct Powerline {
+    separator: String,
+    direction: Direction,
+}
+
+impl Powerline {
+    // Separator between two pieces, `None` for the edges of the bar
+    fn transition(&self, previous: Option<&Background>, next: Option<&Background>) -> String {
+        let none = Background::default();
+        let (previous, next) = (previous.unwrap_or(&none), next.unwrap_or(&none));
+
+        let (background, foreground) = match self.direction {
+            Direction::Right if !previous.is_empty() => (&next.background, &previous.foreground),
+            Direction::Left if !next.is_empty() => (&previous.background, &next.foreground),
+            _ => return String::new(),
+        };
+
+        format!(
+            "{}{}{}{}{}",
+            style::Reset,
+            background,
+            foreground,
+            self.separator,
+            style::Reset
+        )
+    }
+
+    fn join(&self, pieces: &[(String, &Background)]) -> String {
+        let mut line = String::new();
+        let mut previous = None;
+
+        for (piece, background) in pieces {
+            line.push_str(&self.transition(previous, Some(background)));
+            line.push_str(piece);
+            previous = Some(*background);
+        }
+
+        line.push_str(&self.transition(previous, None));
+        line
+    }
+}
+
+// A prompt assembled again on every repaint
+#[derive(Default)]
+pub struct Bar {
+    pieces: Vec<(Piece, Background)>,
+    // Between non-empty pieces
+    separator: String,
+    powerline: Option<Powerline>,
+}
+
+impl Bar {
+    pub fn new(separator: &str) -> Self {
+        Self {
+            pieces: Vec::new(),
+            separator: String::from(separator),
+            powerline: None,
+        }
+    }
+
+    pub fn powerline(separator: &str, direction: Direction) -> Self {
+        Self {
+            pieces: Vec::new(),
+            separator: String::new(),
+            powerline: Some(Powerline {
+                separator: String::from(separator),
+                direction,
+            }),
+        }
+    }
+
+    pub fn push(&mut self, piece: Piece) {
+        self.push_on(piece, Background::default());
+    }
+
+    pub fn push_on(&mut self, piece: Piece, background: Background) {
+        self.pieces.push((piece, background));
+    }
+
+    pub fn render(&self) -> String {
+        let pieces: Vec<(String, &Background)> = self
+            .pieces
+            .iter()
+            .map(|(piece, background)| (piece.render(), background))
+            .filter(|(piece, _)| !piece.is_empty())
+            .collect();
+
+        match &self.powerline {
+            Some(powerline) => powerline.join(&pieces),
+            None => pieces
+                .into_iter()
+                .map(|(piece, _)| piece)
+                .collect::<Vec<String>>()
+                .join(&self.separator),
+        }
+    }
+}
+
+// Redraws the prompt from another thread, only while a line is read
+#[derive(Clone)]
+struct Repaint {
+    reading: Arc<AtomicBool>,
+    // pthread_t of the thread reading the line, which has to receive the signal
+    thread: usize,
+}
+
+impl Repaint {
+    fn request(&self) {
+        if self.reading.load(Ordering::SeqCst) {
+            unsafe {
+                libc::pthread_kill(self.thread as libc::pthread_t, libc::SIGWINCH);
+            }
+        }
+    }
+}
+
+pub struct AsyncSegments {
+    values: Values,
+    // Segments still rendering, they are not started a second time
+    running: Arc<Mutex<HashSet<Key>>>,
+    repaint: Repaint,
+}
+
+impl AsyncSegments {
+    // Has to be created on the thread reading the lines
+    pub fn new() -> Self {
+        Self {
+            values: Arc::new(Mutex::new(HashMap::new())),
+            running: Arc::new(Mutex::new(HashSet::new())),
+            repaint: Repaint {
+                reading: Arc::new(AtomicBool::new(false)),
+                thread: unsafe { libc::pthread_self() } as usize,
+            },
+        }
+    }
+
+    // Results arriving while reading a line repaint the prompt
+    pub fn set_reading(&self, reading: bool) {
+        self.repaint.reading.store(reading, Ordering::SeqCst);
+    }
+
+    // Starts rendering the segment `name` in the working directory and
+    // waits for it up to `timeout`
+    pub fn render(&self, name: &str, job: Job, timeout: Duration, placeholder: &str) -> AsyncValue {
+        let dir = env::current_dir().unwrap_or_default();
+        let key = (String::from(name), dir.clone());
+
+        let value = AsyncValue {
+            key: key.clone(),
+            values: Arc::clone(&self.values),
+            placeholder: String::from(placeholder),
+        };
+
+        if !self.running.lock().unwrap().insert(key.clone()) {
+            return value;
+        }
+
+        let (sender, receiver) = mpsc::channel();
+        let values = Arc::clone(&self.values);
+        let running = Arc::clone(&self.running);
+        let repaint = self.repaint.clone();
+
+        thread::spawn(move || {
+            let rendered = job(&dir);
+
+            values.lock().unwrap().insert(key.clone(), rendered);
+            running.lock().unwrap().remove(&key);
+
+            // Nobody waits anymore, the prompt shows the cached value
+            if sender.send(()).is_err() {
+                repaint.request();
+            }
+        });
+
+        let _ = receiver.recv_timeout(timeout);
+
+        value
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn wait_for(value: &AsyncValue, expected: &str) -> bool {
+        for _ in 0..100 {
+            if value.get() == expected {
+                return true;
+            }
+            thread::sleep(Duration::from_millis(10));
+        }
+        false
+    }
+
+    #[test]
+    fn test_render_within_timeout() {
+        let segments = AsyncSegments::new();
+
+        let value = segments.render(
+            "fast",
+            Box::new(|_| String::from("master")),
+            Duration::from_secs(5),
+            "…",
+        );
+        assert_eq!(value.get(), "master");
+    }
+
+    #[test]
+    fn test_render_late() {
+        let segments = AsyncSegments::new();
+        let slow = || -> Job {
+            Box::new(|_| {
+                thread::sleep(Duration::from_millis(100));
+                String::from("master")
+            })
+        };
+
+        let value = segments.render("slow", slow(), Duration::from_millis(1), "…");
+        assert_eq!(value.get(), "…");
+
+        // Not started again while running
+        let second = segments.render(
+            "slow",
+            Box::new(|_| String::from("x")),
+            Duration::from_millis(1),
+            "…",
+        );
+        assert_eq!(second.get(), "…");
+
+        assert!(wait_for(&value, "master"));
+        assert_eq!(second.get(), "master");
+
+        // The last value is shown while rendering again
+        let value = segments.render("slow", slow(), Duration::from_millis(1), "…");
+        assert_eq!(value.get(), "master");
+    }
+
+    #[test]
+    fn test_bar_render() {
+        let segments = AsyncSegments::new();
+        let empty = segments.render(
+            "empty",
+            Box::new(|_| String::new()),
+            Duration::from_secs(5),
+            "…",
+        );
+        let branch = segments.render(
+            "branch",
+            Box::new(|_| String::from("master")),
+            Duration::from_secs(5),
+            "…",
+        );
+
+        let mut bar = Bar::new(" ");
+        bar.push(Piece::Text(String::from("~/src")));
+        bar.push(Piece::Async {
+            before: String::from("["),
+            after: String::from("]"),
+            format: ValueFormat::new("%s"),
+            value: empty,
+        });
+        bar.push(Piece::Async {
+            before: String::from("("),
+            after: String::from(")"),
+            format: ValueFormat::new("on %s"),
+            value: branch,
+        });
+
+        assert_eq!(bar.render(), "~/src (on master)");
+    }
+
+    #[test]
+    fn test_powerline_render() {
+        let blue = Background {
+            background: String::from("<bg-blue>"),
+            foreground: String::from("<blue>"),
+        };
+        let gray = Background {
+            background: String::from("<bg-gray>"),
+            foreground: String::from("<gray>"),
+        };
+
+        let mut bar = Bar::powerline(">", Direction::Right);
+        bar.push_on(Piece::Text(String::from("~/src")), blue.clone());
+        bar.push_on(Piece::Text(String::new()), Background::default());
+        bar.push_on(Piece::Text(String::from("master")), gray.clone());
+        bar.push(Piece::Text(String::from(" ")));
+
+        assert_eq!(
+            bar.render(),
+            "~/src\u{1b}[m<bg-gray><blue>>\u{1b}[mmaster\u{1b}[m<gray>>\u{1b}[m "
+        );
+
+        let mut bar = Bar::powerline("<", Direction::Left);
+        bar.push_on(Piece::Text(String::from("12:00")), gray);
+        bar.push_on(Piece::Text(String::from("exit 1")), blue);
+
+        assert_eq!(
+            bar.render(),
+            "\u{1b}[m<gray><\u{1b}[m12:00\u{1b}[m<bg-gray><blue><\u{1b}[mexit 1"
+        );
+    }
+}
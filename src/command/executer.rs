@@ -1,26 +1,36 @@
+use std::os::unix::process::ExitStatusExt;
 use std::process::Command as SysCommand;
-use std::process::Stdio;
+use std::process::{ExitStatus as ProcessStatus, Stdio};
 
 use log::{error, info};
 
 use super::error::CommandError;
+use super::suggest::suggest;
 
 use crate::builtins::executer;
 use crate::builtins::exit_status::ExitStatus;
 use crate::command::{Command, ExecStrategy, PipeType};
+use crate::config::alias::AliasKind;
+use crate::config::interpreter::{call_hook, COMMAND_NOT_FOUND_HOOK};
 use crate::config::manager::ContextManager;
 use rcalc::{RuntimeItem, Value};
 
+// Exit code of shells for commands that could not be found
+static COMMAND_NOT_FOUND: i32 = 127;
+
+// Exit code of builtins and commands failing with an error
+static COMMAND_FAILED: i32 = 1;
+
 //
 // Depending on using pipes or just the sequential delimiter
 // We have to capture the stdout out and pipe it into
 // the stdin of the next command.
 //
-// If it just a sequential, then throw exit code etc away...for now
-//
+// The exit status is the one of the last command, of a pipe the one of
+// its last process
 //
 pub fn exec_sequentially(commands: &mut Vec<Command>, ctx: &ContextManager) -> ExitStatus {
-    let mut current_status: ExitStatus = ExitStatus { code: -1 };
+    let mut current_status: ExitStatus = ExitStatus { code: 0 };
 
     while !commands.is_empty() {
         // Peek
@@ -28,15 +38,18 @@ pub fn exec_sequentially(commands: &mut Vec<Command>, ctx: &ContextManager) -> E
 
         info!("Execute command {:?}", command);
         match command.pipe_type {
-            PipeType::Undefined => match exec_command(commands.pop().unwrap(), ctx) {
+            PipeType::Undefined => match exec_command(commands.remove(0), ctx) {
                 Ok(exit_status) => current_status = exit_status,
                 Err(err) => {
                     error!("{}", err);
                     println!("{}", err.message);
+                    current_status = ExitStatus {
+                        code: COMMAND_FAILED,
+                    };
                 }
             },
             _ => {
-                execute_pipe(commands);
+                current_status = execute_pipe(commands, ctx);
             }
         }
     }
@@ -56,7 +69,7 @@ fn exec_arithmetic_expression(
                 match *v {
                     Value::Integer(n) => {
                         println!("{}", n);
-                        return Ok(ExitStatus { code: 1 });
+                        return Ok(ExitStatus { code: 0 });
                     }
                     _ => {
                         return Err(CommandError {
@@ -97,7 +110,7 @@ fn exec_command(command: Command, ctx: &ContextManager) -> Result<ExitStatus, Co
             match child {
                 Ok(mut c) => match c.wait() {
                     Ok(exit_status) => Ok(ExitStatus {
-                        code: exit_status.code().unwrap(),
+                        code: status_code(exit_status),
                     }),
                     Err(_) => Err(CommandError {
                         kind: String::from("process"),
@@ -110,73 +123,174 @@ fn exec_command(command: Command, ctx: &ContextManager) -> Result<ExitStatus, Co
                 }),
             }
         }
-        _ => Err(CommandError {
-            kind: String::from("exec_command"),
-            message: String::from("Could not determine execution strategy"),
-        }),
+        ExecStrategy::Undefined => command_not_found(command, ctx),
+    }
+}
+
+// Runs the `command_not_found_handler` of the python config if there is one,
+// otherwise reports the command with suggestions for similar ones
+fn command_not_found(command: Command, ctx: &ContextManager) -> Result<ExitStatus, CommandError> {
+    if let Some(hook) = ctx.hooks.borrow().get(COMMAND_NOT_FOUND_HOOK) {
+        match call_hook(hook, command.command_name.as_str(), &command.arguments) {
+            Ok(code) => {
+                return Ok(ExitStatus {
+                    code: code.unwrap_or(COMMAND_NOT_FOUND),
+                })
+            }
+            Err(err) => error!("{}", err),
+        }
+    }
+
+    report_not_found(command.command_name.as_str(), ctx);
+
+    Ok(ExitStatus {
+        code: COMMAND_NOT_FOUND,
+    })
+}
+
+fn report_not_found(command_name: &str, ctx: &ContextManager) {
+    eprintln!("civa: command not found: {}", command_name);
+
+    let suggestions = command_suggestions(command_name, ctx);
+    if !suggestions.is_empty() {
+        eprintln!("Did you mean: {}?", suggestions.join(", "));
+    }
+}
+
+fn command_suggestions(command_name: &str, ctx: &ContextManager) -> Vec<String> {
+    let builtins = ctx.builtins.borrow();
+    let alias_system = ctx.alias_system.borrow();
+
+    let candidates = ctx
+        .env_manager
+        .command_names()
+        .chain(builtins.names())
+        .chain(
+            alias_system
+                .aliases_of_kind(AliasKind::Command)
+                .into_iter()
+                .map(|(name, _)| name.as_str()),
+        );
+
+    suggest(command_name, candidates)
+}
+
+// Exit code of a process, 128 + n if it was killed by signal n
+fn status_code(status: ProcessStatus) -> i32 {
+    status
+        .code()
+        .or_else(|| status.signal().map(|signal| 128 + signal))
+        .unwrap_or(COMMAND_FAILED)
+}
+
+fn execute_pipe(commands: &mut Vec<Command>, ctx: &ContextManager) -> ExitStatus {
+    match pipe_consumer(commands, None) {
+        Ok(exit_status) => exit_status,
+        Err(command_name) => {
+            report_not_found(command_name.as_str(), ctx);
+            ExitStatus {
+                code: COMMAND_NOT_FOUND,
+            }
+        }
     }
 }
 
-fn execute_pipe(commands: &mut Vec<Command>) {
-    pipe_consumer(commands, None);
+// Whether `next` continues the pipe `cmd` is part of
+fn continues_pipe(cmd: &Command, next: Option<&Command>) -> bool {
+    cmd.pipe_type != PipeType::ReceivesInput
+        && next.map_or(false, |next| {
+            next.pipe_type == PipeType::OutAndInput || next.pipe_type == PipeType::ReceivesInput
+        })
+}
+
+// Removes the commands of the pipe after `cmd`, once one of them could not
+// be started
+fn skip_pipe(cmd: &Command, commands: &mut Vec<Command>) {
+    if !continues_pipe(cmd, commands.first()) {
+        return;
+    }
+
+    while let Some(next) = commands.first() {
+        let end = next.pipe_type == PipeType::ReceivesInput;
+        commands.remove(0);
+        if end {
+            break;
+        }
+    }
 }
 
+// Runs the pipe at the start of `commands` and returns the exit status of its
+// last process, the name of the command if one could not be started
+//
 // TODO: Fix Bug
 // BUG: When pipe ends in a 'cat' command, sometimes the output is not finished corretly
 // and rustyline cannot find the location to place the new cursor and command bar is not displayed
-fn pipe_consumer(commands: &mut Vec<Command>, stdout: Option<std::process::ChildStdout>) {
+fn pipe_consumer(
+    commands: &mut Vec<Command>,
+    stdout: Option<std::process::ChildStdout>,
+) -> Result<ExitStatus, String> {
     if commands.is_empty() {
-        return;
+        return Ok(ExitStatus { code: 0 });
     }
 
-    let cmd = commands.pop().unwrap();
+    let cmd = commands.remove(0);
+    let stdin = stdout.map_or_else(Stdio::inherit, Stdio::from);
 
     // If the end of pipe is reached construct a non pipe command which takes
     // only only previous stdout
-    if commands.is_empty() || commands.first().unwrap().pipe_type == PipeType::Undefined {
+    if !continues_pipe(&cmd, commands.first()) {
         info!("Called last");
-        let process = SysCommand::new(cmd.command_name)
-            .args(cmd.arguments)
-            .stdin(stdout.unwrap())
+        let process = SysCommand::new(&cmd.command_name)
+            .args(&cmd.arguments)
+            .stdin(stdin)
             .spawn();
 
-        match process {
+        return match process {
             Ok(mut c) => match c.wait() {
-                Ok(_) => {}
-                Err(_) => info!("Could not get exit code of process"),
+                Ok(exit_status) => Ok(ExitStatus {
+                    code: status_code(exit_status),
+                }),
+                Err(_) => {
+                    info!("Could not get exit code of process");
+                    Ok(ExitStatus {
+                        code: COMMAND_FAILED,
+                    })
+                }
             },
-            Err(_) => info!("Could not find command "),
-        }
-
-        return;
+            Err(_) => Err(cmd.command_name),
+        };
     }
 
-    match stdout {
-        // The is stdout of a previous command, pipe it in new command
-        Some(prev_stdout) => {
-            info!("Called middle");
-            let process = SysCommand::new(cmd.command_name)
-                .args(cmd.arguments)
-                .stdin(prev_stdout)
-                .stdout(Stdio::piped())
-                .spawn()
-                .expect("MEEEEH");
-            pipe_consumer(commands, process.stdout);
+    // The first command of the pipe or one in the middle, its stdout goes
+    // into the next one
+    info!("Called first or middle");
+    let process = SysCommand::new(&cmd.command_name)
+        .args(&cmd.arguments)
+        .stdin(stdin)
+        .stdout(Stdio::piped())
+        .spawn();
+
+    match process {
+        Ok(mut process) => {
+            let status = pipe_consumer(commands, process.stdout.take());
+            reap(process);
+            status
         }
-        // There is no previous stdout -> First called command of pipe
-        None => {
-            info!("Called first");
-            let process = SysCommand::new(cmd.command_name)
-                .args(cmd.arguments)
-                .stdout(Stdio::piped())
-                .spawn()
-                .expect("MEEEEH");
-
-            pipe_consumer(commands, process.stdout);
+        Err(_) => {
+            skip_pipe(&cmd, commands);
+            Err(cmd.command_name)
         }
     }
 }
 
+// Waits for a process in front of the last one of a pipe, so it does not stay
+// a zombie. Its exit status does not matter.
+fn reap(mut process: std::process::Child) {
+    if let Err(err) = process.wait() {
+        info!("Could not wait for process {}: {}", process.id(), err);
+    }
+}
+
 #[cfg(test)]
 mod tests {
 
@@ -262,7 +376,7 @@ mod tests {
 
         // let expected_result = helper_get_files_in_dir(".");
 
-        pipe_consumer(&mut cmds, None);
+        pipe_consumer(&mut cmds, None).unwrap();
     }
 
     #[test]
@@ -286,4 +400,86 @@ mod tests {
 
         // let out = execute_pipe(cmds);
     }
+
+    fn pipe_command(args: &[&str], pipe_type: PipeType) -> Command {
+        Command {
+            command_name: String::from(args[0]),
+            arguments: args[1..].iter().map(|arg| String::from(*arg)).collect(),
+            strategy: ExecStrategy::PathCommand,
+            pipe_type,
+        }
+    }
+
+    #[test]
+    fn pipe_status() {
+        let mut cmds = vec![
+            pipe_command(&["false"], PipeType::PassesOutput),
+            pipe_command(
+                &["sh", "-c", "cat > /dev/null; exit 3"],
+                PipeType::ReceivesInput,
+            ),
+            pipe_command(&["true"], PipeType::Undefined),
+        ];
+
+        assert_eq!(pipe_consumer(&mut cmds, None), Ok(ExitStatus { code: 3 }));
+        // The command after the pipe is left for `exec_sequentially`
+        assert_eq!(cmds.len(), 1);
+
+        let mut cmds = vec![
+            pipe_command(&["false"], PipeType::PassesOutput),
+            pipe_command(&["cat"], PipeType::OutAndInput),
+            pipe_command(&["true"], PipeType::ReceivesInput),
+        ];
+        assert_eq!(pipe_consumer(&mut cmds, None), Ok(ExitStatus { code: 0 }));
+    }
+
+    #[test]
+    fn two_pipes() {
+        // true | sh -c '...' ; cat | sh -c '...'
+        let mut cmds = vec![
+            pipe_command(&["true"], PipeType::PassesOutput),
+            pipe_command(&["sh", "-c", "exit 4"], PipeType::ReceivesInput),
+            pipe_command(&["echo", "second"], PipeType::PassesOutput),
+            pipe_command(&["sh", "-c", "read line; exit 5"], PipeType::ReceivesInput),
+        ];
+
+        assert_eq!(pipe_consumer(&mut cmds, None), Ok(ExitStatus { code: 4 }));
+        assert_eq!(cmds.len(), 2);
+        // The second pipe gets the output of `echo`, not of the first pipe
+        assert_eq!(pipe_consumer(&mut cmds, None), Ok(ExitStatus { code: 5 }));
+        assert!(cmds.is_empty());
+    }
+
+    #[test]
+    fn pipe_with_unknown_command() {
+        let mut cmds = vec![
+            pipe_command(&["civa-no-such-command"], PipeType::PassesOutput),
+            pipe_command(&["cat"], PipeType::OutAndInput),
+            pipe_command(&["true"], PipeType::ReceivesInput),
+            pipe_command(&["true"], PipeType::Undefined),
+        ];
+
+        assert_eq!(
+            pipe_consumer(&mut cmds, None),
+            Err(String::from("civa-no-such-command"))
+        );
+        // The rest of the pipe is not run on its own
+        assert_eq!(cmds.len(), 1);
+
+        let mut cmds = vec![
+            pipe_command(&["echo"], PipeType::PassesOutput),
+            pipe_command(&["civa-no-such-command"], PipeType::OutAndInput),
+            pipe_command(&["cat"], PipeType::ReceivesInput),
+        ];
+        assert!(pipe_consumer(&mut cmds, None).is_err());
+        assert!(cmds.is_empty());
+    }
+
+    #[test]
+    fn test_status_code() {
+        assert_eq!(status_code(ProcessStatus::from_raw(0)), 0);
+        assert_eq!(status_code(ProcessStatus::from_raw(2 << 8)), 2);
+        // Killed by SIGKILL
+        assert_eq!(status_code(ProcessStatus::from_raw(9)), 137);
+    }
 }
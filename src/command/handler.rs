@@ -30,18 +30,34 @@
 // For every command a Command object is contructed and passed to the
 // command executer
 //
-use crate::builtins::BUILTIN_NAMES;
+use crate::builtins::BuiltinRegistry;
 use crate::command::{Command, ExecStrategy, PipeType};
+use crate::config::alias::{AliasKind, AliasSystem};
 use crate::config::ContextManager;
 use crate::env::environment::EnvManager;
 
 use crate::command::PipeType::Undefined;
 use log::{debug, info};
 use regex::Regex;
+use std::fs;
+use std::os::unix::fs::PermissionsExt;
+use std::path::Path;
 
 type CommandTokenCollection = Vec<Vec<String>>;
 
 pub fn handle_commands(command_string: &str, ctx: &ContextManager) -> Vec<Command> {
+    let command_string = expand_global_aliases(command_string, &ctx.alias_system.borrow());
+
+    build_commands(command_string.as_str(), ctx, &mut Vec::new())
+}
+
+// `expanded_aliases` holds the aliases resolved on the way to this call, so an
+// alias using its own name (`alias ls='ls -G'`) falls through to the command
+fn build_commands(
+    command_string: &str,
+    ctx: &ContextManager,
+    expanded_aliases: &mut Vec<String>,
+) -> Vec<Command> {
     let mut commands: Vec<Command> = Vec::new();
 
     // Only splits sequential commands, not pipes
@@ -52,7 +68,8 @@ pub fn handle_commands(command_string: &str, ctx: &ContextManager) -> Vec<Comman
         if command.clone().contains(&String::from("|")) {
             info!("Pipe is: {:?}", command);
 
-            let mut pipe_commands = build_pipe_commands(command.clone(), &ctx.env_manager);
+            let mut pipe_commands =
+                build_pipe_commands(command.clone(), &ctx.env_manager, &ctx.builtins.borrow());
 
             commands.append(pipe_commands.as_mut());
 
@@ -62,25 +79,31 @@ pub fn handle_commands(command_string: &str, ctx: &ContextManager) -> Vec<Comman
             let mut command_name = command.remove(0);
 
             // Check for aliases
-            match ctx.alias_system.borrow().get_alias(command_name.clone()) {
+            match expand_alias(command_name.as_str(), ctx, expanded_aliases) {
                 Some(alias) => {
-                    let sub_command = handle_commands(alias, ctx);
-
-                    info!("New command: {:?}", sub_command);
-                    for (index, cmd) in sub_command.iter().enumerate() {
-                        if index == sub_command.len() {
-                            let mut new_cmd = cmd.clone();
-                            new_cmd.arguments.append(&mut command);
-                            info!("Making new commmand: {:?}", new_cmd);
-                            commands.push(new_cmd);
-                        } else {
-                            info!("Pushing old command: {:?}", cmd);
-                            commands.push(cmd.clone());
+                    expanded_aliases.push(command_name);
+                    let mut sub_commands = build_commands(alias.as_str(), ctx, expanded_aliases);
+                    expanded_aliases.pop();
+
+                    info!("New command: {:?}", sub_commands);
+
+                    // Arguments of the alias call belong to the last command of the alias
+                    if let Some(last) = sub_commands.last_mut() {
+                        last.arguments.append(&mut command);
+
+                        if last.pipe_type == Undefined && is_sudo_command(last) {
+                            make_command_sudo(last);
                         }
                     }
+
+                    commands.append(&mut sub_commands);
                 }
                 None => {
-                    let strategy = define_command_strategy(command_name.as_str(), &ctx.env_manager);
+                    let strategy = define_command_strategy(
+                        command_name.as_str(),
+                        &ctx.env_manager,
+                        &ctx.builtins.borrow(),
+                    );
 
                     info!("Defined strategy: {:?}", strategy);
 
@@ -119,6 +142,75 @@ pub fn handle_commands(command_string: &str, ctx: &ContextManager) -> Vec<Comman
     commands
 }
 
+// Resolves the command name to a regular alias or, if the name is no runnable
+// command itself, to a suffix alias for its extension (`notes.md` -> `vim notes.md`)
+fn expand_alias(
+    command_name: &str,
+    ctx: &ContextManager,
+    expanded_aliases: &[String],
+) -> Option<String> {
+    if expanded_aliases.iter().any(|alias| alias == command_name) {
+        return None;
+    }
+
+    let alias_system = ctx.alias_system.borrow();
+
+    if let Some(alias) = alias_system.get_alias(String::from(command_name)) {
+        return Some(alias.clone());
+    }
+
+    let extension = Path::new(command_name).extension()?.to_str()?;
+    let program = alias_system.get_alias_of_kind(AliasKind::Suffix, extension)?;
+
+    if is_runnable(command_name, ctx) {
+        return None;
+    }
+
+    Some(format!(
+        "{} {}",
+        EnvManager::expand_variables(program.as_str()),
+        command_name
+    ))
+}
+
+fn is_runnable(command_name: &str, ctx: &ContextManager) -> bool {
+    match define_command_strategy(command_name, &ctx.env_manager, &ctx.builtins.borrow()) {
+        ExecStrategy::Undefined => false,
+        ExecStrategy::SlashCommand | ExecStrategy::AbsolutePathCommand => {
+            match fs::metadata(command_name) {
+                Ok(metadata) => metadata.is_file() && metadata.permissions().mode() & 0o111 != 0,
+                Err(_) => false,
+            }
+        }
+        _ => true,
+    }
+}
+
+// Replaces every unquoted word matching a global alias, before the line
+// is split, so aliases like `G` -> `| grep` can introduce pipes
+fn expand_global_aliases(command_string: &str, alias_system: &AliasSystem) -> String {
+    lazy_static! {
+        static ref REGEX_WORD: Regex = Regex::new(r#""[^"]*"|'[^']*'|\S+"#).unwrap();
+    }
+
+    let mut expanded = String::with_capacity(command_string.len());
+    let mut last_end = 0;
+
+    for word in REGEX_WORD.find_iter(command_string) {
+        expanded.push_str(&command_string[last_end..word.start()]);
+
+        match alias_system.get_alias_of_kind(AliasKind::Global, word.as_str()) {
+            Some(alias) => expanded.push_str(alias.as_str()),
+            None => expanded.push_str(word.as_str()),
+        }
+
+        last_end = word.end();
+    }
+
+    expanded.push_str(&command_string[last_end..]);
+    expanded
+}
+
 fn make_command_sudo(cmd: &mut Command) {
     let tmp_cmd_name = cmd.command_name.clone();
     cmd.command_name = String::from("sudo");
@@ -133,7 +225,11 @@ fn is_sudo_command(cmd: &Command) -> bool {
     }
 }
 
-fn build_pipe_commands(command: Vec<String>, env_manager: &EnvManager) -> Vec<Command> {
+fn build_pipe_commands(
+    command: Vec<String>,
+    env_manager: &EnvManager,
+    builtins: &BuiltinRegistry,
+) -> Vec<Command> {
     let mut commands: Vec<Command> = Vec::new();
 
     let collected_commands = split_pipe(command);
@@ -158,7 +254,7 @@ fn build_pipe_commands(command: Vec<String>, env_manager: &EnvManager) -> Vec<Co
         // Take command name
         let mut command_name: String = command.remove(0);
 
-        let strategy = define_command_strategy(command_name.as_str(), env_manager);
+        let strategy = define_command_strategy(command_name.as_str(), env_manager, builtins);
 
         match strategy {
             ExecStrategy::Builtin => {
@@ -204,7 +300,11 @@ fn split_pipe(raw_pipe_commands: Vec<String>) -> CommandTokenCollection {
     commands
 }
 
-fn define_command_strategy(command_name: &str, env_manager: &EnvManager) -> ExecStrategy {
+fn define_command_strategy(
+    command_name: &str,
+    env_manager: &EnvManager,
+    builtins: &BuiltinRegistry,
+) -> ExecStrategy {
     if command_name.starts_with('$') {
         ExecStrategy::ArithmeticExpression
     } else
@@ -216,7 +316,7 @@ fn define_command_strategy(command_name: &str, env_manager: &EnvManager) -> Exec
         ExecStrategy::AbsolutePathCommand
     } else
     // Check if command is a builtin utility
-    if BUILTIN_NAMES.contains(&command_name) {
+    if builtins.contains(command_name) {
         ExecStrategy::Builtin
 
     // Check in PATH
@@ -316,6 +416,15 @@ fn is_delimiter(token: &str) -> bool {
     token.contains(';') || token.contains("&&") || token.contains("||")
 }
 
+// Checks if a word following `preceding` would be the name of a command,
+// i.e. it is the first word of the line, of a sequence or of a pipe
+pub fn is_command_position(preceding: &str) -> bool {
+    match preceding.split_whitespace().last() {
+        Some(token) => is_delimiter(token) || token.ends_with('|'),
+        None => true,
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -370,7 +479,10 @@ mod tests {
             },
         ];
 
-        assert_eq!(expected_result, build_pipe_commands(cmd, &env_mananger));
+        assert_eq!(
+            expected_result,
+            build_pipe_commands(cmd, &env_mananger, &BuiltinRegistry::with_defaults())
+        );
     }
 
     #[test]
@@ -406,7 +518,10 @@ mod tests {
             },
         ];
 
-        assert_eq!(expected_result, build_pipe_commands(cmd, &env_manager));
+        assert_eq!(
+            expected_result,
+            build_pipe_commands(cmd, &env_manager, &BuiltinRegistry::with_defaults())
+        );
     }
 
     #[test]
@@ -419,6 +534,35 @@ mod tests {
         assert_eq!(is_delimiter("||"), true);
     }
 
+    #[test]
+    fn test_expand_global_aliases() {
+        let mut alias_system = AliasSystem::new();
+        alias_system.update_alias_of_kind(
+            AliasKind::Global,
+            String::from("G"),
+            String::from("| grep"),
+        );
+
+        assert_eq!(
+            expand_global_aliases("ls -la G foo", &alias_system),
+            "ls -la | grep foo"
+        );
+        assert_eq!(
+            expand_global_aliases("echo \"G\" Gs", &alias_system),
+            "echo \"G\" Gs"
+        );
+    }
+
+    #[test]
+    fn test_is_command_position() {
+        assert!(is_command_position(""));
+        assert!(is_command_position("  "));
+        assert!(is_command_position("ls -la && "));
+        assert!(is_command_position("ls | "));
+        assert!(!is_command_position("ls "));
+        assert!(!is_command_position("git checkout "));
+    }
+
     #[test]
     fn test_has_next_delimiter_at_found() {
         let v = vec!["ls", "||", "some"];
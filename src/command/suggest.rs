@@ -0,0 +1,111 @@
+// "Did you mean" suggestions for unknown commands
+//
+// Candidates are ranked by their optimal string alignment distance to the
+// unknown command (Levenshtein plus transpositions, so `sl` finds `ls`).
+// Only candidates within a small distance relative to the length of the
+// command are suggested.
+//
+
+static MAX_SUGGESTIONS: usize = 3;
+
+pub fn suggest<'a, I>(command_name: &str, candidates: I) -> Vec<String>
+where
+    I: IntoIterator<Item = &'a str>,
+{
+    let max_distance = match command_name.chars().count() {
+        0 => return Vec::new(),
+        1..=3 => 1,
+        4..=7 => 2,
+        _ => 3,
+    };
+
+    let mut scored: Vec<(usize, &str)> = candidates
+        .into_iter()
+        .filter(|candidate| *candidate != command_name)
+        .map(|candidate| (edit_distance(command_name, candidate), candidate))
+        .filter(|(distance, _)| *distance <= max_distance)
+        .collect();
+
+    scored.sort();
+    scored.dedup();
+
+    scored
+        .into_iter()
+        .take(MAX_SUGGESTIONS)
+        .map(|(_, candidate)| String::from(candidate))
+        .collect()
+}
+
+// Optimal string alignment distance
+pub fn edit_distance(a: &str, b: &str) -> usize {
+    let a: Vec<char> = a.chars().collect();
+    let b: Vec<char> = b.chars().collect();
+
+    let mut distances = vec![vec![0; b.len() + 1]; a.len() + 1];
+
+    for (i, row) in distances.iter_mut().enumerate() {
+        row[0] = i;
+    }
+    for j in 0..=b.len() {
+        distances[0][j] = j;
+    }
+
+    for i in 1..=a.len() {
+        for j in 1..=b.len() {
+            let cost = if a[i - 1] == b[j - 1] { 0 } else { 1 };
+
+            let mut distance = (distances[i - 1][j] + 1)
+                .min(distances[i][j - 1] + 1)
+                .min(distances[i - 1][j - 1] + cost);
+
+            if i > 1 && j > 1 && a[i - 1] == b[j - 2] && a[i - 2] == b[j - 1] {
+                distance = distance.min(distances[i - 2][j - 2] + 1);
+            }
+
+            distances[i][j] = distance;
+        }
+    }
+
+    distances[a.len()][b.len()]
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_edit_distance() {
+        assert_eq!(edit_distance("", ""), 0);
+        assert_eq!(edit_distance("git", "git"), 0);
+        assert_eq!(edit_distance("gti", "git"), 1);
+        assert_eq!(edit_distance("kitten", "sitting"), 3);
+        assert_eq!(edit_distance("", "ls"), 2);
+    }
+
+    #[test]
+    fn test_suggest_orders_by_distance() {
+        let candidates = vec!["grep", "git", "gist", "cargo", "gitk"];
+
+        assert_eq!(suggest("gti", candidates), vec!["git"]);
+    }
+
+    #[test]
+    fn test_suggest_transposition() {
+        assert_eq!(suggest("sl", vec!["ls", "cd", "sleep"]), vec!["ls"]);
+    }
+
+    #[test]
+    fn test_suggest_limits_and_filters() {
+        let candidates = vec!["cargo", "cargo", "carg", "cargoo", "crago", "xxxxx"];
+
+        let suggestions = suggest("cargo", candidates);
+
+        assert_eq!(suggestions, vec!["carg", "cargoo", "crago"]);
+    }
+
+    #[test]
+    fn test_suggest_nothing_close() {
+        assert!(suggest("qwertz", vec!["ls", "cd"]).is_empty());
+        assert!(suggest("", vec!["ls"]).is_empty());
+    }
+}
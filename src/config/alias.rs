@@ -4,39 +4,216 @@ use std::collections::HashMap;
 use std::fs::File;
 use std::io::prelude::*;
 use std::io::BufReader;
+use std::sync::{Arc, RwLock};
+
+use crate::command::handler::is_command_position;
 
 use log::info;
 
+#[derive(Debug, PartialEq, Copy, Clone)]
+pub enum AliasKind {
+    // Expanded in command position, `ll` -> `ls -la`
+    Command,
+    // Expanded anywhere on the line (zsh `alias -g`), `G` -> `| grep`
+    Global,
+    // Keyed by file extension (zsh `alias -s`), `notes.md` -> `$EDITOR notes.md`
+    Suffix,
+}
+
+impl AliasKind {
+    pub fn from_flag(flag: &str) -> Option<Self> {
+        match flag {
+            "-g" => Some(AliasKind::Global),
+            "-s" => Some(AliasKind::Suffix),
+            _ => None,
+        }
+    }
+
+    // Flag as written in front of the definition, including the separating space
+    pub fn flag(self) -> &'static str {
+        match self {
+            AliasKind::Command => "",
+            AliasKind::Global => "-g ",
+            AliasKind::Suffix => "-s ",
+        }
+    }
+}
+
 pub struct AliasSystem {
     alias_map: HashMap<String, String>,
+    global_alias_map: HashMap<String, String>,
+    suffix_alias_map: HashMap<String, String>,
+    abbreviations: Abbreviations,
+}
+
+// Fish style abbreviations. Unlike aliases they are expanded inside the
+// editor buffer, so the history stores the real command.
+//
+// The map is shared with the key handler of the editor, which lives on
+// another thread as far as rustyline is concerned, hence the Arc<RwLock>.
+#[derive(Clone, Default)]
+pub struct Abbreviations {
+    map: Arc<RwLock<HashMap<String, String>>>,
+}
+
+impl Abbreviations {
+    pub fn get(&self, name: &str) -> Option<String> {
+        self.map.read().unwrap().get(name).cloned()
+    }
+
+    pub fn insert(&self, name: String, expansion: String) -> bool {
+        self.map.write().unwrap().insert(name, expansion).is_some()
+    }
+
+    pub fn all(&self) -> Vec<(String, String)> {
+        let mut abbreviations: Vec<(String, String)> = self
+            .map
+            .read()
+            .unwrap()
+            .iter()
+            .map(|(k, v)| (k.clone(), v.clone()))
+            .collect();
+        abbreviations.sort();
+        abbreviations
+    }
+
+    // Expands every abbreviation in command position, used when a line is
+    // accepted before the abbreviation was expanded by typing a space
+    pub fn expand_line(&self, line: &str) -> String {
+        lazy_static! {
+            static ref WORD_REGEX: Regex = Regex::new(r"\S+").unwrap();
+        }
+
+        let mut expanded = String::with_capacity(line.len());
+        let mut last_end = 0;
+
+        for word in WORD_REGEX.find_iter(line) {
+            expanded.push_str(&line[last_end..word.start()]);
+
+            match self.get(word.as_str()) {
+                Some(expansion) if is_command_position(&line[..word.start()]) => {
+                    expanded.push_str(expansion.as_str())
+                }
+                _ => expanded.push_str(word.as_str()),
+            }
+
+            last_end = word.end();
+        }
+
+        expanded.push_str(&line[last_end..]);
+        expanded
+    }
 }
 
 impl AliasSystem {
+    pub fn new() -> Self {
+        Self {
+            alias_map: HashMap::new(),
+            global_alias_map: HashMap::new(),
+            suffix_alias_map: HashMap::new(),
+            abbreviations: Abbreviations::default(),
+        }
+    }
+
     pub fn get_alias(&self, token: String) -> Option<&String> {
         self.alias_map.get(&token)
     }
 
-    #[allow(dead_code)]
-    pub fn update_alias(&mut self, token: String, alias: String) -> bool {
-        self.alias_map.insert(token, alias).is_some()
+    pub fn get_alias_of_kind(&self, kind: AliasKind, token: &str) -> Option<&String> {
+        self.map(kind).get(token)
+    }
+
+    pub fn update_alias_of_kind(&mut self, kind: AliasKind, token: String, alias: String) -> bool {
+        self.map_mut(kind).insert(token, alias).is_some()
+    }
+
+    fn map(&self, kind: AliasKind) -> &HashMap<String, String> {
+        match kind {
+            AliasKind::Command => &self.alias_map,
+            AliasKind::Global => &self.global_alias_map,
+            AliasKind::Suffix => &self.suffix_alias_map,
+        }
+    }
+
+    fn map_mut(&mut self, kind: AliasKind) -> &mut HashMap<String, String> {
+        match kind {
+            AliasKind::Command => &mut self.alias_map,
+            AliasKind::Global => &mut self.global_alias_map,
+            AliasKind::Suffix => &mut self.suffix_alias_map,
+        }
+    }
+
+    pub fn abbreviations(&self) -> Abbreviations {
+        self.abbreviations.clone()
+    }
+
+    // All aliases of a kind sorted by name, used by `alias` without arguments
+    pub fn aliases_of_kind(&self, kind: AliasKind) -> Vec<(&String, &String)> {
+        let mut aliases: Vec<(&String, &String)> = self.map(kind).iter().collect();
+        aliases.sort();
+        aliases
+    }
+}
+
+// Parses a definition with an optional kind flag, `-g G='| grep'`
+pub fn parse_alias_with_kind(definition: &str) -> Option<(AliasKind, String, String)> {
+    let definition = definition.trim();
+
+    let flag = definition
+        .find(' ')
+        .and_then(|i| AliasKind::from_flag(&definition[..i]).map(|kind| (kind, i)));
+
+    let (kind, definition) = match flag {
+        Some((kind, i)) => (kind, &definition[i..]),
+        None => (AliasKind::Command, definition),
+    };
+
+    parse_alias_definition(definition).map(|(alias, command)| (kind, alias, command))
+}
+
+// Parses the bash style definition `ll='ls -la'` (without the leading `alias`)
+// into its name and command. Quotes are optional for single word commands.
+pub fn parse_alias_definition(definition: &str) -> Option<(String, String)> {
+    lazy_static! {
+        static ref DEFINITION_REGEX: Regex = Regex::new(
+            r#"^(?P<alias>[a-zA-Z.]+) *= *(?:"(?P<double>.*)"|'(?P<single>.*)'|(?P<bare>[^"'\s]+))$"#
+        )
+        .unwrap();
     }
+
+    let caps = DEFINITION_REGEX.captures(definition.trim())?;
+
+    let command = caps
+        .name("double")
+        .or_else(|| caps.name("single"))
+        .or_else(|| caps.name("bare"))?;
+
+    Some((String::from(&caps["alias"]), String::from(command.as_str())))
 }
 
 impl AliasSystem {
     pub fn from_file(path: &str) -> Result<Self, ConfigError> {
-        lazy_static! {
-            static ref ALIAS_REGEX: Regex =
-                Regex::new(r#"^alias (?P<alias>[a-zA-Z.]+) *= *["'](?P<command>.*)["']"#).unwrap();
-        }
-
         let mut lines: Vec<String> = file_to_vec(path);
 
-        let mut alias_map: HashMap<String, String> = HashMap::new();
+        let mut alias_system = AliasSystem::new();
+
         for (index, line) in lines.iter_mut().enumerate() {
-            if ALIAS_REGEX.is_match(line.trim()) {
-                let caps = ALIAS_REGEX.captures(line).unwrap();
-                info!("Alias: {}, Command: {}", &caps["alias"], &caps["command"]);
-                alias_map.insert(String::from(&caps["alias"]), String::from(&caps["command"]));
+            let definition = line
+                .trim()
+                .strip_prefix("alias ")
+                .map(parse_alias_with_kind);
+
+            let abbreviation = line
+                .trim()
+                .strip_prefix("abbr ")
+                .map(parse_alias_definition);
+
+            if let Some(Some((kind, alias, command))) = definition {
+                info!("Alias ({:?}): {}, Command: {}", kind, alias, command);
+                alias_system.update_alias_of_kind(kind, alias, command);
+            } else if let Some(Some((abbreviation, expansion))) = abbreviation {
+                info!("Abbreviation: {}, Expansion: {}", abbreviation, expansion);
+                alias_system.abbreviations.insert(abbreviation, expansion);
             } else
             // Comments
             if line.trim().starts_with('#') || line.trim() == "" {
@@ -48,7 +225,7 @@ impl AliasSystem {
             }
         }
 
-        Ok(AliasSystem { alias_map })
+        Ok(alias_system)
     }
 }
 
@@ -70,4 +247,75 @@ mod tests {
     // fn read_aliases() {
     //     AliasSystem::from_file("/Users/patrickhaller/.dotfiles/bash_aliases");
     // }
+
+    #[test]
+    fn test_parse_alias_definition_single_quotes() {
+        let result = parse_alias_definition("ll='ls -la'");
+
+        assert_eq!(result, Some((String::from("ll"), String::from("ls -la"))));
+    }
+
+    #[test]
+    fn test_parse_alias_definition_double_quotes_and_spaces() {
+        let result = parse_alias_definition("gs = \"git status\"");
+
+        assert_eq!(
+            result,
+            Some((String::from("gs"), String::from("git status")))
+        );
+    }
+
+    #[test]
+    fn test_parse_alias_definition_bare_word() {
+        let result = parse_alias_definition("g=git");
+
+        assert_eq!(result, Some((String::from("g"), String::from("git"))));
+    }
+
+    #[test]
+    fn test_expand_line_command_position_only() {
+        let abbreviations = Abbreviations::default();
+        abbreviations.insert(String::from("gco"), String::from("git checkout"));
+
+        assert_eq!(
+            abbreviations.expand_line("gco master"),
+            "git checkout master"
+        );
+        assert_eq!(abbreviations.expand_line("echo gco"), "echo gco");
+        assert_eq!(
+            abbreviations.expand_line("ls && gco  -b"),
+            "ls && git checkout  -b"
+        );
+    }
+
+    #[test]
+    fn test_parse_alias_with_kind() {
+        assert_eq!(
+            parse_alias_with_kind("-g G='| grep'"),
+            Some((AliasKind::Global, String::from("G"), String::from("| grep")))
+        );
+        assert_eq!(
+            parse_alias_with_kind("-s md='$EDITOR'"),
+            Some((
+                AliasKind::Suffix,
+                String::from("md"),
+                String::from("$EDITOR")
+            ))
+        );
+        assert_eq!(
+            parse_alias_with_kind("ll='ls -la'"),
+            Some((
+                AliasKind::Command,
+                String::from("ll"),
+                String::from("ls -la")
+            ))
+        );
+        assert_eq!(parse_alias_with_kind("-x ll='ls -la'"), None);
+    }
+
+    #[test]
+    fn test_parse_alias_definition_invalid() {
+        assert_eq!(parse_alias_definition("ll"), None);
+        assert_eq!(parse_alias_definition("ll=ls -la"), None);
+    }
 }
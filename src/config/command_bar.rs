@@ -8,30 +8,105 @@
 //
 //
 // CWD = Current working directory, fold if to long or referencing user directories
+//       (`truncation: fish | last | repository`, `max_width`)
 // SVN = Quite common now, shows current branch and current changes
 // PROMPT_SYMBOL = Usually something like ">" or "$"
 // INPUT_FIELD = Where the use input text is displayed
 // OPTIONALS = Zsh shows the current times or amount of inputed commands
 //
+// Besides those there are components for the exit status and duration of
+// the last command, the time, hostname, jobs, shell level, python virtualenv
+// and rust toolchain. Every component has a `format` where `%s` is replaced
+// by its content, e.g. `format: "took %s"`.
+//
+// The optionals are configured with `right_component_order` and aligned to
+// the right edge of the terminal. They are hidden while the input reaches them.
+//
+// Instead of `component_order` the bar can be written as a template, e.g.
+// `template: "{cwd:yellow,bold} {git:[%s]} {status?✘ } {symbol} "`, see
+// `template`. It is compiled into the same components, text between the
+// placeholders becomes `TEXT` components.
+//
+// Components can have a `background` and several styles. With a `powerline`
+// section the components are drawn as powerline segments, the separators
+// blend the background of one component into the next.
+//
 //
 // Civa provides customization
 //
 //
 
 use std::fs;
+use std::time::Duration;
 
-use log::info;
+use chrono::format::{Item, StrftimeItems};
+use git::GitSymbols;
+use log::{info, warn};
 use yaml_rust::{Yaml, YamlLoader};
 
 use super::error::ConfigError;
-use super::{Color, Style};
+use super::highlight::HighlightConfig;
+use super::template::{parse_template, Padding, TemplateError, TemplateItem, TemplateOption};
+use super::{Color, ColorName, Style};
+
+static DEFAULT_FORMAT: &str = "%s";
+static DEFAULT_TIME_FORMAT: &str = "%H:%M:%S";
+// Commands running at least this long show their duration
+static DEFAULT_DURATION_THRESHOLD: f64 = 2.0;
+// How long the prompt waits for components rendered in the background
+static DEFAULT_TIMEOUT_MS: u64 = 100;
+static DEFAULT_PLACEHOLDER: &str = "…";
+// Directories kept whole by the fish style, shown by `last`
+static DEFAULT_FISH_SEGMENTS: usize = 1;
+static DEFAULT_LAST_SEGMENTS: usize = 3;
+// Need a powerline patched font
+static DEFAULT_POWERLINE_SEPARATOR: &str = "\u{e0b0}";
+static DEFAULT_POWERLINE_RIGHT_SEPARATOR: &str = "\u{e0b2}";
+
+// How the working directory is shortened, `$HOME` is always shown as `~`
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum CwdTruncation {
+    Full,
+    // `~/s/p/civa`, every directory but the last `segments` abbreviated
+    Fish { segments: usize },
+    // `…/p/civa/src`, only the last `segments` directories
+    Last { segments: usize },
+    // `civa/src/cli`, relative to the root of the repository
+    Repository,
+}
 
 #[derive(Debug)]
 pub enum CommandBarComponents {
-    CWD,
-    SVN,
+    // Directories in front are left out while wider than `max_width`
+    CWD {
+        truncation: CwdTruncation,
+        max_width: Option<usize>,
+    },
+    SVN {
+        symbols: GitSymbols,
+    },
     PROMPT,
     USER,
+    // Exit code of the last command, in `failure_color` if it failed
+    STATUS {
+        failure_color: Color,
+    },
+    // Duration of the last command if it took at least `threshold`
+    DURATION {
+        threshold: Duration,
+    },
+    TIME {
+        format: String,
+    },
+    HOSTNAME,
+    JOBS,
+    SHLVL,
+    VENV,
+    TOOLCHAIN,
+    // Text between the placeholders of a template
+    TEXT {
+        text: String,
+    },
     UNDEFINED,
 }
 
@@ -60,34 +135,112 @@ impl Sorround {
 #[derive(Debug)]
 pub struct Component {
     pub color: Color,
+    pub background: Option<Color>,
     pub style: Style,
     pub sorround: Sorround,
     pub component_type: CommandBarComponents,
+    // `%s` is replaced by the content
+    pub format: String,
+    // Components which may be slow (svn, jobs, toolchain) are rendered in the
+    // background, the placeholder is shown if they take longer than this
+    pub timeout: Duration,
+    pub placeholder: String,
+    // Set by templates: shown instead of the format if the content is
+    // neither empty nor `0`, truncation and padding of the content
+    pub condition: Option<String>,
+    pub max_width: Option<usize>,
+    pub padding: Option<Padding>,
 }
 
 impl Component {
-    fn from_string(component_name: &str, color: Color, style: Style, sorround: Sorround) -> Self {
+    fn from_string(
+        component_name: &str,
+        component_config: &Yaml,
+        color: Color,
+        background: Option<Color>,
+        style: Style,
+        sorround: Sorround,
+    ) -> Self {
         let comp = match component_name.to_lowercase().as_str() {
-            "cwd" => CommandBarComponents::CWD,
-            "svn" => CommandBarComponents::SVN,
+            "cwd" => CommandBarComponents::CWD {
+                truncation: cwd_truncation(component_config),
+                max_width: component_config["max_width"]
+                    .as_i64()
+                    .map(|width| width.max(0) as usize),
+            },
+            "svn" | "git" | "vcs" => CommandBarComponents::SVN {
+                symbols: git_symbols(&component_config["symbols"]),
+            },
             "prompt" => CommandBarComponents::PROMPT,
             "user" => CommandBarComponents::USER,
+            "status" => CommandBarComponents::STATUS {
+                failure_color: Color::from_yaml(&component_config["failure_color"]).unwrap_or(
+                    Color {
+                        color_name: ColorName::RED,
+                    },
+                ),
+            },
+            "duration" => {
+                let threshold = &component_config["threshold"];
+                let seconds = threshold
+                    .as_f64()
+                    .or_else(|| threshold.as_i64().map(|seconds| seconds as f64))
+                    .unwrap_or(DEFAULT_DURATION_THRESHOLD);
+
+                CommandBarComponents::DURATION {
+                    threshold: Duration::from_secs_f64(seconds.max(0.0)),
+                }
+            }
+            "time" => CommandBarComponents::TIME {
+                format: time_format(&component_config["time_format"]),
+            },
+            "hostname" => CommandBarComponents::HOSTNAME,
+            "jobs" => CommandBarComponents::JOBS,
+            "shlvl" => CommandBarComponents::SHLVL,
+            "venv" => CommandBarComponents::VENV,
+            "toolchain" => CommandBarComponents::TOOLCHAIN,
             _ => CommandBarComponents::UNDEFINED,
         };
 
         Self {
             component_type: comp,
             color,
+            background,
             style,
             sorround,
+            format: String::from(
+                component_config["format"]
+                    .as_str()
+                    .unwrap_or(DEFAULT_FORMAT),
+            ),
+            timeout: Duration::from_millis(
+                component_config["timeout"]
+                    .as_i64()
+                    .map_or(DEFAULT_TIMEOUT_MS, |timeout| timeout.max(0) as u64),
+            ),
+            placeholder: String::from(
+                component_config["placeholder"]
+                    .as_str()
+                    .unwrap_or(DEFAULT_PLACEHOLDER),
+            ),
+            condition: None,
+            max_width: None,
+            padding: None,
         }
     }
     fn default(component: CommandBarComponents) -> Self {
         Self {
             color: Color::default(),
+            background: None,
             style: Style::default(),
             component_type: component,
             sorround: Sorround::default(),
+            format: String::from(DEFAULT_FORMAT),
+            timeout: Duration::from_millis(DEFAULT_TIMEOUT_MS),
+            placeholder: String::from(DEFAULT_PLACEHOLDER),
+            condition: None,
+            max_width: None,
+            padding: None,
         }
     }
 }
@@ -97,6 +250,7 @@ pub struct Prompt {
     pub symbol: String,
     pub style: Style,
     pub color: Color,
+    pub background: Option<Color>,
     pub sorround: Sorround,
 }
 
@@ -106,26 +260,90 @@ impl Prompt {
             symbol: String::from(">"),
             style: Style::default(),
             color: Color::default(),
+            background: None,
             sorround: Sorround::default(),
         }
     }
+
+    fn from_yaml(component_config: &Yaml) -> Self {
+        let (color, background, style, sorround) = component_appearance(component_config);
+        let symbol = component_config["symbol"].as_str().unwrap_or(">");
+
+        info!("Promp symbol: {}", symbol);
+
+        Self {
+            symbol: String::from(symbol),
+            style,
+            color,
+            background,
+            sorround,
+        }
+    }
+}
+
+#[derive(Debug)]
+pub struct Powerline {
+    // Between the components of the left prompt
+    pub separator: String,
+    // Between the components of the right prompt
+    pub right_separator: String,
+}
+
+impl Powerline {
+    fn from_yaml(config: &Yaml) -> Option<Self> {
+        if config.is_badvalue() || config.is_null() {
+            return None;
+        }
+
+        Some(Self {
+            separator: String::from(
+                config["separator"]
+                    .as_str()
+                    .unwrap_or(DEFAULT_POWERLINE_SEPARATOR),
+            ),
+            right_separator: String::from(
+                config["right_separator"]
+                    .as_str()
+                    .unwrap_or(DEFAULT_POWERLINE_RIGHT_SEPARATOR),
+            ),
+        })
+    }
 }
 
 #[derive(Debug)]
 pub struct CommandBarConfig {
     pub components: Vec<Component>,
+    // Right prompt
+    pub right_components: Vec<Component>,
     pub prompt: Prompt,
+    // Syntax highlighting of the input line
+    pub highlight: HighlightConfig,
+    pub powerline: Option<Powerline>,
+    // The components come from `template` and `right_template`, they place
+    // the prompt symbol and the spaces themselves
+    pub template: bool,
+    pub right_template: bool,
 }
 
 impl<'a> CommandBarConfig {
     pub fn default() -> Self {
         Self {
             components: vec![
-                Component::default(CommandBarComponents::CWD),
-                Component::default(CommandBarComponents::SVN),
+                Component::default(CommandBarComponents::CWD {
+                    truncation: CwdTruncation::Full,
+                    max_width: None,
+                }),
+                Component::default(CommandBarComponents::SVN {
+                    symbols: GitSymbols::default(),
+                }),
                 Component::default(CommandBarComponents::PROMPT),
             ],
+            right_components: Vec::new(),
             prompt: Prompt::default(),
+            highlight: HighlightConfig::default(),
+            powerline: None,
+            template: false,
+            right_template: false,
         }
     }
 }
@@ -148,76 +366,240 @@ pub fn command_bar_config_reader(config_file: &str) -> Result<CommandBarConfig,
     Ok(config_builder(config))
 }
 
-fn config_builder(config: Vec<Yaml>) -> CommandBarConfig {
-    let config = &config[0];
-    let component_order: Vec<&str> = config["component_order"]
-        .as_vec()
-        .unwrap()
-        .iter()
-        .map(|c| c.as_str().unwrap())
-        .collect();
+// Symbols of the git status, every one can be replaced in `symbols`
+fn git_symbols(config: &Yaml) -> GitSymbols {
+    let mut symbols = GitSymbols::default();
+
+    let mut fields = [
+        ("ahead", &mut symbols.ahead),
+        ("behind", &mut symbols.behind),
+        ("staged", &mut symbols.staged),
+        ("modified", &mut symbols.modified),
+        ("untracked", &mut symbols.untracked),
+        ("conflicted", &mut symbols.conflicted),
+        ("stashed", &mut symbols.stashed),
+        ("detached", &mut symbols.detached),
+        ("rebase", &mut symbols.rebase),
+        ("merge", &mut symbols.merge),
+        ("bisect", &mut symbols.bisect),
+        ("cherry_pick", &mut symbols.cherry_pick),
+        ("revert", &mut symbols.revert),
+    ];
+
+    for (key, field) in fields.iter_mut() {
+        if let Some(symbol) = config[*key].as_str() {
+            **field = String::from(symbol);
+        }
+    }
 
-    let mut components: Vec<Component> = Vec::new();
+    symbols
+}
 
-    let mut maybe_prompt: Option<Prompt> = None;
+// chrono panics while rendering an invalid format, e.g. `%Q`, so it is
+// checked once here
+fn time_format(config: &Yaml) -> String {
+    match config.as_str() {
+        Some(format) if !StrftimeItems::new(format).any(|item| item == Item::Error) => {
+            String::from(format)
+        }
+        Some(format) => {
+            warn!(
+                "Invalid time_format {:?}, using {:?}",
+                format, DEFAULT_TIME_FORMAT
+            );
+            String::from(DEFAULT_TIME_FORMAT)
+        }
+        None => String::from(DEFAULT_TIME_FORMAT),
+    }
+}
 
-    for component_name in component_order {
-        info!("Component: {}", component_name);
-        let component_config = &config[component_name];
-        info!("With config: {:?}", component_config);
+fn cwd_truncation(config: &Yaml) -> CwdTruncation {
+    let segments = |default: usize| {
+        config["segments"]
+            .as_i64()
+            .map_or(default, |segments| segments.max(0) as usize)
+    };
 
-        let color = match component_config["color"].as_str() {
-            Some(color_string) => Color::from_string(color_string),
-            None => Color::default(),
+    match config["truncation"].as_str().unwrap_or_default() {
+        "fish" => CwdTruncation::Fish {
+            segments: segments(DEFAULT_FISH_SEGMENTS),
+        },
+        "last" => CwdTruncation::Last {
+            segments: segments(DEFAULT_LAST_SEGMENTS),
+        },
+        "repository" | "repo" => CwdTruncation::Repository,
+        _ => CwdTruncation::Full,
+    }
+}
+
+// Color, background, style and sorround of a component
+fn component_appearance(component_config: &Yaml) -> (Color, Option<Color>, Style, Sorround) {
+    let color = Color::from_yaml(&component_config["color"]).unwrap_or_else(Color::default);
+    let background = Color::from_yaml(&component_config["background"]);
+    let style = Style::from_yaml(&component_config["style"]).unwrap_or_else(Style::default);
+
+    let sorround_left = component_config["sorround"]["left"]
+        .as_str()
+        .unwrap_or_default();
+    let sorround_right = component_config["sorround"]["right"]
+        .as_str()
+        .unwrap_or_default();
+
+    (
+        color,
+        background,
+        style,
+        Sorround::new(sorround_left, sorround_right),
+    )
+}
+
+fn component_names<'a>(config: &'a Yaml, key: &str) -> Vec<&'a str> {
+    match config[key].as_vec() {
+        Some(names) => names.iter().filter_map(|c| c.as_str()).collect(),
+        None => Vec::new(),
+    }
+}
+
+// The components of a template, the appearance configured for a component
+// in its own section is taken unless the placeholder overrides it
+fn components_from_template(
+    template: &str,
+    config: &Yaml,
+) -> Result<Vec<Component>, TemplateError> {
+    let mut components = Vec::new();
+
+    for item in parse_template(template)? {
+        let placeholder = match item {
+            TemplateItem::Text(text) => {
+                components.push(Component::default(CommandBarComponents::TEXT { text }));
+                continue;
+            }
+            TemplateItem::Placeholder(placeholder) => placeholder,
         };
 
-        let style = match component_config["style"].as_str() {
-            Some(style_string) => Style::from_string(style_string),
-            None => Style::default(),
+        let name = match placeholder.name.as_str() {
+            "symbol" => "prompt",
+            name => name,
         };
+        let component_config = &config[name];
+        let (color, background, style, sorround) = component_appearance(component_config);
+        let mut component =
+            Component::from_string(name, component_config, color, background, style, sorround);
+
+        if let CommandBarComponents::UNDEFINED = component.component_type {
+            return Err(TemplateError::new(
+                template,
+                placeholder.position,
+                format!("unknown component `{}`", placeholder.name),
+            ));
+        }
+
+        let mut style_names = Vec::new();
+        for option in placeholder.options {
+            match option {
+                TemplateOption::Color(color) => component.color = color,
+                TemplateOption::Background(color) => component.background = Some(color),
+                TemplateOption::Style(style_name) => style_names.push(style_name),
+                TemplateOption::Format(format) => component.format = format,
+                TemplateOption::MaxWidth(width) => component.max_width = Some(width),
+                TemplateOption::Padding(padding) => component.padding = Some(padding),
+            }
+        }
+        if !style_names.is_empty() {
+            component.style = Style { style_names };
+        }
+        component.condition = placeholder.condition;
 
-        let sorround_left = component_config["sorround"]["left"]
-            .as_str()
-            .unwrap_or_default();
-        let sorround_right = component_config["sorround"]["right"]
-            .as_str()
-            .unwrap_or_default();
+        components.push(component);
+    }
 
-        let sorround = Sorround::new(sorround_left, sorround_right);
+    Ok(components)
+}
 
-        if component_name == "prompt" {
-            let symbol_string = component_config["symbol"].as_str();
+// Components of the template under `key`, `None` without a template or if it
+// does not compile. The error is printed at startup, where the user sees it
+// before the fallback to `component_order`.
+fn template_components(config: &Yaml, key: &str) -> Option<Vec<Component>> {
+    let template = config[key].as_str()?;
+
+    match components_from_template(template, config) {
+        Ok(components) => Some(components),
+        Err(err) => {
+            eprintln!("civa: ignoring `{}` of bar.yaml\n{}", key, err);
+            None
+        }
+    }
+}
+
+fn config_builder(config: Vec<Yaml>) -> CommandBarConfig {
+    let config = &config[0];
+    let component_order = component_names(config, "component_order");
+
+    let mut components: Vec<Component> = Vec::new();
 
-            info!("Promp symbol: {}", symbol_string.unwrap());
+    let mut maybe_prompt: Option<Prompt> = None;
 
-            let symbol = match symbol_string {
-                Some(s) => String::from(s),
-                None => String::from(">"),
-            };
+    for component_name in component_order {
+        info!("Component: {}", component_name);
+        let component_config = &config[component_name];
+        info!("With config: {:?}", component_config);
 
-            maybe_prompt = Some(Prompt {
-                symbol,
-                style,
-                color,
-                sorround,
-            });
+        if component_name == "prompt" {
+            maybe_prompt = Some(Prompt::from_yaml(component_config));
             continue;
         }
 
+        let (color, background, style, sorround) = component_appearance(component_config);
+
         components.push(Component::from_string(
             component_name,
+            component_config,
             color,
+            background,
             style,
             sorround,
         ))
     }
 
-    match maybe_prompt {
-        Some(prompt) => CommandBarConfig { components, prompt },
-        None => CommandBarConfig {
-            components,
-            prompt: Prompt::default(),
-        },
+    // The prompt symbol only makes sense on the left
+    let mut right_components = component_names(config, "right_component_order")
+        .into_iter()
+        .filter(|name| *name != "prompt")
+        .map(|name| {
+            let (color, background, style, sorround) = component_appearance(&config[name]);
+            Component::from_string(name, &config[name], color, background, style, sorround)
+        })
+        .collect();
+
+    // A template replaces the component lists, the symbol of `{symbol}` is
+    // configured in the `prompt` section
+    let template = match template_components(config, "template") {
+        Some(template_components) => {
+            components = template_components;
+            maybe_prompt = Some(Prompt::from_yaml(&config["prompt"]));
+            true
+        }
+        None => false,
+    };
+    let right_template = match template_components(config, "right_template") {
+        Some(template_components) => {
+            right_components = template_components;
+            true
+        }
+        None => false,
+    };
+
+    let highlight = HighlightConfig::from_yaml(&config["highlight"]);
+    let powerline = Powerline::from_yaml(&config["powerline"]);
+
+    CommandBarConfig {
+        components,
+        right_components,
+        prompt: maybe_prompt.unwrap_or_else(Prompt::default),
+        highlight,
+        powerline,
+        template,
+        right_template,
     }
 }
 
@@ -225,9 +607,198 @@ fn config_builder(config: Vec<Yaml>) -> CommandBarConfig {
 mod test {
 
     use super::*;
+    use crate::config::StyleName;
 
     #[test]
     fn test_config_yaml() {
         let c = command_bar_config_reader("examples/.civa.bar.yaml");
     }
+
+    #[test]
+    fn test_right_component_order() {
+        let config = YamlLoader::load_from_str(
+            "component_order: [cwd, prompt]\n\
+             right_component_order: [user, prompt, svn]\n\
+             svn:\n  color: green\n  sorround:\n    left: '('\n    right: ')'\n  symbols:\n    ahead: '↑'\n",
+        )
+        .unwrap();
+
+        let config = config_builder(config);
+        assert_eq!(config.components.len(), 1);
+        assert_eq!(config.right_components.len(), 2);
+
+        let svn = &config.right_components[1];
+        assert!(matches!(
+            svn.component_type,
+            CommandBarComponents::SVN { .. }
+        ));
+        assert!(matches!(svn.color.color_name, ColorName::GREEN));
+        assert_eq!(svn.sorround.left, "(");
+        assert!(matches!(
+            &svn.component_type,
+            CommandBarComponents::SVN { symbols } if symbols.ahead == "↑" && symbols.behind == "⇣"
+        ));
+    }
+
+    #[test]
+    fn test_component_options() {
+        let config = YamlLoader::load_from_str(
+            "component_order: [status, duration, time, prompt]\n\
+             duration:\n  threshold: 5\n  format: 'took %s'\n  timeout: 20\n\
+             time:\n  time_format: '%H:%M'\n",
+        )
+        .unwrap();
+
+        let config = config_builder(config);
+        let components = &config.components;
+
+        assert!(matches!(
+            &components[0].component_type,
+            CommandBarComponents::STATUS { failure_color } if matches!(failure_color.color_name, ColorName::RED)
+        ));
+        assert!(matches!(
+            components[1].component_type,
+            CommandBarComponents::DURATION { threshold } if threshold == Duration::from_secs(5)
+        ));
+        assert_eq!(components[1].format, "took %s");
+        assert_eq!(components[1].timeout, Duration::from_millis(20));
+        assert_eq!(components[2].timeout, Duration::from_millis(100));
+        assert_eq!(components[2].placeholder, "…");
+        assert!(matches!(
+            &components[2].component_type,
+            CommandBarComponents::TIME { format } if format == "%H:%M"
+        ));
+        assert_eq!(components[2].format, "%s");
+    }
+
+    #[test]
+    fn test_colors_and_powerline() {
+        let config = YamlLoader::load_from_str(
+            "component_order: [cwd, prompt]\n\
+             cwd:\n  color: 208\n  background: '#303030'\n  style: [bold, underline]\n\
+             prompt:\n  color: bright_blue\n  style: bold dim\n\
+             powerline:\n  separator: '>'\n",
+        )
+        .unwrap();
+
+        let config = config_builder(config);
+        let cwd = &config.components[0];
+
+        assert_eq!(cwd.color.color_name, ColorName::FIXED(208));
+        assert_eq!(
+            cwd.background.as_ref().map(|color| color.color_name),
+            Some(ColorName::RGB(48, 48, 48))
+        );
+        assert_eq!(
+            cwd.style.style_names,
+            vec![StyleName::BOLD, StyleName::UNDERLINE]
+        );
+        assert_eq!(config.prompt.color.color_name, ColorName::FIXED(12));
+        assert!(config.prompt.background.is_none());
+        assert_eq!(
+            config.prompt.style.style_names,
+            vec![StyleName::BOLD, StyleName::DIM]
+        );
+
+        let powerline = config.powerline.unwrap();
+        assert_eq!(powerline.separator, ">");
+        assert_eq!(powerline.right_separator, "\u{e0b2}");
+    }
+
+    #[test]
+    fn test_template() {
+        let config = YamlLoader::load_from_str(
+            "template: '{cwd:yellow,bold} {git:[%s]} {status?✘ } {symbol:max=3} '\n\
+             right_template: '{time:lpad=8}'\n\
+             cwd:\n  color: red\n  background: blue\n\
+             prompt:\n  symbol: 'λ'\n  color: green\n",
+        )
+        .unwrap();
+
+        let config = config_builder(config);
+        assert!(config.template && config.right_template);
+        assert_eq!(config.components.len(), 8);
+        assert_eq!(config.prompt.symbol, "λ");
+
+        let cwd = &config.components[0];
+        assert_eq!(cwd.color.color_name, ColorName::YELLOW);
+        assert_eq!(
+            cwd.background.as_ref().map(|color| color.color_name),
+            Some(ColorName::BLUE)
+        );
+        assert_eq!(cwd.style.style_names, vec![StyleName::BOLD]);
+
+        assert!(matches!(
+            &config.components[1].component_type,
+            CommandBarComponents::TEXT { text } if text == " "
+        ));
+        assert!(matches!(
+            config.components[2].component_type,
+            CommandBarComponents::SVN { .. }
+        ));
+        assert_eq!(config.components[2].format, "[%s]");
+        assert_eq!(config.components[4].condition.as_deref(), Some("✘ "));
+
+        let symbol = &config.components[6];
+        assert!(matches!(
+            symbol.component_type,
+            CommandBarComponents::PROMPT
+        ));
+        assert_eq!(symbol.color.color_name, ColorName::GREEN);
+        assert_eq!(symbol.max_width, Some(3));
+
+        assert_eq!(config.right_components[0].padding, Some(Padding::Left(8)));
+    }
+
+    #[test]
+    fn test_template_errors() {
+        let config = YamlLoader::load_from_str("cwd:\n  color: red\n").unwrap();
+
+        let error = components_from_template("{cwd} {gti}", &config[0]).unwrap_err();
+        assert_eq!(error.position, 7);
+        assert_eq!(error.message, "unknown component `gti`");
+
+        // Falls back to the component list
+        let config =
+            YamlLoader::load_from_str("component_order: [cwd, prompt]\ntemplate: '{cwd'\n")
+                .unwrap();
+        let config = config_builder(config);
+        assert!(!config.template);
+        assert_eq!(config.components.len(), 1);
+    }
+
+    #[test]
+    fn test_cwd_options() {
+        let config = YamlLoader::load_from_str(
+            "component_order: [cwd, prompt]\n\
+             cwd:\n  truncation: fish\n  max_width: 30\n",
+        )
+        .unwrap();
+
+        let config = config_builder(config);
+        assert!(matches!(
+            config.components[0].component_type,
+            CommandBarComponents::CWD {
+                truncation: CwdTruncation::Fish { segments: 1 },
+                max_width: Some(30)
+            }
+        ));
+
+        let config =
+            YamlLoader::load_from_str("cwd:\n  truncation: last\n  segments: 2\n").unwrap();
+        assert_eq!(
+            cwd_truncation(&config[0]["cwd"]),
+            CwdTruncation::Last { segments: 2 }
+        );
+        assert_eq!(cwd_truncation(&config[0]["missing"]), CwdTruncation::Full);
+    }
+
+    #[test]
+    fn test_invalid_time_format() {
+        let config = YamlLoader::load_from_str("valid: '%H:%M'\ninvalid: '%H %Q'\n").unwrap();
+
+        assert_eq!(time_format(&config[0]["valid"]), "%H:%M");
+        assert_eq!(time_format(&config[0]["invalid"]), DEFAULT_TIME_FORMAT);
+        assert_eq!(time_format(&config[0]["missing"]), DEFAULT_TIME_FORMAT);
+    }
 }
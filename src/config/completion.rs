@@ -0,0 +1,588 @@
+//
+// Completion specs describe the command line of a program so the completer
+// can offer its subcommands, flags and arguments.
+//
+// Specs are read from `$XDG_CONFIG_HOME/civa/completions/<command>.yaml`
+// or defined at runtime with the `complete` builtin.
+//
+//      command: cargo
+//      description: Rust's package manager
+//      flags:
+//        - --version
+//        - name: --manifest-path
+//          description: Path to Cargo.toml
+//          args: files
+//      subcommands:
+//        build:
+//          description: Compile the current package
+//          flags: [--release, --all-targets]
+//          args: none
+//
+// Argument types:
+//
+//      files       paths (default for commands and subcommands)
+//      dirs        directories only
+//      hosts       hosts from /etc/hosts and ~/.ssh/config
+//      none        nothing (default for flags, i.e. a switch)
+//      words:      a fixed list of words
+//      command:    every line of the output of a shell command
+//      git_*       objects of the current git repository: git_branches,
+//                  git_remote_branches, git_remotes, git_tags, git_stashes,
+//                  git_modified, git_staged and git_untracked
+//
+// A list of types offers the candidates of all of them, e.g.
+// `args: [git_branches, git_tags]`.
+//
+
+use std::collections::{BTreeMap, HashMap, HashSet};
+use std::fs;
+use std::path::Path;
+
+use log::{info, warn};
+use yaml_rust::yaml::Hash;
+use yaml_rust::{Yaml, YamlEmitter, YamlLoader};
+
+use super::error::ConfigError;
+
+#[derive(Debug, PartialEq, Copy, Clone)]
+pub enum GitSource {
+    Branches,
+    RemoteBranches,
+    Remotes,
+    Tags,
+    Stashes,
+    Modified,
+    Staged,
+    Untracked,
+}
+
+static GIT_SOURCES: &[(&str, GitSource)] = &[
+    ("git_branches", GitSource::Branches),
+    ("git_remote_branches", GitSource::RemoteBranches),
+    ("git_remotes", GitSource::Remotes),
+    ("git_tags", GitSource::Tags),
+    ("git_stashes", GitSource::Stashes),
+    ("git_modified", GitSource::Modified),
+    ("git_staged", GitSource::Staged),
+    ("git_untracked", GitSource::Untracked),
+];
+
+#[derive(Debug, PartialEq, Clone)]
+pub enum ArgType {
+    None,
+    Files,
+    Dirs,
+    Hosts,
+    Words(Vec<String>),
+    Command(String),
+    Git(GitSource),
+    Union(Vec<ArgType>),
+}
+
+impl ArgType {
+    pub fn from_name(name: &str) -> Option<Self> {
+        match name {
+            "none" => Some(ArgType::None),
+            "files" => Some(ArgType::Files),
+            "dirs" => Some(ArgType::Dirs),
+            "hosts" => Some(ArgType::Hosts),
+            _ => GIT_SOURCES
+                .iter()
+                .find(|(source_name, _)| *source_name == name)
+                .map(|(_, source)| ArgType::Git(*source)),
+        }
+    }
+
+    // A missing `args` key falls back to the given default
+    fn from_yaml(yaml: &Yaml, default: ArgType) -> Result<Self, ConfigError> {
+        if yaml.is_badvalue() {
+            return Ok(default);
+        }
+
+        if let Some(name) = yaml.as_str() {
+            return ArgType::from_name(name).ok_or_else(|| ConfigError {
+                message: format!("Unknown argument type '{}'", name),
+            });
+        }
+
+        if let Some(types) = yaml.as_vec() {
+            let mut union = Vec::new();
+            for arg_type in types {
+                union.push(ArgType::from_yaml(arg_type, ArgType::None)?);
+            }
+            return Ok(ArgType::Union(union));
+        }
+
+        if let Some(words) = yaml["words"].as_vec() {
+            return Ok(ArgType::Words(
+                words
+                    .iter()
+                    .filter_map(|word| word.as_str().map(String::from))
+                    .collect(),
+            ));
+        }
+
+        if let Some(command) = yaml["command"].as_str() {
+            return Ok(ArgType::Command(String::from(command)));
+        }
+
+        Err(ConfigError {
+            message: format!("Invalid argument type {:?}", yaml),
+        })
+    }
+
+    fn to_yaml(&self) -> Yaml {
+        let name = match self {
+            ArgType::None => "none",
+            ArgType::Files => "files",
+            ArgType::Dirs => "dirs",
+            ArgType::Hosts => "hosts",
+            ArgType::Words(words) => {
+                let mut hash = Hash::new();
+                hash.insert(
+                    Yaml::from_str("words"),
+                    Yaml::Array(words.iter().map(|w| Yaml::String(w.clone())).collect()),
+                );
+                return Yaml::Hash(hash);
+            }
+            ArgType::Command(command) => {
+                let mut hash = Hash::new();
+                hash.insert(Yaml::from_str("command"), Yaml::String(command.clone()));
+                return Yaml::Hash(hash);
+            }
+            ArgType::Git(source) => GIT_SOURCES.iter().find(|(_, s)| s == source).unwrap().0,
+            ArgType::Union(types) => {
+                return Yaml::Array(types.iter().map(ArgType::to_yaml).collect());
+            }
+        };
+
+        Yaml::from_str(name)
+    }
+}
+
+#[derive(Debug, PartialEq, Clone)]
+pub struct FlagSpec {
+    pub name: String,
+    pub description: String,
+    // Value the flag takes, `ArgType::None` for switches
+    pub args: ArgType,
+}
+
+impl FlagSpec {
+    pub fn new(name: &str) -> Self {
+        Self {
+            name: String::from(name),
+            description: String::new(),
+            args: ArgType::None,
+        }
+    }
+
+    // Either just the name or a hash with name, description and args
+    fn from_yaml(yaml: &Yaml) -> Result<Self, ConfigError> {
+        if let Some(name) = yaml.as_str() {
+            return Ok(FlagSpec::new(name));
+        }
+
+        let name = yaml["name"].as_str().ok_or_else(|| ConfigError {
+            message: format!("Flag without name {:?}", yaml),
+        })?;
+
+        Ok(Self {
+            name: String::from(name),
+            description: String::from(yaml["description"].as_str().unwrap_or("")),
+            args: ArgType::from_yaml(&yaml["args"], ArgType::None)?,
+        })
+    }
+
+    fn to_yaml(&self) -> Yaml {
+        if self.description.is_empty() && self.args == ArgType::None {
+            return Yaml::String(self.name.clone());
+        }
+
+        let mut hash = Hash::new();
+        hash.insert(Yaml::from_str("name"), Yaml::String(self.name.clone()));
+        if !self.description.is_empty() {
+            hash.insert(
+                Yaml::from_str("description"),
+                Yaml::String(self.description.clone()),
+            );
+        }
+        if self.args != ArgType::None {
+            hash.insert(Yaml::from_str("args"), self.args.to_yaml());
+        }
+        Yaml::Hash(hash)
+    }
+}
+
+// Spec of a command, subcommands are specs themselves so they can nest
+// (`git remote add`)
+#[derive(Debug, PartialEq, Clone)]
+pub struct CompletionSpec {
+    pub name: String,
+    pub description: String,
+    pub flags: Vec<FlagSpec>,
+    pub subcommands: BTreeMap<String, CompletionSpec>,
+    pub args: ArgType,
+}
+
+// What to offer for the word under the cursor: fixed candidates with their
+// description plus a dynamic source
+#[derive(Debug, PartialEq)]
+pub struct Resolved<'a> {
+    pub candidates: Vec<(&'a str, &'a str)>,
+    pub args: &'a ArgType,
+}
+
+impl CompletionSpec {
+    pub fn new(name: &str) -> Self {
+        Self {
+            name: String::from(name),
+            description: String::new(),
+            flags: Vec::new(),
+            subcommands: BTreeMap::new(),
+            args: ArgType::Files,
+        }
+    }
+
+    pub fn from_file(path: &Path) -> Result<Self, ConfigError> {
+        let contents = fs::read_to_string(path).map_err(|e| ConfigError {
+            message: format!("Could not read {}: {}", path.display(), e),
+        })?;
+
+        let docs = YamlLoader::load_from_str(&contents).map_err(|e| ConfigError {
+            message: format!("Could not parse {}: {}", path.display(), e),
+        })?;
+
+        let doc = docs.get(0).ok_or_else(|| ConfigError {
+            message: format!("Empty completion spec {}", path.display()),
+        })?;
+
+        // The command defaults to the file name, `cargo.yaml` -> `cargo`
+        let name = match doc["command"].as_str() {
+            Some(name) => String::from(name),
+            None => path
+                .file_stem()
+                .and_then(|stem| stem.to_str())
+                .map(String::from)
+                .unwrap_or_default(),
+        };
+
+        CompletionSpec::from_yaml(&name, doc)
+    }
+
+    fn from_yaml(name: &str, yaml: &Yaml) -> Result<Self, ConfigError> {
+        let mut spec = CompletionSpec::new(name);
+
+        spec.description = String::from(yaml["description"].as_str().unwrap_or(""));
+        spec.args = ArgType::from_yaml(&yaml["args"], ArgType::Files)?;
+
+        if let Some(flags) = yaml["flags"].as_vec() {
+            for flag in flags {
+                spec.flags.push(FlagSpec::from_yaml(flag)?);
+            }
+        }
+
+        if let Some(subcommands) = yaml["subcommands"].as_hash() {
+            for (name, subcommand) in subcommands {
+                let name = name.as_str().ok_or_else(|| ConfigError {
+                    message: format!("Invalid subcommand name {:?}", name),
+                })?;
+                spec.subcommands.insert(
+                    String::from(name),
+                    CompletionSpec::from_yaml(name, subcommand)?,
+                );
+            }
+        }
+
+        Ok(spec)
+    }
+
+    fn to_yaml(&self, root: bool) -> Yaml {
+        let mut hash = Hash::new();
+
+        if root {
+            hash.insert(Yaml::from_str("command"), Yaml::String(self.name.clone()));
+        }
+        if !self.description.is_empty() {
+            hash.insert(
+                Yaml::from_str("description"),
+                Yaml::String(self.description.clone()),
+            );
+        }
+        if self.args != ArgType::Files {
+            hash.insert(Yaml::from_str("args"), self.args.to_yaml());
+        }
+        if !self.flags.is_empty() {
+            hash.insert(
+                Yaml::from_str("flags"),
+                Yaml::Array(self.flags.iter().map(FlagSpec::to_yaml).collect()),
+            );
+        }
+        if !self.subcommands.is_empty() {
+            let mut subcommands = Hash::new();
+            for (name, subcommand) in &self.subcommands {
+                subcommands.insert(Yaml::String(name.clone()), subcommand.to_yaml(false));
+            }
+            hash.insert(Yaml::from_str("subcommands"), Yaml::Hash(subcommands));
+        }
+
+        Yaml::Hash(hash)
+    }
+
+    // Serializes the spec in the format it is read from
+    pub fn to_yaml_string(&self) -> String {
+        let mut out = String::new();
+        YamlEmitter::new(&mut out)
+            .dump(&self.to_yaml(true))
+            .unwrap();
+        out
+    }
+
+    pub fn flag(&self, name: &str) -> Option<&FlagSpec> {
+        self.flags.iter().find(|flag| flag.name == name)
+    }
+
+    // Walks the words after the command name to find out what the word under
+    // the cursor (`current`) is: a subcommand, a flag or the value of a flag
+    pub fn resolve(&self, words: &[&str], current: &str) -> Resolved<'_> {
+        let mut spec = self;
+        let mut flag_value: Option<&ArgType> = None;
+
+        for word in words {
+            if flag_value.is_none() {
+                if let Some(subcommand) = spec.subcommands.get(*word) {
+                    spec = subcommand;
+                    continue;
+                }
+            }
+
+            flag_value = spec
+                .flag(word)
+                .map(|flag| &flag.args)
+                .filter(|args| **args != ArgType::None);
+        }
+
+        if let Some(args) = flag_value {
+            return Resolved {
+                candidates: Vec::new(),
+                args,
+            };
+        }
+
+        if current.starts_with('-') {
+            return Resolved {
+                candidates: spec
+                    .flags
+                    .iter()
+                    .filter(|flag| flag.name.starts_with(current))
+                    .map(|flag| (flag.name.as_str(), flag.description.as_str()))
+                    .collect(),
+                args: &ArgType::None,
+            };
+        }
+
+        Resolved {
+            candidates: spec
+                .subcommands
+                .iter()
+                .filter(|(name, _)| name.starts_with(current))
+                .map(|(name, subcommand)| (name.as_str(), subcommand.description.as_str()))
+                .collect(),
+            args: &spec.args,
+        }
+    }
+}
+
+// All known specs by command name
+#[derive(Default)]
+pub struct CompletionSpecs {
+    specs: HashMap<String, CompletionSpec>,
+    // Commands whose spec was generated from a man page or `--help`, these
+    // never replace a spec written by the user
+    generated: HashSet<String>,
+}
+
+impl CompletionSpecs {
+    // Invalid files are skipped so a single broken spec does not keep the shell from starting
+    pub fn load_files<P: AsRef<Path>>(&mut self, paths: &[P], generated: bool) {
+        for path in paths {
+            let path = path.as_ref();
+            if path.extension().map_or(true, |ext| ext != "yaml") {
+                continue;
+            }
+
+            match CompletionSpec::from_file(path) {
+                Ok(spec) if generated => {
+                    self.insert_generated(spec);
+                }
+                Ok(spec) => {
+                    info!("Completion spec: {}", spec.name);
+                    self.insert(spec);
+                }
+                Err(e) => warn!("{}", e),
+            }
+        }
+    }
+
+    pub fn get(&self, command: &str) -> Option<&CompletionSpec> {
+        self.specs.get(command)
+    }
+
+    pub fn get_or_insert(&mut self, command: &str) -> &mut CompletionSpec {
+        self.generated.remove(command);
+        self.specs
+            .entry(String::from(command))
+            .or_insert_with(|| CompletionSpec::new(command))
+    }
+
+    pub fn insert(&mut self, spec: CompletionSpec) -> bool {
+        self.generated.remove(&spec.name);
+        self.specs.insert(spec.name.clone(), spec).is_some()
+    }
+
+    // Returns false if the user already defined a spec for the command
+    pub fn insert_generated(&mut self, spec: CompletionSpec) -> bool {
+        if self.specs.contains_key(&spec.name) && !self.generated.contains(&spec.name) {
+            return false;
+        }
+
+        self.generated.insert(spec.name.clone());
+        self.specs.insert(spec.name.clone(), spec);
+        true
+    }
+
+    pub fn remove(&mut self, command: &str) -> Option<CompletionSpec> {
+        self.generated.remove(command);
+        self.specs.remove(command)
+    }
+
+    pub fn names(&self) -> Vec<&str> {
+        let mut names: Vec<&str> = self.specs.keys().map(String::as_str).collect();
+        names.sort();
+        names
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn cargo_spec() -> CompletionSpec {
+        let yaml = YamlLoader::load_from_str(
+            "
+command: cargo
+flags:
+  - --version
+  - name: --manifest-path
+    description: Path to Cargo.toml
+    args: files
+subcommands:
+  build:
+    description: Compile the current package
+    flags: [--release, {name: --target, args: {words: [wasm32-unknown-unknown]}}]
+    args: none
+  run:
+    description: Run a binary
+    args: [git_branches, {words: [main]}]
+",
+        )
+        .unwrap();
+
+        CompletionSpec::from_yaml("cargo", &yaml[0]).unwrap()
+    }
+
+    #[test]
+    fn test_from_yaml() {
+        let spec = cargo_spec();
+
+        assert_eq!(spec.args, ArgType::Files);
+        assert_eq!(spec.flags.len(), 2);
+        assert_eq!(spec.flag("--manifest-path").unwrap().args, ArgType::Files);
+        assert_eq!(spec.subcommands["build"].args, ArgType::None);
+        assert_eq!(
+            spec.subcommands["run"].args,
+            ArgType::Union(vec![
+                ArgType::Git(GitSource::Branches),
+                ArgType::Words(vec![String::from("main")])
+            ])
+        );
+        assert_eq!(
+            spec.subcommands["build"].flag("--target").unwrap().args,
+            ArgType::Words(vec![String::from("wasm32-unknown-unknown")])
+        );
+    }
+
+    #[test]
+    fn test_resolve_subcommands() {
+        let spec = cargo_spec();
+        let resolved = spec.resolve(&[], "b");
+
+        assert_eq!(
+            resolved.candidates,
+            vec![("build", "Compile the current package")]
+        );
+        assert_eq!(resolved.args, &ArgType::Files);
+    }
+
+    #[test]
+    fn test_resolve_flags_and_values() {
+        let spec = cargo_spec();
+
+        let flags = spec.resolve(&["build"], "--r");
+        assert_eq!(flags.candidates, vec![("--release", "")]);
+
+        let value = spec.resolve(&["build", "--target"], "");
+        assert!(value.candidates.is_empty());
+        assert_eq!(
+            value.args,
+            &ArgType::Words(vec![String::from("wasm32-unknown-unknown")])
+        );
+
+        // A switch does not take a value
+        let after_switch = spec.resolve(&["build", "--release"], "");
+        assert_eq!(after_switch.args, &ArgType::None);
+    }
+
+    #[test]
+    fn test_example_specs() {
+        let mut specs = CompletionSpecs::default();
+        specs.load_files(
+            &[
+                "examples/completions/cargo.yaml",
+                "examples/completions/git.yaml",
+                "examples/completions/make.yaml",
+            ],
+            false,
+        );
+
+        assert_eq!(specs.names(), vec!["cargo", "git", "make"]);
+        assert_eq!(
+            specs
+                .get("git")
+                .unwrap()
+                .resolve(&["remote"], "a")
+                .candidates,
+            vec![("add", "Add a remote")]
+        );
+    }
+
+    #[test]
+    fn test_generated_specs_do_not_replace_user_specs() {
+        let mut specs = CompletionSpecs::default();
+
+        assert!(specs.insert_generated(CompletionSpec::new("ls")));
+        assert!(specs.insert_generated(CompletionSpec::new("ls")));
+
+        specs.insert(cargo_spec());
+        assert!(!specs.insert_generated(CompletionSpec::new("cargo")));
+        assert_eq!(specs.get("cargo").unwrap(), &cargo_spec());
+    }
+
+    #[test]
+    fn test_yaml_round_trip() {
+        let spec = cargo_spec();
+        let yaml = YamlLoader::load_from_str(&spec.to_yaml_string()).unwrap();
+
+        assert_eq!(CompletionSpec::from_yaml("cargo", &yaml[0]).unwrap(), spec);
+    }
+}
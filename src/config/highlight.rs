@@ -0,0 +1,131 @@
+//
+// Colors of the syntax highlighting of the input line, configured in the
+// `highlight` section of `bar.yaml` with the same color and style names
+// as the command bar components:
+//
+//      highlight:
+//          command:
+//              color: green
+//          unknown_command:
+//              color: red
+//              style: bold
+//          path:
+//              style: underline
+//
+// A token without color keeps the color of the terminal.
+//
+
+use yaml_rust::Yaml;
+
+use super::{Color, Style};
+
+#[derive(Debug)]
+pub struct HighlightStyle {
+    pub color: Option<Color>,
+    pub style: Style,
+}
+
+impl HighlightStyle {
+    fn new(color: Option<&str>, style: &str) -> Self {
+        Self {
+            color: color.map(Color::from_string),
+            style: Style::from_string(style),
+        }
+    }
+
+    // Keys missing in the config fall back to the default
+    fn from_yaml(yaml: &Yaml, default: HighlightStyle) -> Self {
+        if yaml.is_badvalue() {
+            return default;
+        }
+
+        Self {
+            color: Color::from_yaml(&yaml["color"]).or(default.color),
+            style: Style::from_yaml(&yaml["style"]).unwrap_or(default.style),
+        }
+    }
+}
+
+#[derive(Debug)]
+pub struct HighlightConfig {
+    pub command: HighlightStyle,
+    pub unknown_command: HighlightStyle,
+    pub string: HighlightStyle,
+    pub variable: HighlightStyle,
+    pub operator: HighlightStyle,
+    pub redirection: HighlightStyle,
+    pub comment: HighlightStyle,
+    pub path: HighlightStyle,
+}
+
+impl HighlightConfig {
+    pub fn default() -> Self {
+        Self {
+            command: HighlightStyle::new(Some("green"), "normal"),
+            unknown_command: HighlightStyle::new(Some("red"), "bold"),
+            string: HighlightStyle::new(Some("yellow"), "normal"),
+            variable: HighlightStyle::new(Some("blue"), "normal"),
+            operator: HighlightStyle::new(Some("blue"), "bold"),
+            redirection: HighlightStyle::new(Some("blue"), "normal"),
+            comment: HighlightStyle::new(Some("white"), "italic"),
+            path: HighlightStyle::new(None, "underline"),
+        }
+    }
+
+    pub fn from_yaml(yaml: &Yaml) -> Self {
+        let default = HighlightConfig::default();
+
+        Self {
+            command: HighlightStyle::from_yaml(&yaml["command"], default.command),
+            unknown_command: HighlightStyle::from_yaml(
+                &yaml["unknown_command"],
+                default.unknown_command,
+            ),
+            string: HighlightStyle::from_yaml(&yaml["string"], default.string),
+            variable: HighlightStyle::from_yaml(&yaml["variable"], default.variable),
+            operator: HighlightStyle::from_yaml(&yaml["operator"], default.operator),
+            redirection: HighlightStyle::from_yaml(&yaml["redirection"], default.redirection),
+            comment: HighlightStyle::from_yaml(&yaml["comment"], default.comment),
+            path: HighlightStyle::from_yaml(&yaml["path"], default.path),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::{ColorName, StyleName};
+    use yaml_rust::YamlLoader;
+
+    #[test]
+    fn test_from_yaml_falls_back_to_defaults() {
+        let yaml = YamlLoader::load_from_str(
+            "
+command:
+    color: yellow
+path:
+    color: blue
+",
+        )
+        .unwrap();
+
+        let config = HighlightConfig::from_yaml(&yaml[0]);
+
+        match config.command.color {
+            Some(Color {
+                color_name: ColorName::YELLOW,
+            }) => {}
+            _ => panic!("command color not read"),
+        }
+        match config.path.style.style_names.as_slice() {
+            [StyleName::UNDERLINE] => {}
+            _ => panic!("path style should keep its default"),
+        }
+        match config.unknown_command.color {
+            Some(Color {
+                color_name: ColorName::RED,
+            }) => {}
+            _ => panic!("unknown command color should keep its default"),
+        }
+    }
+}
@@ -36,6 +36,16 @@ use log::info;
 
 use pyo3::prelude::*;
 use pyo3::types::{IntoPyDict, PyDict};
+use regex::Regex;
+
+use super::alias::AliasKind;
+use super::error::ConfigError;
+use super::ContextManager;
+
+pub static COMMAND_NOT_FOUND_HOOK: &str = "command_not_found_handler";
+
+// Functions the config can define to customize the shell
+static HOOKS: &[&str] = &[COMMAND_NOT_FOUND_HOOK];
 
 pub struct PyConfRuntime<'a> {
     py: Python<'a>,
@@ -51,16 +61,21 @@ impl<'a> PyConfRuntime<'a> {
         }
     }
 
-    pub fn exec_configs(&self) {
-        let locals = PyDict::new(self.py);
-        // let globals = PyDict::new(self.py);
+    pub fn exec_configs(&self, ctx: &ContextManager) {
+        // Run config and helpers in a single namespace, otherwise the
+        // `global` statements of the helpers can't see the collections
         let globals = [("__builtins__", self.py.import("builtins").unwrap())].into_py_dict(self.py);
 
         let config_content = self.get_py_file_content();
 
         let setup = r#"
 _aliases = {}
+_global_aliases = {}
+_suffix_aliases = {}
+_abbreviations = {}
 _exports = {}
+_share_history = False
+_history_ignore = []
 
 def aliases(kwrags):
     global _aliases
@@ -71,24 +86,123 @@ def alias(key, value):
     global _aliases
     _aliases[key] = value
 
+def global_alias(key, value):
+    global _global_aliases
+    _global_aliases[key] = value
+
+def suffix_alias(extension, value):
+    global _suffix_aliases
+    _suffix_aliases[extension] = value
+
+def abbr(key, value):
+    global _abbreviations
+    _abbreviations[key] = value
+
 def export(key, value):
     global _exports
     _exports[key] = value
+
+def share_history(enabled=True):
+    global _share_history
+    _share_history = enabled
+
+def history_ignore(pattern):
+    global _history_ignore
+    _history_ignore.append(pattern)
             "#;
 
         let exec_script: Vec<&str> = vec![setup, config_content.as_str()];
 
         match self
             .py
-            .run(exec_script.join("\n").as_str(), Some(globals), Some(locals))
+            .run(exec_script.join("\n").as_str(), Some(globals), None)
         {
             Ok(_) => info!("Success"),
             Err(err) => info!("Error: {:?}", err.print(self.py)),
         }
 
-        match locals.get_item("foo").unwrap().call0() {
-            Ok(_) => info!("Success"),
-            Err(err) => info!("Error: {:?}", err.print(self.py)),
+        if let Some(foo) = globals.get_item("foo") {
+            match foo.call0() {
+                Ok(_) => info!("Success"),
+                Err(err) => info!("Error: {:?}", err.print(self.py)),
+            }
+        }
+
+        self.load_aliases(globals, ctx);
+        self.load_hooks(globals, ctx);
+        self.load_settings(globals, ctx);
+    }
+
+    fn load_settings(&self, globals: &PyDict, ctx: &ContextManager) {
+        if let Some(share_history) = globals.get_item("_share_history") {
+            match share_history.extract::<bool>() {
+                Ok(enabled) => ctx.share_history.set(enabled),
+                Err(err) => info!("Error: {:?}", err.print(self.py)),
+            }
+        }
+
+        if let Some(patterns) = globals.get_item("_history_ignore") {
+            match patterns.extract::<Vec<String>>() {
+                Ok(patterns) => {
+                    let mut privacy = ctx.history_privacy.borrow_mut();
+                    for pattern in patterns {
+                        match Regex::new(&pattern) {
+                            Ok(re) => privacy.ignore_patterns.push(re),
+                            Err(err) => info!("Invalid history ignore pattern: {}", err),
+                        }
+                    }
+                }
+                Err(err) => info!("Error: {:?}", err.print(self.py)),
+            }
+        }
+    }
+
+    fn load_hooks(&self, globals: &PyDict, ctx: &ContextManager) {
+        let mut hooks = ctx.hooks.borrow_mut();
+
+        for name in HOOKS {
+            match globals.get_item(*name) {
+                Some(function) if function.is_callable() => {
+                    info!("Found hook {}", name);
+                    hooks.insert(String::from(*name), function.to_object(self.py));
+                }
+                _ => {}
+            }
+        }
+    }
+
+    // Hands the aliases and abbreviations collected by the helpers over to the alias system
+    fn load_aliases(&self, globals: &PyDict, ctx: &ContextManager) {
+        let mut alias_system = ctx.alias_system.borrow_mut();
+
+        let kinds = [
+            ("_aliases", AliasKind::Command),
+            ("_global_aliases", AliasKind::Global),
+            ("_suffix_aliases", AliasKind::Suffix),
+        ];
+
+        for (name, kind) in kinds.iter() {
+            if let Some(aliases) = self.extract_map(globals, name) {
+                for (key, value) in aliases {
+                    alias_system.update_alias_of_kind(*kind, key, value);
+                }
+            }
+        }
+
+        if let Some(abbreviations) = self.extract_map(globals, "_abbreviations") {
+            for (key, value) in abbreviations {
+                alias_system.abbreviations().insert(key, value);
+            }
+        }
+    }
+
+    fn extract_map(&self, globals: &PyDict, name: &str) -> Option<HashMap<String, String>> {
+        match globals.get_item(name)?.extract::<HashMap<String, String>>() {
+            Ok(map) => Some(map),
+            Err(err) => {
+                info!("Error: {:?}", err.print(self.py));
+                None
+            }
         }
     }
 
@@ -112,3 +226,19 @@ def export(key, value):
 
     // }
 }
+
+// Calls a hook of the python config with the command name and its arguments.
+// The hook may return an exit code, `None` is passed on as no exit code.
+pub fn call_hook(hook: &PyObject, name: &str, args: &[String]) -> Result<Option<i32>, ConfigError> {
+    let gil = Python::acquire_gil();
+    let py = gil.python();
+
+    hook.call1(py, (name, args.to_vec()))
+        .and_then(|result| result.extract::<Option<i32>>(py))
+        .map_err(|err| {
+            err.print(py);
+            ConfigError {
+                message: format!("Hook for '{}' failed", name),
+            }
+        })
+}
@@ -1,25 +1,54 @@
 use super::alias::AliasSystem;
 use super::command_bar::{command_bar_config_reader, CommandBarConfig};
+use super::completion::CompletionSpecs;
+use crate::builtins::BuiltinRegistry;
 use crate::env::environment::EnvManager;
+use crate::history::jsonl::JsonlHistoryStore;
+use crate::history::privacy::HistoryPrivacy;
+use crate::history::{Session, SharedHistory};
 
+use pyo3::PyObject;
 use rcalc::Calculator;
-use std::cell::RefCell;
-use std::fs::File;
-use std::path::Path;
+use std::cell::{Cell, RefCell};
+use std::collections::HashMap;
+use std::io;
+use std::path::PathBuf;
+use std::rc::Rc;
+use std::sync::{Arc, RwLock};
+use std::time::Duration;
 use xdg;
 
 static PREFIX: &str = "civa";
 static COMMAND_BAR_CONFIG_FILE: &str = "bar.yaml";
-static HISTORY_FILE: &str = "civa.history.txt";
+static HISTORY_FILE: &str = "history.jsonl";
 static ALIAS_FILE: &str = "civa.alias.txt";
+static COMPLETIONS_DIR: &str = "completions";
 
 pub struct ContextManager {
     // config_dir: Option<xdg::BaseDirectories>,
     pub command_bar_config: CommandBarConfig,
     base_dir: xdg::BaseDirectories,
-    pub alias_system: RefCell<AliasSystem>,
-    pub env_manager: EnvManager,
+    // Shared with the editor helper for completion
+    pub alias_system: Rc<RefCell<AliasSystem>>,
+    pub env_manager: Rc<EnvManager>,
     pub calculator: RefCell<Calculator>,
+    pub builtins: Rc<RefCell<BuiltinRegistry>>,
+    // Per command completion specs, shared with the completer
+    pub completion_specs: Rc<RefCell<CompletionSpecs>>,
+    // Functions of the python config called by the shell, e.g. `command_not_found_handler`
+    pub hooks: RefCell<HashMap<String, PyObject>>,
+    // Shared with the editor, which is populated from it at startup
+    pub history: SharedHistory,
+    pub session: Session,
+    // Import the entries of other sessions before every prompt
+    pub share_history: Cell<bool>,
+    // Ignore patterns of the config and incognito mode
+    pub history_privacy: RefCell<HistoryPrivacy>,
+    // Lines run in this session as typed, history expansion refers to them
+    pub session_lines: RefCell<Vec<String>>,
+    // Shown by the status and duration components of the command bar
+    pub last_exit_status: Cell<Option<i32>>,
+    pub last_duration: Cell<Option<Duration>>,
 }
 
 impl ContextManager {
@@ -42,36 +71,43 @@ impl ContextManager {
                 )
                 .unwrap();
 
+                // Generated specs first, so the ones written by the user take precedence
+                let mut completion_specs = CompletionSpecs::default();
+                completion_specs.load_files(&dir.list_cache_files(COMPLETIONS_DIR), true);
+                completion_specs.load_files(&dir.list_config_files(COMPLETIONS_DIR), false);
+
+                let session = Session::new();
+                let history = JsonlHistoryStore::open(
+                    &dir.place_data_file(HISTORY_FILE)
+                        .expect("Could not create the data directory"),
+                    &session.id,
+                );
+
                 return Self {
                     calculator: RefCell::new(Calculator::new()),
                     base_dir: dir,
                     command_bar_config,
-                    alias_system: RefCell::new(alias_system),
-                    env_manager: EnvManager::new(),
+                    alias_system: Rc::new(RefCell::new(alias_system)),
+                    env_manager: Rc::new(EnvManager::new()),
+                    builtins: Rc::new(RefCell::new(BuiltinRegistry::with_defaults())),
+                    completion_specs: Rc::new(RefCell::new(completion_specs)),
+                    hooks: RefCell::new(HashMap::new()),
+                    history: Arc::new(RwLock::new(history)),
+                    session,
+                    share_history: Cell::new(false),
+                    history_privacy: RefCell::new(HistoryPrivacy::default()),
+                    session_lines: RefCell::new(Vec::new()),
+                    last_exit_status: Cell::new(None),
+                    last_duration: Cell::new(None),
                 };
             }
             Err(_) => panic!("Could not find home"), //CommandBarConfig::default(),
         };
     }
 
-    pub fn retrieve_history_cache(&self) -> String {
-        match self.base_dir.find_cache_file(HISTORY_FILE) {
-            Some(buf) => buf.to_str().unwrap().to_string(),
-            None => {
-                let cache_file = self
-                    .base_dir
-                    .place_cache_file(Path::new(HISTORY_FILE))
-                    .unwrap()
-                    .to_str()
-                    .unwrap()
-                    .to_string();
-
-                match File::create(Path::new(&cache_file)) {
-                    Ok(_) => cache_file,
-                    Err(_) => String::new(),
-                }
-            }
-        }
+    // Where specs generated by `update_completions` are stored
+    pub fn completion_cache_dir(&self) -> io::Result<PathBuf> {
+        self.base_dir.create_cache_directory(COMPLETIONS_DIR)
     }
 
     // pub fn retrieve_alias_config(&self) -> String {
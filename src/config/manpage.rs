@@ -0,0 +1,452 @@
+//
+// Generates completion specs from the documentation installed on the system,
+// similar to fish's `fish_update_completions`.
+//
+// Two sources are understood:
+//
+//      - man pages (roff), both the man macros (`.TP`, `.IP`) used by GNU
+//        and help2man pages and the mdoc macros (`.It Fl`) of BSD pages
+//      - the output of `command --help`, option lines like
+//        `  -a, --all    do not ignore entries` and a `Commands:` section
+//
+// Only local man pages are read, nothing is downloaded. Compressed pages
+// are decompressed with `gzip -dc`.
+//
+
+use std::collections::HashSet;
+use std::env;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::process::{Command, Stdio};
+
+use regex::Regex;
+
+use super::completion::{ArgType, CompletionSpec, FlagSpec};
+use super::error::ConfigError;
+
+static DEFAULT_MAN_PATH: &[&str] = &["/usr/share/man", "/usr/local/share/man"];
+// Sections with user commands and system administration commands
+static SECTIONS: &[&str] = &["1", "8"];
+static MAX_DESCRIPTION: usize = 80;
+
+pub fn man_dirs() -> Vec<PathBuf> {
+    match env::var("MANPATH") {
+        Ok(man_path) if !man_path.is_empty() => man_path
+            .split(':')
+            .filter(|dir| !dir.is_empty())
+            .map(PathBuf::from)
+            .collect(),
+        _ => DEFAULT_MAN_PATH.iter().map(PathBuf::from).collect(),
+    }
+}
+
+// Command name of a man page file, `ls.1.gz` -> (`ls`, `1`)
+fn page_name(file_name: &str) -> Option<(&str, &str)> {
+    let file_name = file_name.trim_end_matches(".gz");
+    let dot = file_name.rfind('.')?;
+    Some((&file_name[..dot], &file_name[dot + 1..]))
+}
+
+// Man page of a command in one of the command sections
+pub fn find_man_page(command: &str) -> Option<PathBuf> {
+    for dir in man_dirs() {
+        for section in SECTIONS {
+            let section_dir = dir.join(format!("man{}", section));
+            let entries = match fs::read_dir(&section_dir) {
+                Ok(entries) => entries,
+                Err(_) => continue,
+            };
+
+            for entry in entries.filter_map(Result::ok) {
+                let file_name = entry.file_name();
+                let file_name = file_name.to_string_lossy();
+                if let Some((name, page_section)) = page_name(&file_name) {
+                    if name == command && page_section.starts_with(section) {
+                        return Some(entry.path());
+                    }
+                }
+            }
+        }
+    }
+
+    None
+}
+
+// All commands with a man page, each with the first page found
+pub fn list_man_pages() -> Vec<(String, PathBuf)> {
+    let mut seen = HashSet::new();
+    let mut pages = Vec::new();
+
+    for dir in man_dirs() {
+        for section in SECTIONS {
+            let entries = match fs::read_dir(dir.join(format!("man{}", section))) {
+                Ok(entries) => entries,
+                Err(_) => continue,
+            };
+
+            for entry in entries.filter_map(Result::ok) {
+                let file_name = entry.file_name();
+                let file_name = file_name.to_string_lossy();
+                if let Some((name, _)) = page_name(&file_name) {
+                    if seen.insert(String::from(name)) {
+                        pages.push((String::from(name), entry.path()));
+                    }
+                }
+            }
+        }
+    }
+
+    pages.sort();
+    pages
+}
+
+pub fn read_man_page(path: &Path) -> Result<String, ConfigError> {
+    let contents = if path.extension().map_or(false, |ext| ext == "gz") {
+        let output = Command::new("gzip")
+            .arg("-dc")
+            .arg(path)
+            .stderr(Stdio::null())
+            .output()
+            .map_err(|e| ConfigError {
+                message: format!("Could not run gzip: {}", e),
+            })?;
+        String::from_utf8_lossy(&output.stdout).into_owned()
+    } else {
+        fs::read_to_string(path).map_err(|e| ConfigError {
+            message: format!("Could not read {}: {}", path.display(), e),
+        })?
+    };
+
+    // `.so man1/other.1` pages only include another page
+    if let Some(include) = contents.trim().strip_prefix(".so ") {
+        let root = path
+            .parent()
+            .and_then(Path::parent)
+            .ok_or_else(|| ConfigError {
+                message: format!("Invalid man page path {}", path.display()),
+            })?;
+        let included = root.join(include.trim());
+
+        if included.exists() {
+            return read_man_page(&included);
+        }
+        return read_man_page(&root.join(format!("{}.gz", include.trim())));
+    }
+
+    Ok(contents)
+}
+
+// Output of `command --help`, None if the command could not be run
+pub fn read_help_output(command: &str) -> Option<String> {
+    let output = Command::new(command)
+        .arg("--help")
+        .stdin(Stdio::null())
+        .output()
+        .ok()?;
+
+    // Some programs print their usage to stderr
+    let mut text = String::from_utf8_lossy(&output.stdout).into_owned();
+    if text.trim().is_empty() {
+        text = String::from_utf8_lossy(&output.stderr).into_owned();
+    }
+
+    Some(text)
+}
+
+// Removes roff escapes and font changes, `\fB\-\-all\fR` -> `--all`
+fn clean_roff(text: &str) -> String {
+    lazy_static! {
+        static ref ESCAPE_REGEX: Regex =
+            Regex::new(r"\\f(?:\[[^\]]*\]|\(..|.)|\\\*(?:\(..|.)|\\\((?:aq|dq|..)|\\.").unwrap();
+    }
+
+    ESCAPE_REGEX
+        .replace_all(text, |caps: &regex::Captures| match &caps[0] {
+            r"\-" | r"\(hy" | r"\(em" | r"\(en" => "-",
+            r"\(aq" => "'",
+            r"\(dq" => "\"",
+            r"\e" | r"\\" => "\\",
+            r"\ " | r"\~" => " ",
+            _ => "",
+        })
+        .into_owned()
+}
+
+// Text of a line, without the macro name and quotes for macro lines
+fn macro_text(line: &str) -> String {
+    let text = if line.starts_with('.') {
+        line.splitn(2, char::is_whitespace).nth(1).unwrap_or("")
+    } else {
+        line
+    };
+
+    clean_roff(&text.replace('"', ""))
+}
+
+// mdoc option tags, `.It Fl f Ar file` -> `-f file`
+fn mdoc_tag(line: &str) -> String {
+    let mut tag = Vec::new();
+    let mut tokens = line.split_whitespace().skip(1);
+
+    while let Some(token) = tokens.next() {
+        match token {
+            "Fl" => tag.push(format!("-{}", tokens.next().unwrap_or(""))),
+            "Ar" | "Cm" => tag.push(String::from(tokens.next().unwrap_or("arg"))),
+            "Op" | "Oo" | "Oc" | "Ns" | "Pq" | "Xo" | "Ek" => {}
+            _ => tag.push(String::from(token)),
+        }
+    }
+
+    clean_roff(&tag.join(" "))
+}
+
+// Flags named in an option tag and whether they take a value,
+// `-b, --block-size=SIZE` -> [(-b, false), (--block-size, true)]
+fn parse_option_tag(tag: &str) -> Vec<(String, bool)> {
+    lazy_static! {
+        static ref FLAG_REGEX: Regex =
+            Regex::new(r"(?:^|[\s,|\[])(--?[A-Za-z0-9?][\w.-]*)(=|\[=|\s+[<A-Za-z])?").unwrap();
+    }
+
+    FLAG_REGEX
+        .captures_iter(tag.trim())
+        .map(|caps| (String::from(&caps[1]), caps.get(2).is_some()))
+        .collect()
+}
+
+// First sentence of a description, shortened to fit the completion menu
+fn short_description(text: &str) -> String {
+    let text = text.split_whitespace().collect::<Vec<&str>>().join(" ");
+    let sentence = match text.find(". ") {
+        Some(end) => &text[..end],
+        None => text.trim_end_matches('.').trim_end(),
+    };
+
+    if sentence.chars().count() > MAX_DESCRIPTION {
+        let short: String = sentence.chars().take(MAX_DESCRIPTION - 3).collect();
+        format!("{}...", short.trim_end())
+    } else {
+        String::from(sentence)
+    }
+}
+
+fn add_flags(spec: &mut CompletionSpec, tag: &str, description: &str) {
+    for (name, takes_value) in parse_option_tag(tag) {
+        if spec.flag(&name).is_some() {
+            continue;
+        }
+
+        spec.flags.push(FlagSpec {
+            name,
+            description: short_description(description),
+            args: if takes_value {
+                ArgType::Files
+            } else {
+                ArgType::None
+            },
+        });
+    }
+}
+
+pub fn parse_man_page(command: &str, roff: &str) -> CompletionSpec {
+    let mut spec = CompletionSpec::new(command);
+    let lines: Vec<&str> = roff
+        .lines()
+        .filter(|line| !line.starts_with(".\\\"") && !line.starts_with("'\\\""))
+        .collect();
+
+    // The first text line after the next macro line, used as description
+    let text_after = |start: usize| -> String {
+        lines[start..]
+            .iter()
+            .skip_while(|line| line.starts_with('.'))
+            .take_while(|line| !line.starts_with('.'))
+            .map(|line| clean_roff(line))
+            .collect::<Vec<String>>()
+            .join(" ")
+    };
+
+    let mut i = 0;
+    while i < lines.len() {
+        let line = lines[i].trim_end();
+
+        if line.starts_with(".SH") && macro_text(line).eq_ignore_ascii_case("name") {
+            // `ls \- list directory contents`
+            let name = text_after(i + 1);
+            if let Some(dash) = name.find(" - ") {
+                spec.description = short_description(&name[dash + 3..]);
+            }
+        } else if line == ".TP" || line.starts_with(".TP ") {
+            // The next line is the tag, the lines after it the description
+            if let Some(tag) = lines.get(i + 1) {
+                add_flags(&mut spec, &macro_text(tag), &text_after(i + 2));
+                i += 1;
+            }
+        } else if line.starts_with(".IP ") {
+            add_flags(&mut spec, &macro_text(line), &text_after(i + 1));
+        } else if line.starts_with(".It ") {
+            add_flags(&mut spec, &mdoc_tag(line), &text_after(i + 1));
+        } else if line.starts_with(".Nd ") {
+            spec.description = short_description(&macro_text(line));
+        }
+
+        i += 1;
+    }
+
+    spec
+}
+
+pub fn parse_help_output(command: &str, help: &str) -> CompletionSpec {
+    lazy_static! {
+        // `  -a, --all       do not ignore entries`
+        static ref OPTION_REGEX: Regex =
+            Regex::new(r"^\s+(?P<tag>-\S.*?)(?:\s{2,}(?P<description>\S.*))?$").unwrap();
+        // `    build, b    Compile the current package`
+        static ref COMMAND_REGEX: Regex =
+            Regex::new(r"^\s+(?P<name>[a-z][\w-]*)(?:,\s*[\w-]+)*(?:\s{2,}(?P<description>\S.*))?$")
+                .unwrap();
+    }
+
+    let mut spec = CompletionSpec::new(command);
+    let lines: Vec<&str> = help.lines().collect();
+    let mut in_commands = false;
+
+    for (i, line) in lines.iter().enumerate() {
+        if !line.starts_with(char::is_whitespace) && !line.trim().is_empty() {
+            in_commands = line
+                .to_lowercase()
+                .trim_end_matches(':')
+                .ends_with("commands");
+            continue;
+        }
+
+        if let Some(caps) = OPTION_REGEX.captures(line) {
+            // The description may start on the next line
+            let description = match caps.name("description") {
+                Some(description) => String::from(description.as_str()),
+                None => lines
+                    .get(i + 1)
+                    .map(|next| next.trim())
+                    .filter(|next| !next.starts_with('-'))
+                    .map(String::from)
+                    .unwrap_or_default(),
+            };
+            add_flags(&mut spec, &caps["tag"], &description);
+        } else if in_commands {
+            if let Some(caps) = COMMAND_REGEX.captures(line) {
+                let mut subcommand = CompletionSpec::new(&caps["name"]);
+                subcommand.description = caps
+                    .name("description")
+                    .map(|d| short_description(d.as_str()))
+                    .unwrap_or_default();
+                spec.subcommands
+                    .entry(String::from(&caps["name"]))
+                    .or_insert(subcommand);
+            }
+        }
+    }
+
+    spec
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_clean_roff() {
+        assert_eq!(clean_roff(r"\fB\-a\fR, \fB\-\-all\fR"), "-a, --all");
+        assert_eq!(
+            clean_roff(r"\fB\-\-block\-size\fR=\fI\,SIZE\/\fR"),
+            "--block-size=SIZE"
+        );
+    }
+
+    #[test]
+    fn test_parse_option_tag() {
+        assert_eq!(
+            parse_option_tag("-a, --all"),
+            vec![(String::from("-a"), false), (String::from("--all"), false)]
+        );
+        assert_eq!(
+            parse_option_tag("--color[=WHEN]"),
+            vec![(String::from("--color"), true)]
+        );
+        assert_eq!(
+            parse_option_tag("-f file"),
+            vec![(String::from("-f"), true)]
+        );
+    }
+
+    #[test]
+    fn test_parse_man_page() {
+        let roff = r#".TH LS "1"
+.SH NAME
+ls \- list directory contents
+.SH DESCRIPTION
+.TP
+\fB\-a\fR, \fB\-\-all\fR
+do not ignore entries starting with .
+.TP
+\fB\-\-block\-size\fR=\fI\,SIZE\/\fR
+with \fB\-l\fR, scale sizes by SIZE when printing them;
+.It Fl B Ar bind_interface
+Bind to the address of
+.Ar bind_interface
+"#;
+
+        let spec = parse_man_page("ls", roff);
+
+        assert_eq!(spec.description, "list directory contents");
+        assert_eq!(
+            spec.flag("--all").unwrap().description,
+            "do not ignore entries starting with"
+        );
+        assert_eq!(spec.flag("-a").unwrap().args, ArgType::None);
+        assert_eq!(spec.flag("--block-size").unwrap().args, ArgType::Files);
+        assert_eq!(spec.flag("-B").unwrap().args, ArgType::Files);
+    }
+
+    #[test]
+    fn test_parse_help_output() {
+        let help = "Rust's package manager
+
+Usage: cargo [OPTIONS] [COMMAND]
+
+Options:
+  -V, --version             Print version info and exit
+      --list                List installed commands
+  -C <DIRECTORY>
+          Change to DIRECTORY before doing anything
+
+Commands:
+    build, b    Compile the current package
+    check, c    Analyze the current package
+";
+
+        let spec = parse_help_output("cargo", help);
+
+        assert_eq!(
+            spec.flag("--version").unwrap().description,
+            "Print version info and exit"
+        );
+        assert!(spec.flag("--list").is_some());
+        assert_eq!(
+            spec.flag("-C").unwrap().description,
+            "Change to DIRECTORY before doing anything"
+        );
+        assert_eq!(spec.flag("-C").unwrap().args, ArgType::Files);
+        assert_eq!(
+            spec.subcommands["build"].description,
+            "Compile the current package"
+        );
+        assert_eq!(spec.subcommands.len(), 2);
+    }
+
+    #[test]
+    fn test_page_name() {
+        assert_eq!(page_name("ls.1.gz"), Some(("ls", "1")));
+        assert_eq!(page_name("CA.pl.1ssl.gz"), Some(("CA.pl", "1ssl")));
+        assert_eq!(page_name("README"), None);
+    }
+}
@@ -1,13 +1,22 @@
-mod alias;
+pub mod alias;
 pub mod command_bar;
+pub mod completion;
 mod error;
+pub mod highlight;
 pub mod interpreter;
 pub mod manager;
+pub mod manpage;
+pub mod template;
 
 pub use interpreter::PyConfRuntime;
 pub use manager::ContextManager;
 
-#[derive(Debug)]
+use yaml_rust::Yaml;
+
+// Colors of bar.yaml: the 16 named ANSI colors, a color of the 256 color
+// palette (`color: 208`) or a true color (`color: "#ff8800"`). Terminals
+// with fewer colors get the closest one they support, see `cli::colors`.
+#[derive(Debug, Clone, Copy, PartialEq)]
 pub enum ColorName {
     RED,
     BLUE,
@@ -15,40 +24,83 @@ pub enum ColorName {
     BLACK,
     WHITE,
     GREEN,
+    MAGENTA,
+    CYAN,
+    // Palette index, 8 to 15 are the bright variants of the named colors
+    FIXED(u8),
+    RGB(u8, u8, u8),
 }
 
-#[derive(Debug)]
+#[derive(Debug, Clone, Copy, PartialEq)]
 pub enum StyleName {
     BOLD,
     NORMAL,
     ITALIC,
+    UNDERLINE,
+    DIM,
+    REVERSE,
+    STRIKETHROUGH,
 }
 
+// Any number of styles, `style: bold underline` or `style: [bold, underline]`
 #[derive(Debug)]
 pub struct Style {
-    pub style_name: StyleName,
+    pub style_names: Vec<StyleName>,
 }
 
 impl Style {
     fn default() -> Self {
         Self {
-            style_name: StyleName::NORMAL,
+            style_names: vec![StyleName::NORMAL],
+        }
+    }
+
+    fn from_string(style_names: &str) -> Style {
+        Self {
+            style_names: style_names
+                .split(|c: char| c.is_whitespace() || c == ',' || c == '+')
+                .filter(|name| !name.is_empty())
+                .map(Style::style_name)
+                .collect(),
+        }
+    }
+
+    fn from_yaml(yaml: &Yaml) -> Option<Style> {
+        match yaml {
+            Yaml::String(style_names) => Some(Style::from_string(style_names)),
+            Yaml::Array(style_names) => Some(Self {
+                style_names: style_names
+                    .iter()
+                    .filter_map(Yaml::as_str)
+                    .map(Style::style_name)
+                    .collect(),
+            }),
+            _ => None,
         }
     }
 
-    fn from_string(style_name: &str) -> Style {
+    fn style_name(style_name: &str) -> StyleName {
+        Style::parse_name(style_name).unwrap_or(StyleName::NORMAL)
+    }
+
+    // `None` for unknown names
+    fn parse_name(style_name: &str) -> Option<StyleName> {
         let style = match style_name.to_lowercase().as_str() {
             "bold" => StyleName::BOLD,
             "normal" => StyleName::NORMAL,
             "italic" => StyleName::ITALIC,
-            _ => StyleName::NORMAL,
+            "underline" => StyleName::UNDERLINE,
+            "dim" => StyleName::DIM,
+            "reverse" => StyleName::REVERSE,
+            "strikethrough" => StyleName::STRIKETHROUGH,
+            _ => return None,
         };
 
-        Self { style_name: style }
+        Some(style)
     }
 }
 
-#[derive(Debug)]
+#[derive(Debug, Clone)]
 pub struct Color {
     pub color_name: ColorName,
 }
@@ -61,16 +113,142 @@ impl Color {
     }
 
     fn from_string(color_name: &str) -> Self {
-        let color = match color_name.to_lowercase().as_str() {
+        Color::parse(color_name).unwrap_or_else(Color::default)
+    }
+
+    // `None` for unknown colors
+    fn parse(color_name: &str) -> Option<Self> {
+        let color_name = color_name.trim().to_lowercase();
+
+        let color = match color_name.as_str() {
             "red" => ColorName::RED,
             "blue" => ColorName::BLUE,
             "yellow" => ColorName::YELLOW,
             "black" => ColorName::BLACK,
             "white" => ColorName::WHITE,
             "green" => ColorName::GREEN,
-            _ => ColorName::WHITE,
+            "magenta" => ColorName::MAGENTA,
+            "cyan" => ColorName::CYAN,
+            name => Color::bright_color(name)
+                .or_else(|| name.parse().ok().map(ColorName::FIXED))
+                .or_else(|| Color::hex_color(name))?,
+        };
+
+        Some(Self { color_name: color })
+    }
+
+    // Also takes palette indices written as numbers
+    fn from_yaml(yaml: &Yaml) -> Option<Self> {
+        match yaml {
+            Yaml::String(color_name) => Some(Color::from_string(color_name)),
+            Yaml::Integer(index) => Some(Self {
+                color_name: ColorName::FIXED((*index).clamp(0, 255) as u8),
+            }),
+            _ => None,
+        }
+    }
+
+    // `bright_red` or `light_red`, `gray` is bright black
+    fn bright_color(color_name: &str) -> Option<ColorName> {
+        if color_name == "gray" || color_name == "grey" {
+            return Some(ColorName::FIXED(8));
+        }
+
+        let index = match color_name
+            .strip_prefix("bright_")
+            .or_else(|| color_name.strip_prefix("light_"))?
+        {
+            "black" => 8,
+            "red" => 9,
+            "green" => 10,
+            "yellow" => 11,
+            "blue" => 12,
+            "magenta" => 13,
+            "cyan" => 14,
+            "white" => 15,
+            _ => return None,
+        };
+
+        Some(ColorName::FIXED(index))
+    }
+
+    // `#ff8800` or `#f80`
+    fn hex_color(color_name: &str) -> Option<ColorName> {
+        let hex = color_name.strip_prefix('#')?;
+        if !hex.chars().all(|c| c.is_ascii_hexdigit()) {
+            return None;
+        }
+
+        let channel = |i: usize, len: usize| {
+            let value = u8::from_str_radix(&hex[i * len..(i + 1) * len], 16).ok()?;
+            Some(if len == 1 { value * 17 } else { value })
+        };
+
+        let len = match hex.len() {
+            3 => 1,
+            6 => 2,
+            _ => return None,
         };
 
-        Self { color_name: color }
+        Some(ColorName::RGB(
+            channel(0, len)?,
+            channel(1, len)?,
+            channel(2, len)?,
+        ))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use yaml_rust::YamlLoader;
+
+    #[test]
+    fn test_color_from_string() {
+        assert_eq!(Color::from_string("Black").color_name, ColorName::BLACK);
+        assert_eq!(Color::from_string("cyan").color_name, ColorName::CYAN);
+        assert_eq!(
+            Color::from_string("bright_red").color_name,
+            ColorName::FIXED(9)
+        );
+        assert_eq!(Color::from_string("gray").color_name, ColorName::FIXED(8));
+        assert_eq!(Color::from_string("208").color_name, ColorName::FIXED(208));
+        assert_eq!(
+            Color::from_string("#ff8800").color_name,
+            ColorName::RGB(255, 136, 0)
+        );
+        assert_eq!(
+            Color::from_string("#F80").color_name,
+            ColorName::RGB(255, 136, 0)
+        );
+        assert_eq!(Color::from_string("#ff88").color_name, ColorName::WHITE);
+        assert_eq!(Color::from_string("purple").color_name, ColorName::WHITE);
+    }
+
+    #[test]
+    fn test_from_yaml() {
+        let yaml = YamlLoader::load_from_str(
+            "color: 208\nbackground: '#ff8800'\nstyle: [bold, underline]\nother: dim reverse\n",
+        )
+        .unwrap();
+        let yaml = &yaml[0];
+
+        assert_eq!(
+            Color::from_yaml(&yaml["color"]).unwrap().color_name,
+            ColorName::FIXED(208)
+        );
+        assert_eq!(
+            Color::from_yaml(&yaml["background"]).unwrap().color_name,
+            ColorName::RGB(255, 136, 0)
+        );
+        assert!(Color::from_yaml(&yaml["missing"]).is_none());
+        assert_eq!(
+            Style::from_yaml(&yaml["style"]).unwrap().style_names,
+            vec![StyleName::BOLD, StyleName::UNDERLINE]
+        );
+        assert_eq!(
+            Style::from_yaml(&yaml["other"]).unwrap().style_names,
+            vec![StyleName::DIM, StyleName::REVERSE]
+        );
     }
 }
@@ -0,0 +1,301 @@
+//
+// Prompt templates, an alternative to `component_order`:
+//
+//      template: "{cwd:yellow,bold} {git:[%s]} {status?✘ } {symbol} "
+//
+// Text outside of braces is shown as it is, `{{` and `}}` are literal
+// braces. A placeholder names a component and optionally takes options
+// after `:` and a condition after `?`:
+//
+//      {git:green,bold,[%s]}   color, background (`bg=blue`), styles and a
+//                              format, `%s` is replaced by the content
+//      {cwd:max=30}            content truncated to 30 columns
+//      {jobs:pad=3}            padded to 3 columns, `lpad` aligns right
+//      {status?✘ }             shown only if the content is neither empty
+//                              nor `0`, `%s` is replaced by the content
+//
+// The template is compiled once into the components of the command bar,
+// see `command_bar::components_from_template`.
+//
+
+use std::fmt;
+
+use super::{Color, Style, StyleName};
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Padding {
+    // Spaces after the content up to the width
+    Right(usize),
+    // Spaces in front of the content up to the width
+    Left(usize),
+}
+
+#[derive(Debug)]
+pub enum TemplateOption {
+    Color(Color),
+    Background(Color),
+    Style(StyleName),
+    Format(String),
+    MaxWidth(usize),
+    Padding(Padding),
+}
+
+#[derive(Debug)]
+pub struct Placeholder {
+    pub name: String,
+    // Of the name in the template, in characters
+    pub position: usize,
+    pub options: Vec<TemplateOption>,
+    pub condition: Option<String>,
+}
+
+#[derive(Debug)]
+pub enum TemplateItem {
+    Text(String),
+    Placeholder(Placeholder),
+}
+
+#[derive(Debug, PartialEq)]
+pub struct TemplateError {
+    // In characters, starting at 0
+    pub position: usize,
+    pub message: String,
+    pub template: String,
+}
+
+impl TemplateError {
+    pub fn new(template: &str, position: usize, message: String) -> Self {
+        Self {
+            position,
+            message,
+            template: String::from(template),
+        }
+    }
+}
+
+// The template with a marker below the position
+impl fmt::Display for TemplateError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "Template Error: {} at column {}\n    {}\n    {}^",
+            self.message,
+            self.position + 1,
+            self.template,
+            " ".repeat(self.position)
+        )
+    }
+}
+
+pub fn parse_template(template: &str) -> Result<Vec<TemplateItem>, TemplateError> {
+    let chars: Vec<char> = template.chars().collect();
+    let error = |position: usize, message: &str| {
+        Err(TemplateError::new(
+            template,
+            position,
+            String::from(message),
+        ))
+    };
+
+    let mut items = Vec::new();
+    let mut text = String::new();
+    let mut i = 0;
+
+    while i < chars.len() {
+        match chars[i] {
+            '{' if chars.get(i + 1) == Some(&'{') => {
+                text.push('{');
+                i += 2;
+            }
+            '}' if chars.get(i + 1) == Some(&'}') => {
+                text.push('}');
+                i += 2;
+            }
+            '}' => return error(i, "unmatched `}`"),
+            '{' => {
+                let end = match chars[i + 1..].iter().position(|c| *c == '}') {
+                    Some(length) => i + 1 + length,
+                    None => return error(i, "unclosed `{`"),
+                };
+                if let Some(nested) = chars[i + 1..end].iter().position(|c| *c == '{') {
+                    return error(i + 1 + nested, "unexpected `{` in a placeholder");
+                }
+
+                if !text.is_empty() {
+                    items.push(TemplateItem::Text(text.split_off(0)));
+                }
+                items.push(TemplateItem::Placeholder(parse_placeholder(
+                    template,
+                    &chars[i + 1..end],
+                    i + 1,
+                )?));
+                i = end + 1;
+            }
+            c => {
+                text.push(c);
+                i += 1;
+            }
+        }
+    }
+
+    if !text.is_empty() {
+        items.push(TemplateItem::Text(text));
+    }
+
+    Ok(items)
+}
+
+// `name:options?condition` starting at `position`
+fn parse_placeholder(
+    template: &str,
+    chars: &[char],
+    position: usize,
+) -> Result<Placeholder, TemplateError> {
+    let (body, condition) = match chars.iter().position(|c| *c == '?') {
+        Some(i) => (&chars[..i], Some(chars[i + 1..].iter().collect())),
+        None => (chars, None),
+    };
+
+    let (name, options) = match body.iter().position(|c| *c == ':') {
+        Some(i) => (&body[..i], Some((&body[i + 1..], position + i + 1))),
+        None => (body, None),
+    };
+
+    let name: String = name.iter().collect();
+    if name.trim().is_empty() {
+        return Err(TemplateError::new(
+            template,
+            position,
+            String::from("missing component name"),
+        ));
+    }
+
+    let mut placeholder = Placeholder {
+        name: String::from(name.trim()),
+        position: position + name.chars().count() - name.trim_start().chars().count(),
+        options: Vec::new(),
+        condition,
+    };
+
+    if let Some((options, mut option_position)) = options {
+        for option in options.split(|c| *c == ',') {
+            let text: String = option.iter().collect();
+            let offset = text.len() - text.trim_start().len();
+
+            placeholder
+                .options
+                .push(parse_option(text.trim()).ok_or_else(|| {
+                    TemplateError::new(
+                        template,
+                        option_position + text[..offset].chars().count(),
+                        format!("unknown option `{}`", text.trim()),
+                    )
+                })?);
+            option_position += option.len() + 1;
+        }
+    }
+
+    Ok(placeholder)
+}
+
+fn parse_option(option: &str) -> Option<TemplateOption> {
+    if option.contains("%s") {
+        return Some(TemplateOption::Format(String::from(option)));
+    }
+
+    if let Some((key, value)) = option.split_once('=') {
+        let width = || value.trim().parse::<usize>().ok();
+
+        return match key.trim() {
+            "bg" => Color::parse(value).map(TemplateOption::Background),
+            "max" => width().map(TemplateOption::MaxWidth),
+            "pad" => width().map(|width| TemplateOption::Padding(Padding::Right(width))),
+            "lpad" => width().map(|width| TemplateOption::Padding(Padding::Left(width))),
+            _ => None,
+        };
+    }
+
+    Style::parse_name(option)
+        .map(TemplateOption::Style)
+        .or_else(|| Color::parse(option).map(TemplateOption::Color))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::ColorName;
+
+    fn placeholder(item: &TemplateItem) -> &Placeholder {
+        match item {
+            TemplateItem::Placeholder(placeholder) => placeholder,
+            TemplateItem::Text(text) => panic!("expected a placeholder, got {:?}", text),
+        }
+    }
+
+    #[test]
+    fn test_parse_template() {
+        let items =
+            parse_template("{cwd:yellow,bold} {git:[%s]} {status?✘ } {{{symbol}}} ").unwrap();
+
+        assert_eq!(items.len(), 8);
+
+        let cwd = placeholder(&items[0]);
+        assert_eq!((cwd.name.as_str(), cwd.position), ("cwd", 1));
+        assert!(matches!(
+            cwd.options.as_slice(),
+            [
+                TemplateOption::Color(Color {
+                    color_name: ColorName::YELLOW
+                }),
+                TemplateOption::Style(StyleName::BOLD)
+            ]
+        ));
+
+        assert!(matches!(
+            placeholder(&items[2]).options.as_slice(),
+            [TemplateOption::Format(format)] if format == "[%s]"
+        ));
+        assert_eq!(placeholder(&items[4]).condition.as_deref(), Some("✘ "));
+        assert!(matches!(&items[5], TemplateItem::Text(text) if text == " {"));
+        assert_eq!(placeholder(&items[6]).name, "symbol");
+        assert!(matches!(&items[7], TemplateItem::Text(text) if text == "} "));
+    }
+
+    #[test]
+    fn test_parse_options() {
+        let items = parse_template("{cwd: max=20, bg=#303030 ,lpad=4,bright_blue}").unwrap();
+
+        assert!(matches!(
+            placeholder(&items[0]).options.as_slice(),
+            [
+                TemplateOption::MaxWidth(20),
+                TemplateOption::Background(Color {
+                    color_name: ColorName::RGB(48, 48, 48)
+                }),
+                TemplateOption::Padding(Padding::Left(4)),
+                TemplateOption::Color(Color {
+                    color_name: ColorName::FIXED(12)
+                })
+            ]
+        ));
+    }
+
+    #[test]
+    fn test_parse_errors() {
+        let position = |template: &str| parse_template(template).unwrap_err().position;
+
+        assert_eq!(position("λ {cwd"), 2);
+        assert_eq!(position("{cwd}}x}"), 5);
+        assert_eq!(position("{cwd {git}}"), 5);
+        assert_eq!(position("{ }"), 1);
+        assert_eq!(position("{cwd:bold, blod}"), 11);
+        assert_eq!(position("{cwd:max=x}"), 5);
+
+        let error = parse_template("{cwd:blod}").unwrap_err();
+        assert_eq!(error.message, "unknown option `blod`");
+        assert_eq!(
+            error.to_string(),
+            "Template Error: unknown option `blod` at column 6\n    {cwd:blod}\n         ^"
+        );
+    }
+}
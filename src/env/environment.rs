@@ -19,6 +19,7 @@
 //
 
 use log::info;
+use regex::{Captures, Regex};
 use std::collections::HashMap;
 use std::env::var;
 use std::fs::canonicalize;
@@ -137,6 +138,32 @@ impl EnvManager {
         self.env_vars.contains_key(command_name)
     }
 
+    pub fn command_names(&self) -> impl Iterator<Item = &str> {
+        self.env_vars.keys().map(|name| name.as_str())
+    }
+
+    // Replaces `$NAME` and `${NAME}` with the value of the environment
+    // variable, unset variables expand to an empty string
+    pub fn expand_variables(value: &str) -> String {
+        lazy_static! {
+            static ref VARIABLE_REGEX: Regex = Regex::new(
+                r"\$(?:\{(?P<braced>[A-Za-z_][A-Za-z0-9_]*)\}|(?P<name>[A-Za-z_][A-Za-z0-9_]*))"
+            )
+            .unwrap();
+        }
+
+        VARIABLE_REGEX
+            .replace_all(value, |caps: &Captures| {
+                let name = caps
+                    .name("braced")
+                    .or_else(|| caps.name("name"))
+                    .unwrap()
+                    .as_str();
+                var(name).unwrap_or_default()
+            })
+            .into_owned()
+    }
+
     pub fn canonicalize_path(rel_path: &str) -> String {
         canonicalize(rel_path)
             .unwrap()
@@ -159,4 +186,15 @@ mod tests {
 
     #[test]
     fn test_collect_all_binaries_of_path() {}
+
+    #[test]
+    fn test_expand_variables() {
+        std::env::set_var("CIVA_EDITOR", "vim");
+        std::env::remove_var("CIVA_UNSET");
+
+        assert_eq!(EnvManager::expand_variables("$CIVA_EDITOR"), "vim");
+        assert_eq!(EnvManager::expand_variables("${CIVA_EDITOR} -R"), "vim -R");
+        assert_eq!(EnvManager::expand_variables("a$CIVA_UNSET"), "a");
+        assert_eq!(EnvManager::expand_variables("no vars"), "no vars");
+    }
 }
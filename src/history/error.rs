@@ -0,0 +1,13 @@
+use std::fmt;
+
+#[derive(Debug, PartialEq)]
+pub struct HistoryError {
+    pub kind: String,
+    pub message: String,
+}
+
+impl fmt::Display for HistoryError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "kind: {}, msg: {}", self.kind, self.message)
+    }
+}
@@ -0,0 +1,547 @@
+//
+// csh/bash style history expansion, applied to the input line before it is parsed
+//
+// Event designators select a previous command:
+//
+//      !!          the last command
+//      !n          the n-th command, counting from the oldest
+//      !-n         the n-th last command
+//      !str        the last command starting with str
+//      !?str?      the last command containing str
+//
+// Word designators select words of it, either after a `:` or directly for
+// `!$`, `!^` and `!*`:
+//
+//      0           the command name
+//      n, n-m      the n-th word, a range of words (`-m` starts at 0)
+//      ^, $        the first argument, the last word
+//      *, n*       all arguments, the words from n on
+//
+// Modifiers follow after another `:`:
+//
+//      h, t        remove the last path component, keep only the last one
+//      r, e        remove the extension, keep only the extension
+//      s/a/b/      replace the first `a` by `b`, `gs/a/b/` replaces all
+//      p           print the expanded line without running it
+//
+// `^a^b` at the start of the line is short for `!!:s/a/b/`.
+//
+// A `!` followed by whitespace, `=`, `(`, a quote, a shell operator (`;`,
+// `|`, `&`, `<`, `>`) or the end of the line is kept as is, so the sudo
+// suffix (`make install !`) and `echo "hi!"` are never taken for an
+// expansion.
+// Nothing is expanded in single quotes or after a backslash.
+//
+// The commands of the current session are taken as they were run rather than
+// as they were recorded, so redacted and unrecorded lines expand to what was
+// typed, see `expansion_events`.
+//
+
+use std::path::Path;
+
+use super::error::HistoryError;
+use super::HistoryEntry;
+
+#[derive(Debug, PartialEq)]
+pub struct Expansion {
+    pub line: String,
+    // `:p` modifier, the line is only printed
+    pub print_only: bool,
+}
+
+fn expansion_error(message: String) -> HistoryError {
+    HistoryError {
+        kind: String::from("history expansion"),
+        message,
+    }
+}
+
+// Commands to expand against, oldest first: the recorded ones of other
+// sessions followed by the lines this session ran
+pub fn expansion_events<'a>(
+    entries: &'a [HistoryEntry],
+    session_id: &str,
+    session_lines: &'a [String],
+) -> Vec<&'a str> {
+    entries
+        .iter()
+        .filter(|entry| entry.session_id != session_id)
+        .map(|entry| entry.command.as_str())
+        .chain(session_lines.iter().map(String::as_str))
+        .collect()
+}
+
+// `history` holds the previous commands, oldest first. Returns `None` if
+// the line contains nothing to expand.
+pub fn expand_history(line: &str, history: &[&str]) -> Result<Option<Expansion>, HistoryError> {
+    if line.starts_with('^') {
+        return quick_substitution(line, history).map(Some);
+    }
+
+    if !line.contains('!') {
+        return Ok(None);
+    }
+
+    let mut expanded = String::with_capacity(line.len());
+    let mut print_only = false;
+    let mut changed = false;
+    let mut in_single_quotes = false;
+    let mut i = 0;
+
+    while i < line.len() {
+        let rest = &line[i..];
+        let c = rest.chars().next().unwrap();
+
+        if c == '\'' {
+            in_single_quotes = !in_single_quotes;
+        } else if c == '\\' && rest[1..].starts_with('!') && !in_single_quotes {
+            expanded.push('!');
+            changed = true;
+            i += 2;
+            continue;
+        } else if c == '!' && !in_single_quotes && starts_designator(&rest[1..]) {
+            let (text, len, print) = expand_designator(&rest[1..], history)?;
+            expanded.push_str(&text);
+            print_only |= print;
+            changed = true;
+            i += 1 + len;
+            continue;
+        }
+
+        expanded.push(c);
+        i += c.len_utf8();
+    }
+
+    if !changed {
+        return Ok(None);
+    }
+
+    Ok(Some(Expansion {
+        line: expanded,
+        print_only,
+    }))
+}
+
+fn starts_designator(rest: &str) -> bool {
+    match rest.chars().next() {
+        None => false,
+        Some(c) => !(c.is_whitespace() || "=(;|&<>\"'".contains(c)),
+    }
+}
+
+// `^old^new[^]`, the rest of the line after the last `^` is appended
+fn quick_substitution(line: &str, history: &[&str]) -> Result<Expansion, HistoryError> {
+    let mut parts = line[1..].splitn(3, '^');
+    let old = parts.next().unwrap_or_default();
+    let new = parts.next().unwrap_or_default();
+    let suffix = parts.next().unwrap_or_default();
+
+    let previous = history
+        .last()
+        .ok_or_else(|| expansion_error(String::from("!!: event not found")))?;
+
+    let mut expanded = substitute(previous, old, new, false)?;
+    expanded.push_str(suffix);
+
+    Ok(Expansion {
+        line: expanded,
+        print_only: false,
+    })
+}
+
+// Expands the designator following a `!`, returns the text, the length of
+// the designator and whether the line should only be printed
+fn expand_designator(
+    designator: &str,
+    history: &[&str],
+) -> Result<(String, usize, bool), HistoryError> {
+    let (event, mut len) = select_event(designator, history)?;
+    let words = split_words(event);
+
+    // Word designator, `!$` and `!:2` alike
+    let rest = &designator[len..];
+    let word_spec = if rest.starts_with(|c| c == '^' || c == '$' || c == '*') {
+        Some(&rest[..1])
+    } else if rest.starts_with(':') && rest[1..].starts_with(is_word_spec_start) {
+        let spec_len = rest[1..]
+            .find(|c: char| !(c.is_ascii_digit() || "^$*-".contains(c)))
+            .unwrap_or(rest.len() - 1);
+        len += 1;
+        Some(&rest[1..=spec_len])
+    } else {
+        None
+    };
+
+    let mut text = match word_spec {
+        Some(spec) => {
+            len += spec.len();
+            select_words(&words, spec)?
+        }
+        None => String::from(event),
+    };
+
+    let mut print_only = false;
+    while let Some((modifier_len, print)) = apply_modifier(&designator[len..], &mut text)? {
+        len += modifier_len;
+        print_only |= print;
+    }
+
+    Ok((text, len, print_only))
+}
+
+fn is_word_spec_start(c: char) -> bool {
+    c.is_ascii_digit() || "^$*-".contains(c)
+}
+
+// Returns the selected command and the length of the event designator
+fn select_event<'a>(
+    designator: &str,
+    history: &[&'a str],
+) -> Result<(&'a str, usize), HistoryError> {
+    let not_found = |event: &str| expansion_error(format!("!{}: event not found", event));
+
+    // `!$`, `!*`, `!:1`, ... refer to the last command
+    if designator.starts_with('!') {
+        return history
+            .last()
+            .map(|e| (*e, 1))
+            .ok_or_else(|| not_found("!"));
+    }
+    if designator.starts_with(|c| c == '^' || c == '$' || c == '*' || c == ':') {
+        return history
+            .last()
+            .map(|e| (*e, 0))
+            .ok_or_else(|| not_found("!"));
+    }
+
+    let offset = usize::from(designator.starts_with('-'));
+    let digits = designator[offset..]
+        .find(|c: char| !c.is_ascii_digit())
+        .unwrap_or(designator.len() - offset);
+    if digits > 0 {
+        let event = &designator[..offset + digits];
+        let n: usize = event[offset..].parse().map_err(|_| not_found(event))?;
+
+        let index = if offset == 1 {
+            history.len().checked_sub(n)
+        } else {
+            n.checked_sub(1)
+        };
+
+        return index
+            .and_then(|index| history.get(index))
+            .map(|e| (*e, offset + digits))
+            .ok_or_else(|| not_found(event));
+    }
+
+    if let Some(search) = designator.strip_prefix('?') {
+        let (needle, len) = match search.find('?') {
+            Some(end) => (&search[..end], end + 2),
+            None => (search, designator.len()),
+        };
+
+        return history
+            .iter()
+            .rev()
+            .find(|e| e.contains(needle))
+            .map(|e| (*e, len))
+            .ok_or_else(|| not_found(&designator[..len]));
+    }
+
+    let prefix_len = designator
+        .find(|c: char| c.is_whitespace() || c == ':' || ";&|<>\"'".contains(c))
+        .unwrap_or(designator.len());
+    let prefix = &designator[..prefix_len];
+    if prefix.is_empty() {
+        return Err(not_found(""));
+    }
+
+    history
+        .iter()
+        .rev()
+        .find(|e| e.starts_with(prefix))
+        .map(|e| (*e, prefix_len))
+        .ok_or_else(|| not_found(prefix))
+}
+
+// Words of a command, quoted strings are a single word
+fn split_words(command: &str) -> Vec<&str> {
+    let mut words = Vec::new();
+    let mut quote: Option<char> = None;
+    let mut start: Option<usize> = None;
+
+    for (i, c) in command.char_indices() {
+        match quote {
+            Some(q) if c == q => quote = None,
+            Some(_) => {}
+            None if c.is_whitespace() => {
+                if let Some(s) = start.take() {
+                    words.push(&command[s..i]);
+                }
+                continue;
+            }
+            None if c == '"' || c == '\'' => quote = Some(c),
+            None => {}
+        }
+        start.get_or_insert(i);
+    }
+
+    if let Some(s) = start {
+        words.push(&command[s..]);
+    }
+
+    words
+}
+
+fn select_words(words: &[&str], spec: &str) -> Result<String, HistoryError> {
+    let bad_spec = || expansion_error(format!("{}: bad word specifier", spec));
+    let last = words.len().saturating_sub(1);
+
+    let parse_index = |index: &str| -> Result<usize, HistoryError> {
+        match index {
+            "^" => Ok(1),
+            "$" => Ok(last),
+            _ => index.parse().map_err(|_| bad_spec()),
+        }
+    };
+
+    let (first, end) = if spec == "*" {
+        // All arguments, nothing if there are none
+        if words.len() < 2 {
+            return Ok(String::new());
+        }
+        (1, last)
+    } else if let Some(from) = spec.strip_suffix('*') {
+        (parse_index(from)?, last)
+    } else if let Some(dash) = spec[1..].find('-').map(|i| i + 1).or_else(|| {
+        if spec.starts_with('-') {
+            Some(0)
+        } else {
+            None
+        }
+    }) {
+        let from = if dash == 0 {
+            0
+        } else {
+            parse_index(&spec[..dash])?
+        };
+        let to = match &spec[dash + 1..] {
+            // `n-` leaves out the last word
+            "" => last.checked_sub(1).ok_or_else(bad_spec)?,
+            to => parse_index(to)?,
+        };
+        (from, to)
+    } else {
+        let index = parse_index(spec)?;
+        (index, index)
+    };
+
+    if first > end || end >= words.len() {
+        return Err(bad_spec());
+    }
+
+    Ok(words[first..=end].join(" "))
+}
+
+// Applies the modifier at the start of `rest` (`:h`, `:s/a/b/`, ...) to the
+// text, returns its length and whether it is `:p`
+fn apply_modifier(rest: &str, text: &mut String) -> Result<Option<(usize, bool)>, HistoryError> {
+    let modifier = match rest.strip_prefix(':') {
+        Some(modifier) => modifier,
+        None => return Ok(None),
+    };
+
+    let path = Path::new(text.as_str());
+    let (replacement, len, print) = match modifier.chars().next() {
+        Some('h') => (
+            path.parent()
+                .map(|parent| parent.to_string_lossy().into_owned())
+                .filter(|parent| !parent.is_empty())
+                .unwrap_or_else(|| text.clone()),
+            1,
+            false,
+        ),
+        Some('t') => (
+            path.file_name()
+                .map(|name| name.to_string_lossy().into_owned())
+                .unwrap_or_else(|| text.clone()),
+            1,
+            false,
+        ),
+        Some('r') => (
+            match (path.extension(), text.rfind('.')) {
+                (Some(_), Some(dot)) => String::from(&text[..dot]),
+                _ => text.clone(),
+            },
+            1,
+            false,
+        ),
+        Some('e') => (
+            path.extension()
+                .map(|extension| format!(".{}", extension.to_string_lossy()))
+                .unwrap_or_default(),
+            1,
+            false,
+        ),
+        Some('p') => (text.clone(), 1, true),
+        Some('s') | Some('g') => {
+            let global = modifier.starts_with('g');
+            let body = if global {
+                match modifier[1..].strip_prefix('s') {
+                    Some(body) => body,
+                    None => return Ok(None),
+                }
+            } else {
+                &modifier[1..]
+            };
+
+            let delimiter = match body.chars().next() {
+                Some(delimiter) => delimiter,
+                None => return Ok(None),
+            };
+            let mut parts = body[delimiter.len_utf8()..].splitn(3, delimiter);
+            let old = parts.next().unwrap_or_default();
+            let new = parts.next().unwrap_or_default();
+
+            // Up to and including the closing delimiter, which may be left out
+            // at the end of the line
+            let mut body_len = delimiter.len_utf8() + old.len();
+            if body.len() > body_len {
+                body_len += delimiter.len_utf8() + new.len();
+                if body.len() > body_len {
+                    body_len += delimiter.len_utf8();
+                }
+            }
+
+            let prefix_len = if global { 2 } else { 1 };
+            (
+                substitute(text, old, new, global)?,
+                prefix_len + body_len,
+                false,
+            )
+        }
+        _ => return Ok(None),
+    };
+
+    *text = replacement;
+    Ok(Some((1 + len, print)))
+}
+
+fn substitute(text: &str, old: &str, new: &str, global: bool) -> Result<String, HistoryError> {
+    if old.is_empty() || !text.contains(old) {
+        return Err(expansion_error(format!("{}: substitution failed", old)));
+    }
+
+    if global {
+        Ok(text.replace(old, new))
+    } else {
+        Ok(text.replacen(old, new, 1))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    static HISTORY: &[&str] = &[
+        "cd /usr/local/src",
+        "vim src/main.rs README.md",
+        "git commit -m \"first try\"",
+        "cargo build --release",
+    ];
+
+    fn expand(line: &str) -> String {
+        expand_history(line, HISTORY).unwrap().unwrap().line
+    }
+
+    #[test]
+    fn test_event_designators() {
+        assert_eq!(expand("sudo !!"), "sudo cargo build --release");
+        assert_eq!(expand("!-2"), "git commit -m \"first try\"");
+        assert_eq!(expand("!1"), "cd /usr/local/src");
+        assert_eq!(expand("!vim"), "vim src/main.rs README.md");
+        assert_eq!(expand("!?first?"), "git commit -m \"first try\"");
+        assert_eq!(
+            expand("!?commit? --amend"),
+            "git commit -m \"first try\" --amend"
+        );
+    }
+
+    #[test]
+    fn test_word_designators() {
+        assert_eq!(expand("ls !$"), "ls --release");
+        assert_eq!(expand("echo !*"), "echo build --release");
+        assert_eq!(expand("echo !^"), "echo build");
+        assert_eq!(expand("echo !vim:1"), "echo src/main.rs");
+        assert_eq!(expand("echo !vim:0-1"), "echo vim src/main.rs");
+        assert_eq!(expand("echo !git:$"), "echo \"first try\"");
+        assert_eq!(expand("echo !-3:2*"), "echo README.md");
+    }
+
+    #[test]
+    fn test_modifiers() {
+        assert_eq!(expand("cd !vim:1:h"), "cd src");
+        assert_eq!(expand("echo !vim:1:t"), "echo main.rs");
+        assert_eq!(expand("echo !vim:1:r"), "echo src/main");
+        assert_eq!(expand("echo !vim:1:e"), "echo .rs");
+        assert_eq!(expand("!!:s/build/test/"), "cargo test --release");
+        assert_eq!(expand("!!:s/build/test"), "cargo test --release");
+        assert_eq!(expand("!cd:gs/l/L/"), "cd /usr/LocaL/src");
+        assert_eq!(expand("^build^check"), "cargo check --release");
+        assert_eq!(expand("^build^check^ -q"), "cargo check --release -q");
+
+        let expansion = expand_history("!!:p", HISTORY).unwrap().unwrap();
+        assert!(expansion.print_only);
+    }
+
+    #[test]
+    fn test_nothing_to_expand() {
+        // The sudo suffix and other lone `!`
+        assert_eq!(expand_history("make install !", HISTORY), Ok(None));
+        assert_eq!(expand_history("test ! -f x", HISTORY), Ok(None));
+        assert_eq!(expand_history("echo hi", HISTORY), Ok(None));
+        assert_eq!(expand_history("echo '!!'", HISTORY), Ok(None));
+        assert_eq!(expand("echo \\!!"), "echo !!");
+
+        // A `!` in front of an operator or a quote
+        assert_eq!(expand_history("echo hi!; ls", HISTORY), Ok(None));
+        assert_eq!(expand_history("echo \"hi!\"", HISTORY), Ok(None));
+        assert_eq!(expand_history("a!|b", HISTORY), Ok(None));
+        assert_eq!(expand_history("a!&& b", HISTORY), Ok(None));
+    }
+
+    #[test]
+    fn test_expansion_events() {
+        let entry = |command: &str, session_id: &str| HistoryEntry {
+            session_id: String::from(session_id),
+            ..HistoryEntry::for_test(command, "/", 0)
+        };
+        let entries = vec![
+            entry("make", "other"),
+            entry("PASSWORD=<redacted> ./deploy", "current"),
+        ];
+        let session_lines = vec![
+            String::from("PASSWORD=x ./deploy"),
+            String::from(" pass show mail"),
+        ];
+
+        let events = expansion_events(&entries, "current", &session_lines);
+        assert_eq!(
+            events,
+            vec!["make", "PASSWORD=x ./deploy", " pass show mail"]
+        );
+        assert_eq!(
+            expand_history("!-2", &events).unwrap().unwrap().line,
+            "PASSWORD=x ./deploy"
+        );
+    }
+
+    #[test]
+    fn test_errors() {
+        assert!(expand_history("!nope", HISTORY).is_err());
+        assert!(expand_history("!42", HISTORY).is_err());
+        assert!(expand_history("!!:9", HISTORY).is_err());
+        assert!(expand_history("^nope^x", HISTORY).is_err());
+        assert!(expand_history("!!", &[]).is_err());
+    }
+}
@@ -0,0 +1,99 @@
+//
+// Export of history entries as a JSON array or as CSV with a header line
+//
+
+use super::HistoryEntry;
+
+#[derive(Debug, PartialEq, Copy, Clone)]
+pub enum ExportFormat {
+    Json,
+    Csv,
+}
+
+impl ExportFormat {
+    pub fn from_str(name: &str) -> Option<Self> {
+        match name {
+            "json" => Some(ExportFormat::Json),
+            "csv" => Some(ExportFormat::Csv),
+            _ => None,
+        }
+    }
+}
+
+static CSV_HEADER: &str = "timestamp,cwd,duration,exit_status,hostname,session_id,command";
+
+pub fn export(entries: &[&HistoryEntry], format: ExportFormat) -> String {
+    match format {
+        ExportFormat::Json => {
+            serde_json::to_string_pretty(entries).expect("History entries are always valid JSON")
+        }
+        ExportFormat::Csv => to_csv(entries),
+    }
+}
+
+fn to_csv(entries: &[&HistoryEntry]) -> String {
+    let mut csv = String::from(CSV_HEADER);
+    csv.push('\n');
+
+    for entry in entries {
+        let fields = [
+            entry.timestamp.to_string(),
+            csv_field(&entry.cwd),
+            entry.duration.to_string(),
+            entry.exit_status.to_string(),
+            csv_field(&entry.hostname),
+            csv_field(&entry.session_id),
+            csv_field(&entry.command),
+        ];
+        csv.push_str(&fields.join(","));
+        csv.push('\n');
+    }
+
+    csv
+}
+
+// Quotes fields containing separators, quotes or line breaks (RFC 4180)
+fn csv_field(value: &str) -> String {
+    if value.contains(|c| c == ',' || c == '"' || c == '\n' || c == '\r') {
+        format!("\"{}\"", value.replace('"', "\"\""))
+    } else {
+        String::from(value)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn entry(command: &str) -> HistoryEntry {
+        HistoryEntry {
+            timestamp: 1614616200,
+            duration: 12,
+            session_id: String::from("1-2"),
+            ..HistoryEntry::for_test(command, "/home/user", 0)
+        }
+    }
+
+    #[test]
+    fn test_export_csv() {
+        let plain = entry("ls -la");
+        let quoted = entry("echo \"a, b\"");
+
+        assert_eq!(
+            export(&[&plain, &quoted], ExportFormat::Csv),
+            "timestamp,cwd,duration,exit_status,hostname,session_id,command\n\
+             1614616200,/home/user,12,0,host,1-2,ls -la\n\
+             1614616200,/home/user,12,0,host,1-2,\"echo \"\"a, b\"\"\"\n"
+        );
+    }
+
+    #[test]
+    fn test_export_json() {
+        let plain = entry("ls -la");
+
+        let json = export(&[&plain], ExportFormat::Json);
+        let parsed: Vec<HistoryEntry> = serde_json::from_str(&json).unwrap();
+
+        assert_eq!(parsed, vec![plain]);
+    }
+}
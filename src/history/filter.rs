@@ -0,0 +1,170 @@
+//
+// Selection of history entries by command, directory, exit status and time
+//
+// Times are either relative to now (`30s`, `15m`, `2h`, `3d`, `1w`) or
+// absolute local times (`2021-03-01`, `2021-03-01 18:30`).
+//
+
+use chrono::{DateTime, Duration, Local, NaiveDate, NaiveDateTime, TimeZone};
+use regex::Regex;
+
+use super::HistoryEntry;
+
+#[derive(Debug, PartialEq, Copy, Clone)]
+pub enum StatusFilter {
+    Code(i32),
+    Success,
+    Failure,
+}
+
+impl StatusFilter {
+    // `ok`, `failed` or an exit code
+    pub fn from_str(value: &str) -> Option<Self> {
+        match value {
+            "ok" | "success" => Some(StatusFilter::Success),
+            "failed" | "failure" => Some(StatusFilter::Failure),
+            code => code.parse().ok().map(StatusFilter::Code),
+        }
+    }
+
+    fn matches(self, exit_status: i32) -> bool {
+        match self {
+            StatusFilter::Code(code) => exit_status == code,
+            StatusFilter::Success => exit_status == 0,
+            StatusFilter::Failure => exit_status != 0,
+        }
+    }
+}
+
+#[derive(Debug, Default)]
+pub struct HistoryFilter {
+    pub pattern: Option<Regex>,
+    pub cwd: Option<String>,
+    pub status: Option<StatusFilter>,
+    // Unix timestamps, both inclusive
+    pub since: Option<u64>,
+    pub until: Option<u64>,
+}
+
+impl HistoryFilter {
+    pub fn matches(&self, entry: &HistoryEntry) -> bool {
+        self.pattern
+            .as_ref()
+            .map_or(true, |pattern| pattern.is_match(&entry.command))
+            && self.cwd.as_ref().map_or(true, |cwd| entry.cwd == *cwd)
+            && self
+                .status
+                .map_or(true, |status| status.matches(entry.exit_status))
+            && self.since.map_or(true, |since| entry.timestamp >= since)
+            && self.until.map_or(true, |until| entry.timestamp <= until)
+    }
+
+    // Positions of the matching entries
+    pub fn apply(&self, entries: &[HistoryEntry]) -> Vec<usize> {
+        entries
+            .iter()
+            .enumerate()
+            .filter(|(_, entry)| self.matches(entry))
+            .map(|(i, _)| i)
+            .collect()
+    }
+}
+
+pub fn parse_time(value: &str, now: DateTime<Local>) -> Option<u64> {
+    let time = match parse_relative(value) {
+        Some(ago) => now - ago,
+        None => parse_absolute(value)?,
+    };
+
+    if time.timestamp() < 0 {
+        return None;
+    }
+    Some(time.timestamp() as u64)
+}
+
+fn parse_relative(value: &str) -> Option<Duration> {
+    let split = value.find(|c: char| !c.is_ascii_digit())?;
+    let amount: i64 = value[..split].parse().ok()?;
+
+    match &value[split..] {
+        "s" => Some(Duration::seconds(amount)),
+        "m" => Some(Duration::minutes(amount)),
+        "h" => Some(Duration::hours(amount)),
+        "d" => Some(Duration::days(amount)),
+        "w" => Some(Duration::weeks(amount)),
+        _ => None,
+    }
+}
+
+fn parse_absolute(value: &str) -> Option<DateTime<Local>> {
+    let naive = NaiveDateTime::parse_from_str(value, "%Y-%m-%d %H:%M")
+        .or_else(|_| NaiveDateTime::parse_from_str(value, "%Y-%m-%dT%H:%M"))
+        .ok()
+        .or_else(|| {
+            NaiveDate::parse_from_str(value, "%Y-%m-%d")
+                .ok()
+                .and_then(|date| date.and_hms_opt(0, 0, 0))
+        })?;
+
+    Local.from_local_datetime(&naive).earliest()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn entry(command: &str, cwd: &str, exit_status: i32, timestamp: u64) -> HistoryEntry {
+        HistoryEntry {
+            timestamp,
+            ..HistoryEntry::for_test(command, cwd, exit_status)
+        }
+    }
+
+    #[test]
+    fn test_filter_apply() {
+        let entries = vec![
+            entry("cargo build", "/src", 0, 100),
+            entry("cargo test", "/src", 101, 200),
+            entry("ls", "/tmp", 0, 300),
+        ];
+
+        let mut filter = HistoryFilter::default();
+        assert_eq!(filter.apply(&entries), vec![0, 1, 2]);
+
+        filter.pattern = Some(Regex::new("^cargo").unwrap());
+        assert_eq!(filter.apply(&entries), vec![0, 1]);
+
+        filter.status = StatusFilter::from_str("failed");
+        assert_eq!(filter.apply(&entries), vec![1]);
+
+        let filter = HistoryFilter {
+            cwd: Some(String::from("/src")),
+            since: Some(150),
+            ..HistoryFilter::default()
+        };
+        assert_eq!(filter.apply(&entries), vec![1]);
+
+        let filter = HistoryFilter {
+            until: Some(100),
+            ..HistoryFilter::default()
+        };
+        assert_eq!(filter.apply(&entries), vec![0]);
+    }
+
+    #[test]
+    fn test_parse_time() {
+        let now = Local::now();
+        let now_secs = now.timestamp() as u64;
+
+        assert_eq!(parse_time("90s", now), Some(now_secs - 90));
+        assert_eq!(parse_time("2h", now), Some(now_secs - 2 * 3600));
+        assert_eq!(parse_time("1w", now), Some(now_secs - 7 * 24 * 3600));
+
+        let date = parse_time("2021-03-01", now).unwrap();
+        let time = parse_time("2021-03-01 18:30", now).unwrap();
+        assert_eq!(time - date, 18 * 3600 + 30 * 60);
+
+        assert_eq!(parse_time("yesterday", now), None);
+        assert_eq!(parse_time("3x", now), None);
+    }
+}
@@ -0,0 +1,415 @@
+//
+// History store writing one JSON object per line
+//
+// The file is shared by all running shells. Every entry is appended as soon
+// as its command finished, so a crash never loses more than the running
+// command. Writers hold an exclusive `flock` on `history.jsonl.lock`, which
+// also covers rewriting the file when entries are deleted.
+//
+// Entries of other sessions appended after the store was opened only show up
+// after `import`, the store remembers up to where it has read the file.
+//
+
+use std::collections::{HashMap, HashSet};
+use std::fs::{self, File, OpenOptions};
+use std::io::{self, Read, Seek, SeekFrom, Write};
+use std::os::unix::fs::MetadataExt;
+use std::os::unix::io::AsRawFd;
+use std::path::{Path, PathBuf};
+
+use log::warn;
+
+use super::{HistoryEntry, HistoryStore};
+
+pub struct JsonlHistoryStore {
+    path: PathBuf,
+    session_id: String,
+    entries: Vec<HistoryEntry>,
+    // Bytes of the file read so far and the inode they were read from,
+    // a new inode means the file was rewritten by another session
+    offset: u64,
+    inode: u64,
+}
+
+// Exclusive lock, released when dropped
+struct FileLock {
+    file: File,
+}
+
+impl FileLock {
+    fn lock(path: &Path) -> io::Result<Self> {
+        let file = OpenOptions::new().create(true).write(true).open(path)?;
+
+        if unsafe { libc::flock(file.as_raw_fd(), libc::LOCK_EX) } != 0 {
+            return Err(io::Error::last_os_error());
+        }
+
+        Ok(Self { file })
+    }
+}
+
+impl Drop for FileLock {
+    fn drop(&mut self) {
+        unsafe {
+            libc::flock(self.file.as_raw_fd(), libc::LOCK_UN);
+        }
+    }
+}
+
+impl JsonlHistoryStore {
+    // Lines that can not be parsed, e.g. a partially written last line,
+    // are skipped
+    pub fn open(path: &Path, session_id: &str) -> Self {
+        let mut store = Self {
+            path: path.to_path_buf(),
+            session_id: String::from(session_id),
+            entries: Vec::new(),
+            offset: 0,
+            inode: 0,
+        };
+
+        let loaded = store.lock().and_then(|_lock| store.reload());
+        if let Err(err) = loaded {
+            warn!("Could not read history {}: {}", path.display(), err);
+        }
+
+        store
+    }
+
+    fn lock(&self) -> io::Result<FileLock> {
+        FileLock::lock(&self.path.with_extension("jsonl.lock"))
+    }
+
+    // Reads the whole file again
+    fn reload(&mut self) -> io::Result<()> {
+        let (content, inode) = read_file(&self.path)?.unwrap_or_default();
+        let complete = complete_lines(&content);
+
+        self.entries = parse_entries(complete, &self.path);
+        self.offset = complete.len() as u64;
+        self.inode = inode;
+        Ok(())
+    }
+
+    // Replaces the file by `content`, written to a temporary file first so
+    // the history is never left half written
+    fn replace_file(&mut self, content: &str) -> io::Result<()> {
+        let tmp_path = self.path.with_extension("jsonl.tmp");
+        fs::write(&tmp_path, content)?;
+        fs::rename(&tmp_path, &self.path)?;
+
+        self.inode = fs::metadata(&self.path)?.ino();
+        Ok(())
+    }
+}
+
+// Content and inode of the file, `None` if it does not exist yet
+fn read_file(path: &Path) -> io::Result<Option<(String, u64)>> {
+    match File::open(path) {
+        Ok(mut file) => {
+            let mut content = String::new();
+            file.read_to_string(&mut content)?;
+            Ok(Some((content, file.metadata()?.ino())))
+        }
+        Err(err) if err.kind() == io::ErrorKind::NotFound => Ok(None),
+        Err(err) => Err(err),
+    }
+}
+
+// A line without newline is still being written or was cut off by a crash
+fn complete_lines(content: &str) -> &str {
+    &content[..content.rfind('\n').map_or(0, |i| i + 1)]
+}
+
+fn parse_entries(content: &str, path: &Path) -> Vec<HistoryEntry> {
+    content
+        .lines()
+        .filter(|line| !line.trim().is_empty())
+        .filter_map(|line| match serde_json::from_str(line) {
+            Ok(entry) => Some(entry),
+            Err(err) => {
+                warn!("Invalid history entry in {}: {}", path.display(), err);
+                None
+            }
+        })
+        .collect()
+}
+
+impl HistoryStore for JsonlHistoryStore {
+    fn append(&mut self, entry: HistoryEntry) -> io::Result<()> {
+        let mut line = serde_json::to_string(&entry)?;
+        line.push('\n');
+
+        let _lock = self.lock()?;
+        let mut file = OpenOptions::new()
+            .create(true)
+            .read(true)
+            .append(true)
+            .open(&self.path)?;
+
+        // Never continue a line cut off by a crash
+        if file.metadata()?.len() > 0 {
+            let mut last = [0; 1];
+            file.seek(SeekFrom::End(-1))?;
+            file.read_exact(&mut last)?;
+            if last[0] != b'\n' {
+                line.insert(0, '\n');
+            }
+        }
+
+        file.write_all(line.as_bytes())?;
+
+        self.entries.push(entry);
+        Ok(())
+    }
+
+    fn entries(&self) -> &[HistoryEntry] {
+        &self.entries
+    }
+
+    // Entries of other sessions not imported yet stay in the file
+    fn remove(&mut self, indices: &[usize]) -> io::Result<()> {
+        let indices: HashSet<usize> = indices.iter().copied().collect();
+
+        // How often each entry is removed, identical entries are removed from
+        // the start of the file
+        let mut removed: HashMap<HistoryEntry, usize> = HashMap::new();
+        for entry in indices.iter().filter_map(|i| self.entries.get(*i)) {
+            *removed.entry(entry.clone()).or_insert(0) += 1;
+        }
+
+        let _lock = self.lock()?;
+        let (content, inode) = read_file(&self.path)?.unwrap_or_default();
+        // Lines already read keep counting as read, unless the file was replaced
+        let read = if inode == self.inode { self.offset } else { 0 };
+
+        let mut kept = String::with_capacity(content.len());
+        let mut offset = 0;
+        let mut position = 0;
+        for line in complete_lines(&content).split_inclusive('\n') {
+            let start = position;
+            position += line.len() as u64;
+
+            let entry: HistoryEntry = match serde_json::from_str(line) {
+                Ok(entry) => entry,
+                Err(_) => continue,
+            };
+            if let Some(count) = removed.get_mut(&entry).filter(|count| **count > 0) {
+                *count -= 1;
+                continue;
+            }
+
+            kept.push_str(line);
+            if start < read {
+                offset += line.len() as u64;
+            }
+        }
+
+        self.replace_file(&kept)?;
+        self.offset = offset;
+
+        let mut index = 0;
+        self.entries.retain(|_| {
+            index += 1;
+            !indices.contains(&(index - 1))
+        });
+        Ok(())
+    }
+
+    fn clear(&mut self) -> io::Result<()> {
+        let _lock = self.lock()?;
+        self.replace_file("")?;
+
+        self.entries.clear();
+        self.offset = 0;
+        Ok(())
+    }
+
+    fn import(&mut self) -> io::Result<bool> {
+        let _lock = self.lock()?;
+
+        let mut file = match File::open(&self.path) {
+            Ok(file) => file,
+            Err(err) if err.kind() == io::ErrorKind::NotFound => return Ok(false),
+            Err(err) => return Err(err),
+        };
+        let metadata = file.metadata()?;
+
+        // Rewritten by another session, e.g. entries were deleted. A store
+        // opened before the file existed has not read anything yet.
+        let rewritten = self.inode != 0 && metadata.ino() != self.inode;
+        if rewritten || metadata.len() < self.offset {
+            self.reload()?;
+            return Ok(true);
+        }
+        self.inode = metadata.ino();
+
+        let mut content = String::new();
+        file.seek(SeekFrom::Start(self.offset))?;
+        file.read_to_string(&mut content)?;
+
+        let complete = complete_lines(&content);
+        self.offset += complete.len() as u64;
+
+        // Entries of this session are known already
+        let session_id = &self.session_id;
+        let new_entries: Vec<HistoryEntry> = parse_entries(complete, &self.path)
+            .into_iter()
+            .filter(|entry| entry.session_id != *session_id)
+            .collect();
+
+        let changed = !new_entries.is_empty();
+        self.entries.extend(new_entries);
+        Ok(changed)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::TempDir;
+
+    static SESSION: &str = "1-1";
+    static OTHER_SESSION: &str = "2-2";
+
+    // The history file and its lock are removed with the directory
+    fn temp_history() -> (TempDir, PathBuf) {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("history.jsonl");
+        (dir, path)
+    }
+
+    fn session_entry(command: &str, exit_status: i32, session_id: &str) -> HistoryEntry {
+        HistoryEntry {
+            duration: 42,
+            session_id: String::from(session_id),
+            ..HistoryEntry::for_test(command, "/tmp", exit_status)
+        }
+    }
+
+    fn entry(command: &str, exit_status: i32) -> HistoryEntry {
+        session_entry(command, exit_status, SESSION)
+    }
+
+    fn commands(store: &JsonlHistoryStore) -> Vec<&str> {
+        store
+            .entries()
+            .iter()
+            .map(|entry| entry.command.as_str())
+            .collect()
+    }
+
+    #[test]
+    fn test_append_and_reopen() {
+        let (_dir, path) = temp_history();
+
+        let mut store = JsonlHistoryStore::open(&path, SESSION);
+        assert!(store.entries().is_empty());
+
+        store.append(entry("ls -la", 0)).unwrap();
+        store.append(entry("false", 1)).unwrap();
+
+        let reopened = JsonlHistoryStore::open(&path, SESSION);
+        assert_eq!(reopened.entries(), store.entries());
+        assert_eq!(reopened.entries()[1].exit_status, 1);
+        assert_eq!(reopened.entries()[0].duration, 42);
+        assert_eq!(reopened.entries()[0].hostname, "host");
+    }
+
+    #[test]
+    fn test_remove_and_clear() {
+        let (_dir, path) = temp_history();
+
+        let mut store = JsonlHistoryStore::open(&path, SESSION);
+        for command in &["a", "b", "c", "d"] {
+            store.append(entry(command, 0)).unwrap();
+        }
+
+        store.remove(&[1, 3]).unwrap();
+        assert_eq!(commands(&store), vec!["a", "c"]);
+        let reopened = JsonlHistoryStore::open(&path, SESSION);
+        assert_eq!(commands(&reopened), vec!["a", "c"]);
+
+        store.clear().unwrap();
+        let reopened = JsonlHistoryStore::open(&path, SESSION);
+        assert!(reopened.entries().is_empty());
+    }
+
+    #[test]
+    fn test_remove_identical_entries() {
+        let (_dir, path) = temp_history();
+
+        let mut store = JsonlHistoryStore::open(&path, SESSION);
+        for command in &["ls", "ls", "ls", "make"] {
+            store.append(entry(command, 0)).unwrap();
+        }
+
+        store.remove(&[0, 2, 2]).unwrap();
+        assert_eq!(commands(&store), vec!["ls", "make"]);
+        let reopened = JsonlHistoryStore::open(&path, SESSION);
+        assert_eq!(commands(&reopened), vec!["ls", "make"]);
+    }
+
+    #[test]
+    fn test_import_entries_of_other_sessions() {
+        let (_dir, path) = temp_history();
+
+        let mut store = JsonlHistoryStore::open(&path, SESSION);
+        let mut other = JsonlHistoryStore::open(&path, OTHER_SESSION);
+
+        store.append(entry("a", 0)).unwrap();
+        other.append(session_entry("b", 0, OTHER_SESSION)).unwrap();
+        store.append(entry("c", 0)).unwrap();
+
+        assert_eq!(commands(&store), vec!["a", "c"]);
+        assert!(store.import().unwrap());
+        assert_eq!(commands(&store), vec!["a", "c", "b"]);
+        assert!(!store.import().unwrap());
+
+        assert!(other.import().unwrap());
+        assert_eq!(commands(&other), vec!["b", "a", "c"]);
+    }
+
+    #[test]
+    fn test_remove_keeps_entries_of_other_sessions() {
+        let (_dir, path) = temp_history();
+
+        let mut store = JsonlHistoryStore::open(&path, SESSION);
+        store.append(entry("a", 0)).unwrap();
+
+        let mut other = JsonlHistoryStore::open(&path, OTHER_SESSION);
+        other.append(session_entry("b", 0, OTHER_SESSION)).unwrap();
+        assert_eq!(commands(&other), vec!["a", "b"]);
+
+        store.remove(&[0]).unwrap();
+        assert!(store.entries().is_empty());
+        assert!(store.import().unwrap());
+        assert_eq!(commands(&store), vec!["b"]);
+
+        // The file was replaced, the other session reads it again
+        assert!(other.import().unwrap());
+        assert_eq!(commands(&other), vec!["b"]);
+    }
+
+    #[test]
+    fn test_invalid_lines_are_skipped() {
+        let (_dir, path) = temp_history();
+
+        let mut store = JsonlHistoryStore::open(&path, SESSION);
+        store.append(entry("echo a", 0)).unwrap();
+        OpenOptions::new()
+            .append(true)
+            .open(&path)
+            .unwrap()
+            .write_all(b"{\"command\": \"trunc")
+            .unwrap();
+
+        let reopened = JsonlHistoryStore::open(&path, SESSION);
+        assert_eq!(commands(&reopened), vec!["echo a"]);
+
+        // The cut off line does not swallow the next entry
+        store.append(entry("echo b", 0)).unwrap();
+        let reopened = JsonlHistoryStore::open(&path, SESSION);
+        assert_eq!(commands(&reopened), vec!["echo a", "echo b"]);
+    }
+}
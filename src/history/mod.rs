@@ -0,0 +1,130 @@
+//
+// Structured command history
+//
+// Every command read by the shell is recorded together with the context it
+// ran in: when and where it was started, how long it took, its exit status
+// and the host and session it came from. The rustyline history used for
+// Up/Down and hints is populated from the store, with the commands of the
+// current directory and project first (see `scope`).
+//
+// Stores implement `HistoryStore`, the default one keeps an append-only
+// JSONL file in `$XDG_DATA_HOME/civa/history.jsonl` shared by all sessions.
+//
+
+pub mod error;
+pub mod expansion;
+pub mod export;
+pub mod filter;
+pub mod jsonl;
+pub mod privacy;
+pub mod scope;
+
+use std::env;
+use std::io;
+use std::process;
+use std::sync::{Arc, RwLock};
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+use serde::{Deserialize, Serialize};
+
+#[derive(Debug, Clone, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub struct HistoryEntry {
+    pub command: String,
+    // Seconds since the unix epoch when the command was started
+    pub timestamp: u64,
+    pub cwd: String,
+    // Milliseconds
+    pub duration: u64,
+    // Of the last command of the line, of a pipe its last process, 128 + n
+    // if it was killed by signal n
+    pub exit_status: i32,
+    pub hostname: String,
+    pub session_id: String,
+}
+
+impl HistoryEntry {
+    pub fn new(
+        command: &str,
+        cwd: &str,
+        started: SystemTime,
+        duration: Duration,
+        exit_status: i32,
+        session: &Session,
+    ) -> Self {
+        Self {
+            command: String::from(command),
+            timestamp: unix_seconds(started),
+            cwd: String::from(cwd),
+            duration: duration.as_millis() as u64,
+            exit_status,
+            hostname: session.hostname.clone(),
+            session_id: session.id.clone(),
+        }
+    }
+
+    // Entry of tests, started at the epoch on `host` without a session
+    #[cfg(test)]
+    pub fn for_test(command: &str, cwd: &str, exit_status: i32) -> Self {
+        Self {
+            command: String::from(command),
+            timestamp: 0,
+            cwd: String::from(cwd),
+            duration: 0,
+            exit_status,
+            hostname: String::from("host"),
+            session_id: String::new(),
+        }
+    }
+}
+
+// Identifies the running shell in the entries it records
+#[derive(Debug, Clone)]
+pub struct Session {
+    pub id: String,
+    pub hostname: String,
+}
+
+impl Session {
+    pub fn new() -> Self {
+        let hostname = hostname::get()
+            .ok()
+            .and_then(|name| name.into_string().ok())
+            .or_else(|| env::var("HOSTNAME").ok())
+            .unwrap_or_default();
+
+        Self {
+            id: format!("{}-{}", unix_seconds(SystemTime::now()), process::id()),
+            hostname,
+        }
+    }
+}
+
+pub fn unix_seconds(time: SystemTime) -> u64 {
+    time.duration_since(UNIX_EPOCH)
+        .map(|d| d.as_secs())
+        .unwrap_or(0)
+}
+
+// The store is shared with the key handlers of the editor, which live on
+// another thread as far as rustyline is concerned, hence the Arc<RwLock>.
+pub type SharedHistory = Arc<RwLock<dyn HistoryStore>>;
+
+pub trait HistoryStore: Send + Sync {
+    // Records a new entry, it has to be persisted when this returns
+    fn append(&mut self, entry: HistoryEntry) -> io::Result<()>;
+
+    // All entries, oldest first
+    fn entries(&self) -> &[HistoryEntry];
+
+    // Removes the entries at the given positions of `entries()`
+    fn remove(&mut self, indices: &[usize]) -> io::Result<()>;
+
+    fn clear(&mut self) -> io::Result<()> {
+        let indices: Vec<usize> = (0..self.entries().len()).collect();
+        self.remove(&indices)
+    }
+
+    // Adds the entries other sessions recorded since the store was opened
+    // or last imported, returns whether the entries changed
+    fn import(&mut self) -> io::Result<bool>;
+}
@@ -0,0 +1,110 @@
+// Orders the history for Up/Down
+//
+// Commands run in the current directory come first, then the ones run
+// somewhere else in the same project (the enclosing git repository), then
+// everything else. Every command is listed once, at the place of its most
+// relevant run.
+
+use std::collections::HashSet;
+use std::path::Path;
+
+use super::HistoryEntry;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum Scope {
+    Global,
+    Project,
+    Directory,
+}
+
+pub fn scope_of(entry: &HistoryEntry, cwd: &Path, project: Option<&Path>) -> Scope {
+    let dir = Path::new(&entry.cwd);
+
+    if dir == cwd {
+        Scope::Directory
+    } else if project.map_or(false, |project| dir.starts_with(project)) {
+        Scope::Project
+    } else {
+        Scope::Global
+    }
+}
+
+// Positions of the entries in the order they are navigated, oldest first as
+// in the editor history, so the most relevant ones are reached first with Up
+pub fn scoped_order(entries: &[HistoryEntry], cwd: &Path, project: Option<&Path>) -> Vec<usize> {
+    let mut order: Vec<(Scope, usize)> = entries
+        .iter()
+        .enumerate()
+        .map(|(i, entry)| (scope_of(entry, cwd, project), i))
+        .collect();
+    order.sort();
+
+    let mut seen = HashSet::new();
+    let mut indices: Vec<usize> = order
+        .into_iter()
+        .rev()
+        .map(|(_, i)| i)
+        .filter(|i| seen.insert(entries[*i].command.as_str()))
+        .collect();
+    indices.reverse();
+
+    indices
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn entry(command: &str, cwd: &str) -> HistoryEntry {
+        HistoryEntry::for_test(command, cwd, 0)
+    }
+
+    #[test]
+    fn test_scope_of() {
+        let cwd = Path::new("/src/civa/src");
+        let project = Some(Path::new("/src/civa"));
+
+        assert_eq!(
+            scope_of(&entry("ls", "/src/civa/src"), cwd, project),
+            Scope::Directory
+        );
+        assert_eq!(
+            scope_of(&entry("ls", "/src/civa"), cwd, project),
+            Scope::Project
+        );
+        assert_eq!(
+            scope_of(&entry("ls", "/src/civa/plugins"), cwd, project),
+            Scope::Project
+        );
+        assert_eq!(
+            scope_of(&entry("ls", "/src/civa-old"), cwd, project),
+            Scope::Global
+        );
+        assert_eq!(
+            scope_of(&entry("ls", "/src/civa"), cwd, None),
+            Scope::Global
+        );
+    }
+
+    #[test]
+    fn test_scoped_order() {
+        let entries = vec![
+            entry("cargo test", "/src/civa/src"),
+            entry("cargo build", "/src/civa"),
+            entry("htop", "/home"),
+            entry("vim main.rs", "/src/civa/src"),
+            entry("cargo test", "/tmp"),
+            entry("git log", "/src/civa"),
+        ];
+
+        let order = scoped_order(
+            &entries,
+            Path::new("/src/civa/src"),
+            Some(Path::new("/src/civa")),
+        );
+        assert_eq!(order, vec![2, 1, 5, 0, 3]);
+
+        let order = scoped_order(&entries, Path::new("/home"), None);
+        assert_eq!(order, vec![1, 3, 4, 5, 2]);
+    }
+}
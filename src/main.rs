@@ -3,17 +3,23 @@ mod cli;
 mod command;
 mod config;
 mod env;
+mod history;
 mod status;
+mod vcs;
 
 use crate::config::PyConfRuntime;
 use clap::{App, Arg};
-use log::{info, LevelFilter};
+use log::{info, warn, LevelFilter};
 use pyo3::prelude::*;
 use rustyline::error::ReadlineError;
+use std::env::{current_dir, set_var, var};
+use std::time::{Instant, SystemTime};
 
 use crate::cli::Cli;
 use crate::command::executer::exec_sequentially;
 use crate::command::handler::handle_commands;
+use crate::history::expansion::{expand_history, expansion_events};
+use crate::history::HistoryEntry;
 
 #[macro_use]
 extern crate lazy_static;
@@ -75,6 +81,8 @@ fn main() {
 }
 
 fn main_loop(civa_opts: CivaOpts) {
+    increment_shell_level();
+
     info!("Init env manager");
 
     let mut cli = Cli::new();
@@ -84,20 +92,74 @@ fn main_loop(civa_opts: CivaOpts) {
     let p = civa_opts.pyconf_lib_path.as_str();
     let py_conf = PyConfRuntime::new(&gil, &p);
 
-    py_conf.exec_configs();
+    py_conf.exec_configs(&cli.context);
 
     loop {
+        if cli.context.share_history.get() {
+            cli.import_history();
+        }
+        cli.update_history_scope();
+
         let p = cli.update();
 
-        match cli.editor.readline(&p) {
+        match cli.readline(&p) {
             Ok(line) => {
-                cli.editor.add_history_entry(line.as_str());
+                let line = match expand_history_line(&mut cli, line) {
+                    Some(line) => line,
+                    None => continue,
+                };
+
+                // Abbreviations not yet expanded by typing a space
+                let line = cli
+                    .context
+                    .alias_system
+                    .borrow()
+                    .abbreviations()
+                    .expand_line(line.as_str());
+
+                // Lines ignored by the editor, e.g. starting with a space, are not recorded
+                let record = cli.editor.add_history_entry(line.as_str());
+                cli.context.session_lines.borrow_mut().push(line.clone());
+                let cwd = current_dir()
+                    .map(|dir| dir.to_string_lossy().into_owned())
+                    .unwrap_or_default();
+                let started = SystemTime::now();
+                let timer = Instant::now();
 
                 info!("Read input line {}", line);
                 let mut commands = handle_commands(line.as_str(), &cli.context);
 
                 info!("Executing commands sequentially: {:?}", commands);
-                exec_sequentially(&mut commands, &cli.context);
+                let history_len = cli.context.history.read().unwrap().entries().len();
+                let exit_status = exec_sequentially(&mut commands, &cli.context).code;
+                let duration = timer.elapsed();
+                cli.context.last_exit_status.set(Some(exit_status));
+                cli.context.last_duration.set(Some(duration));
+
+                if cli.context.history.read().unwrap().entries().len() != history_len {
+                    cli.reload_history();
+                }
+
+                // After running, so enabling incognito mode is not recorded either
+                let recorded = if record {
+                    cli.context.history_privacy.borrow().filter(line.as_str())
+                } else {
+                    None
+                };
+
+                if let Some(command) = recorded {
+                    let entry = HistoryEntry::new(
+                        command.as_str(),
+                        cwd.as_str(),
+                        started,
+                        duration,
+                        exit_status,
+                        &cli.context.session,
+                    );
+                    if let Err(err) = cli.context.history.write().unwrap().append(entry) {
+                        warn!("Could not record history: {}", err);
+                    }
+                }
             }
             // "Soft Reset" the shell
 
@@ -113,3 +175,45 @@ fn main_loop(civa_opts: CivaOpts) {
         }
     }
 }
+
+// Commands started from civa see one more level of nesting
+fn increment_shell_level() {
+    let level = var("SHLVL")
+        .ok()
+        .and_then(|level| level.parse::<u32>().ok())
+        .unwrap_or(0);
+
+    set_var("SHLVL", (level + 1).to_string());
+}
+
+// Applies history expansion (`!!`, `!$`, `^old^new`, ...) and echoes the
+// expanded line. `None` if there is nothing to run.
+fn expand_history_line(cli: &mut Cli, line: String) -> Option<String> {
+    let expansion = {
+        let history = cli.context.history.read().unwrap();
+        let session_lines = cli.context.session_lines.borrow();
+        let commands = expansion_events(history.entries(), &cli.context.session.id, &session_lines);
+        expand_history(line.as_str(), &commands)
+    };
+
+    match expansion {
+        Ok(Some(expansion)) => {
+            println!("{}", expansion.line);
+
+            if expansion.print_only {
+                cli.editor.add_history_entry(expansion.line.as_str());
+                cli.context
+                    .session_lines
+                    .borrow_mut()
+                    .push(expansion.line.clone());
+                return None;
+            }
+            Some(expansion.line)
+        }
+        Ok(None) => Some(line),
+        Err(err) => {
+            eprintln!("{}", err.message);
+            None
+        }
+    }
+}
@@ -0,0 +1,49 @@
+// git, read with libgit2 through the git plugin
+
+use std::path::{Path, PathBuf};
+
+use ::git::{GitCli, GitRepository, GitStatus, GitSymbols, Head};
+
+use super::VcsProvider;
+
+pub struct Git;
+
+impl Git {
+    fn status(root: &Path) -> Option<GitStatus> {
+        GitRepository::discover(root).map(|mut repository| repository.status())
+    }
+}
+
+impl VcsProvider for Git {
+    fn name(&self) -> &'static str {
+        "git"
+    }
+
+    fn detect(&self, dir: &Path) -> Option<PathBuf> {
+        GitCli::find_git_root(dir)
+    }
+
+    fn branch(&self, root: &Path) -> Option<String> {
+        Git::status(root).map(|status| match status.head {
+            Head::Branch(name) => name,
+            Head::Detached(id) => id,
+        })
+    }
+
+    fn is_dirty(&self, root: &Path) -> bool {
+        Git::status(root).map_or(false, |status| {
+            status.staged + status.modified + status.untracked + status.conflicted > 0
+        })
+    }
+
+    fn ahead_behind(&self, root: &Path) -> Option<(usize, usize)> {
+        Git::status(root).map(|status| (status.ahead, status.behind))
+    }
+
+    // The full status, with operations, file counts and stashes
+    fn segment(&self, root: &Path, symbols: &GitSymbols) -> String {
+        Git::status(root)
+            .map(|status| status.format(symbols))
+            .unwrap_or_default()
+    }
+}
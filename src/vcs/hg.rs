@@ -0,0 +1,68 @@
+// mercurial
+//
+// Commits ahead of and behind the default path are not shown, `hg incoming`
+// and `hg outgoing` ask the server. Without `hg` the branch is read from
+// `.hg/branch`, which mercurial only writes for other branches than default.
+
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use super::{find_root, run, VcsProvider};
+
+pub struct Mercurial;
+
+impl VcsProvider for Mercurial {
+    fn name(&self) -> &'static str {
+        "hg"
+    }
+
+    fn detect(&self, dir: &Path) -> Option<PathBuf> {
+        find_root(dir, ".hg")
+    }
+
+    fn branch(&self, root: &Path) -> Option<String> {
+        run("hg", &["branch"], root)
+            .map(|branch| String::from(branch.trim()))
+            .filter(|branch| !branch.is_empty())
+            .or_else(|| Some(branch_file(root)))
+    }
+
+    fn is_dirty(&self, root: &Path) -> bool {
+        // Modified, added, removed and missing files, untracked ones are left out
+        run(
+            "hg",
+            &["status", "--modified", "--added", "--removed", "--deleted"],
+            root,
+        )
+        .map_or(false, |status| !status.trim().is_empty())
+    }
+
+    fn ahead_behind(&self, _: &Path) -> Option<(usize, usize)> {
+        None
+    }
+}
+
+fn branch_file(root: &Path) -> String {
+    fs::read_to_string(root.join(".hg").join("branch"))
+        .map(|branch| String::from(branch.trim()))
+        .ok()
+        .filter(|branch| !branch.is_empty())
+        .unwrap_or_else(|| String::from("default"))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_branch_file() {
+        let temp = tempfile::tempdir().unwrap();
+        let dir = temp.path();
+        fs::create_dir_all(dir.join(".hg")).unwrap();
+
+        assert_eq!(branch_file(dir), "default");
+
+        fs::write(dir.join(".hg").join("branch"), "stable\n").unwrap();
+        assert_eq!(branch_file(dir), "stable");
+    }
+}
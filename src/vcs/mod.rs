@@ -0,0 +1,174 @@
+//
+// Version control systems shown by the `svn` component of the command bar
+//
+// Every system implements `VcsProvider`. The provider of a directory is
+// picked automatically, the one with the closest working copy wins, so a git
+// checkout inside a subversion working copy shows its git status.
+//
+// git is read with libgit2 (see the git plugin), mercurial and subversion
+// call their CLIs. When a CLI is not installed the segment degrades to what
+// can be read from the working copy itself, at least the name of the system.
+//
+
+pub mod git;
+pub mod hg;
+pub mod svn;
+
+use std::path::{Path, PathBuf};
+use std::process::{Command, Stdio};
+
+use ::git::GitSymbols;
+
+pub trait VcsProvider {
+    fn name(&self) -> &'static str;
+
+    // Root of the working copy containing `dir`
+    fn detect(&self, dir: &Path) -> Option<PathBuf>;
+
+    fn branch(&self, root: &Path) -> Option<String>;
+
+    fn is_dirty(&self, root: &Path) -> bool;
+
+    // Commits ahead of and behind the upstream, `None` where the system has
+    // no local notion of it
+    fn ahead_behind(&self, root: &Path) -> Option<(usize, usize)>;
+
+    // `default ⇡1 !`, the name of the system if the branch is unknown
+    fn segment(&self, root: &Path, symbols: &GitSymbols) -> String {
+        let mut parts = vec![self
+            .branch(root)
+            .unwrap_or_else(|| String::from(self.name()))];
+
+        if let Some((ahead, behind)) = self.ahead_behind(root) {
+            if ahead > 0 {
+                parts.push(format!("{}{}", symbols.ahead, ahead));
+            }
+            if behind > 0 {
+                parts.push(format!("{}{}", symbols.behind, behind));
+            }
+        }
+
+        if self.is_dirty(root) {
+            parts.push(symbols.modified.clone());
+        }
+
+        parts.join(" ")
+    }
+}
+
+fn providers() -> Vec<Box<dyn VcsProvider>> {
+    vec![
+        Box::new(git::Git),
+        Box::new(hg::Mercurial),
+        Box::new(svn::Subversion),
+    ]
+}
+
+// The provider with the closest working copy containing `dir` and its root.
+// On a tie the first one of `providers` is taken, e.g. git for git-svn.
+pub fn detect(dir: &Path) -> Option<(Box<dyn VcsProvider>, PathBuf)> {
+    let mut found: Option<(Box<dyn VcsProvider>, PathBuf)> = None;
+
+    for provider in providers() {
+        if let Some(root) = provider.detect(dir) {
+            let closer = found.as_ref().map_or(true, |(_, current)| {
+                root.starts_with(current) && root != *current
+            });
+
+            if closer {
+                found = Some((provider, root));
+            }
+        }
+    }
+
+    found
+}
+
+// Content of the `svn` component for `dir`, empty outside of a working copy
+pub fn segment(dir: &Path, symbols: &GitSymbols) -> String {
+    match detect(dir) {
+        Some((provider, root)) => provider.segment(&root, symbols),
+        None => String::new(),
+    }
+}
+
+// The closest directory containing `marker`, e.g. `.hg`
+fn find_root(dir: &Path, marker: &str) -> Option<PathBuf> {
+    dir.ancestors()
+        .find(|dir| dir.join(marker).is_dir())
+        .map(PathBuf::from)
+}
+
+// Standard output of a successful call, `None` if the program is missing or
+// fails
+fn run(program: &str, args: &[&str], dir: &Path) -> Option<String> {
+    let output = Command::new(program)
+        .args(args)
+        .current_dir(dir)
+        .stdin(Stdio::null())
+        .stderr(Stdio::null())
+        .output()
+        .ok()?;
+
+    if !output.status.success() {
+        return None;
+    }
+
+    String::from_utf8(output.stdout).ok()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::fs;
+
+    struct Stub;
+
+    impl VcsProvider for Stub {
+        fn name(&self) -> &'static str {
+            "stub"
+        }
+
+        fn detect(&self, _: &Path) -> Option<PathBuf> {
+            None
+        }
+
+        fn branch(&self, root: &Path) -> Option<String> {
+            root.to_str()
+                .filter(|root| !root.is_empty())
+                .map(String::from)
+        }
+
+        fn is_dirty(&self, _: &Path) -> bool {
+            true
+        }
+
+        fn ahead_behind(&self, _: &Path) -> Option<(usize, usize)> {
+            Some((2, 0))
+        }
+    }
+
+    #[test]
+    fn test_default_segment() {
+        let symbols = GitSymbols::default();
+
+        assert_eq!(Stub.segment(Path::new("trunk"), &symbols), "trunk ⇡2 !");
+        assert_eq!(Stub.segment(Path::new(""), &symbols), "stub ⇡2 !");
+    }
+
+    #[test]
+    fn test_detect_closest() {
+        let temp = tempfile::tempdir().unwrap();
+        let dir = temp.path().to_path_buf();
+        let checkout = dir.join("trunk").join("vendor");
+        fs::create_dir_all(dir.join(".svn")).unwrap();
+        fs::create_dir_all(checkout.join(".git")).unwrap();
+        fs::create_dir_all(checkout.join("src")).unwrap();
+
+        let (provider, root) = detect(&checkout.join("src")).unwrap();
+        assert_eq!((provider.name(), root), ("git", checkout.clone()));
+
+        let (provider, root) = detect(&dir.join("trunk")).unwrap();
+        assert_eq!((provider.name(), root), ("svn", dir.clone()));
+    }
+}
@@ -0,0 +1,79 @@
+// subversion
+//
+// The branch is derived from the path of the working copy in the repository,
+// following the trunk/branches/tags layout. A centralized system has no
+// commits ahead of or behind anything, `svn status -u` would ask the server.
+
+use std::path::{Path, PathBuf};
+
+use super::{run, VcsProvider};
+
+pub struct Subversion;
+
+impl VcsProvider for Subversion {
+    fn name(&self) -> &'static str {
+        "svn"
+    }
+
+    // Since subversion 1.7 only the root of a working copy has `.svn`, the
+    // outermost one is taken for older working copies
+    fn detect(&self, dir: &Path) -> Option<PathBuf> {
+        dir.ancestors()
+            .filter(|dir| dir.join(".svn").is_dir())
+            .last()
+            .map(PathBuf::from)
+    }
+
+    fn branch(&self, root: &Path) -> Option<String> {
+        run("svn", &["info", "--show-item", "relative-url"], root)
+            .map(|url| branch_of(url.trim()))
+            .filter(|branch| !branch.is_empty())
+    }
+
+    fn is_dirty(&self, root: &Path) -> bool {
+        // `-q` leaves out untracked files
+        run("svn", &["status", "-q"], root).map_or(false, |status| !status.trim().is_empty())
+    }
+
+    fn ahead_behind(&self, _: &Path) -> Option<(usize, usize)> {
+        None
+    }
+}
+
+// `^/project/branches/feature/src` is `feature`, `^/trunk` is `trunk`, other
+// layouts show the last directory
+fn branch_of(relative_url: &str) -> String {
+    let parts: Vec<&str> = relative_url
+        .trim_start_matches('^')
+        .split('/')
+        .filter(|part| !part.is_empty())
+        .collect();
+
+    for (i, part) in parts.iter().enumerate() {
+        match *part {
+            "trunk" => return String::from("trunk"),
+            "branches" | "tags" if i + 1 < parts.len() => return String::from(parts[i + 1]),
+            _ => {}
+        }
+    }
+
+    parts
+        .last()
+        .map(|part| String::from(*part))
+        .unwrap_or_default()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_branch_of() {
+        assert_eq!(branch_of("^/trunk"), "trunk");
+        assert_eq!(branch_of("^/project/trunk/src"), "trunk");
+        assert_eq!(branch_of("^/project/branches/feature/src"), "feature");
+        assert_eq!(branch_of("^/tags/1.0"), "1.0");
+        assert_eq!(branch_of("^/website"), "website");
+        assert_eq!(branch_of("^/"), "");
+    }
+}